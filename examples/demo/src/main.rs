@@ -1,6 +1,6 @@
 use std::env;
 
-use lifec::{editor::*, plugins::*, AttributeGraph, open, start, Runtime, Extension, World, DispatcherBuilder, System};
+use lifec::{editor::*, plugins::*, AttributeGraph, open, start, Runtime, TraceExporter, Extension, World, DispatcherBuilder, System};
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
 /// Demo app for the runtime, can swap projects by dropping a .runmd file in
@@ -111,9 +111,12 @@ impl Extension for Demo {
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    tracing_subscriber::fmt::Subscriber::builder()
-        .with_env_filter(EnvFilter::from_default_env())
-        .compact()
+    // If LIFEC_TRACE_FILE is set, spans are also written to that file as OTLP/JSON
+    let trace_exporter = env::var("LIFEC_TRACE_FILE").ok().map(TraceExporter::new);
+
+    tracing_subscriber::registry()
+        .with(fmt::layer().compact().with_filter(EnvFilter::from_default_env()))
+        .with(trace_exporter)
         .init();
 
     for (index, arg) in args.iter().enumerate() {
@@ -0,0 +1,81 @@
+use std::env;
+use std::process::exit;
+
+use lifec::testing::Snapshot;
+
+const USAGE: &str = "usage: lifec test [--update] [project.runmd]
+
+commands:
+    test    runs each call sequence w/ an `expect_output` block, and compares the output w/ the snapshot next to the project
+
+options:
+    --update    writes the output to the snapshot, accepting any changes";
+
+/// CLI for working w/ lifec projects w/o the editor
+fn main() {
+    let mut update = false;
+    let mut args = vec![];
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--update" => update = true,
+            _ => args.push(arg),
+        }
+    }
+
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    match args.as_slice() {
+        ["test"] => test(".runmd", update),
+        ["test", project_src] => test(project_src, update),
+        _ => {
+            eprintln!("{USAGE}");
+            exit(1);
+        }
+    }
+}
+
+/// Runs the snapshot tests of a project, exits w/ an error if a test failed
+fn test(project_src: &str, update: bool) {
+    let snapshot = match Snapshot::load(project_src) {
+        Some(snapshot) => snapshot,
+        None => {
+            eprintln!("could not load {project_src}");
+            exit(1);
+        }
+    };
+
+    let tests = snapshot.run();
+    let mut failed = 0;
+    for test in tests.iter() {
+        if test.timed_out {
+            failed += 1;
+            println!("test {} ... timed out", test.name);
+        } else if !test.unstubbed.is_empty() {
+            failed += 1;
+            println!("test {} ... FAILED, can't stub {}", test.name, test.unstubbed.join(", "));
+        } else if test.passed() {
+            println!("test {} ... ok", test.name);
+        } else if update {
+            println!("test {} ... updated", test.name);
+        } else {
+            failed += 1;
+            println!("test {} ... FAILED", test.name);
+            match &test.expected {
+                Some(expected) => println!("--- expected\n{expected}+++ actual\n{}", test.actual),
+                None => println!("--- missing from {:?}\n+++ actual\n{}", snapshot.snapshot_src(), test.actual),
+            }
+        }
+    }
+
+    if update {
+        if let Err(err) = snapshot.update(&tests) {
+            eprintln!("could not write {:?}, {err}", snapshot.snapshot_src());
+            exit(1);
+        }
+    }
+
+    println!("\n{} passed, {failed} failed", tests.len() - failed);
+    if failed > 0 {
+        exit(1);
+    }
+}
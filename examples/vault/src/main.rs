@@ -0,0 +1,68 @@
+use std::env;
+use std::process::exit;
+
+use lifec::plugins::{Vault, VaultErrors};
+
+const USAGE: &str = "usage: vault [--vault secrets.json] [--key secrets.key] <command>
+
+commands:
+    create              creates a new vault and key
+    set <name> <value>  encrypts and stores a secret
+    get <name>          decrypts a secret and prints it to stdout
+    remove <name>       removes a secret
+    list                lists the names of secrets in the vault
+    rotate              generates a new key and re-encrypts every secret";
+
+/// CLI for creating and editing a vault used by the `secure` plugin
+fn main() {
+    let mut vault_src = "secrets.json".to_string();
+    let mut key_src = "secrets.key".to_string();
+    let mut args = vec![];
+
+    let mut env_args = env::args().skip(1);
+    while let Some(arg) = env_args.next() {
+        match arg.as_str() {
+            "--vault" => vault_src = env_args.next().unwrap_or(vault_src),
+            "--key" => key_src = env_args.next().unwrap_or(key_src),
+            _ => args.push(arg),
+        }
+    }
+
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    let result = match args.as_slice() {
+        ["create"] => Vault::create(&vault_src, &key_src).and_then(|_| {
+            eprintln!("created {vault_src}, store {key_src} in a safe place");
+            Ok(())
+        }),
+        ["set", name, value] => Vault::open(&vault_src, &key_src).and_then(|mut vault| {
+            vault.set(name, value)?;
+            vault.save()
+        }),
+        ["get", name] => Vault::open(&vault_src, &key_src).and_then(|vault| {
+            println!("{}", vault.get_text(name)?);
+            Ok(())
+        }),
+        ["remove", name] => Vault::open(&vault_src, &key_src).and_then(|mut vault| {
+            if !vault.remove(name) {
+                return Err(VaultErrors::NotFound(name.to_string()));
+            }
+            vault.save()
+        }),
+        ["list"] => Vault::open(&vault_src, &key_src).and_then(|vault| {
+            for name in vault.names() {
+                println!("{name}");
+            }
+            Ok(())
+        }),
+        ["rotate"] => Vault::open(&vault_src, &key_src).and_then(|mut vault| vault.rotate(&key_src)),
+        _ => {
+            eprintln!("{USAGE}");
+            exit(1);
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("vault error: {:?}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,292 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+use tracing::{event, Level};
+
+use crate::plugins::{Project, ThunkContext};
+use crate::{AttributeGraph, ContentHash, Value};
+
+/// Resource w/ an on-disk cache of plugin results, keyed by the content of the plugin's inputs
+///
+/// Events opt-in by enabling `cache` in the block that configures them. Before the plugin runs, a key is computed from the plugin
+/// symbol, the block's attributes, and the content of any declared input files. If the key is in the cache, the plugin is skipped
+/// and the cached context and project are restored, otherwise the result is cached after the plugin completes w/o errors, i.e. in runmd,
+///
+/// ````runmd
+/// ``` build process
+/// add command .text cargo build
+/// add cache .enable
+/// define src cache_input .text src/lib.rs
+/// define manifest cache_input .text Cargo.toml
+/// ```
+/// ````
+///
+/// Changing an attribute or an input file changes the key, so stale entries are never returned. When the cache grows past
+/// it's size limit, the least recently used entries are evicted.
+///
+/// Caveat: Results w/ sensitive values are not cached, so that secrets are never written to disk.
+///
+#[derive(Debug, Clone)]
+pub struct ResultCache {
+    /// Directory cache entries are stored in
+    dir: PathBuf,
+    /// Total size of all entries before entries are evicted
+    max_bytes: u64,
+}
+
+impl Default for ResultCache {
+    fn default() -> Self {
+        Self::new(".lifec/cache", Self::DEFAULT_MAX_BYTES)
+    }
+}
+
+impl ResultCache {
+    /// Default size limit, 256 MiB
+    pub const DEFAULT_MAX_BYTES: u64 = 256 * 1024 * 1024;
+
+    /// Returns a new cache that stores entries in dir
+    pub fn new(dir: impl AsRef<Path>, max_bytes: u64) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            max_bytes,
+        }
+    }
+
+    /// Returns the directory entries are stored in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns true if the context opts in to caching
+    pub fn is_enabled(context: &ThunkContext) -> bool {
+        context.as_ref().is_enabled("cache").unwrap_or_default()
+    }
+
+    /// Returns the cache key for a plugin called w/ context, returns None if a declared input file can't be read
+    pub fn key(plugin_symbol: impl AsRef<str>, context: &ThunkContext) -> Option<ContentHash> {
+        let mut inputs = context.as_ref().clone();
+        // Set by the event runtime while the event is running
+        inputs.find_remove("thunk_symbol");
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"lifec.cache.v1");
+        hasher.update(plugin_symbol.as_ref().as_bytes());
+        hasher.update(inputs.content_hash_without_ids().as_bytes());
+
+        let mut files = Self::input_files(context);
+        files.sort();
+        for file in files {
+            match fs::read(&file) {
+                Ok(content) => {
+                    hasher.update(file.as_bytes());
+                    hasher.update(Sha256::digest(&content));
+                }
+                Err(err) => {
+                    event!(Level::WARN, "could not read cache input {file}, {err}");
+                    return None;
+                }
+            }
+        }
+
+        let mut key = [0; 32];
+        key.copy_from_slice(&hasher.finalize());
+        Some(ContentHash::from(key))
+    }
+
+    /// Returns the cached result for key, restored on to a clone of context
+    pub fn get(&self, key: &ContentHash, context: &ThunkContext) -> Option<ThunkContext> {
+        let path = self.entry_path(key);
+        let bytes = fs::read(&path).ok()?;
+
+        match Self::decode(&bytes) {
+            Some((graph, project)) => {
+                // Touch the entry so that eviction is least recently used, the entry is rewritten since setting
+                // the modified time directly needs a newer toolchain
+                if let Err(err) = fs::write(&path, &bytes) {
+                    event!(Level::DEBUG, "could not touch cache entry {key}, {err}");
+                }
+
+                // The cached graph was saved by a different run, so it's moved to the entity of this run
+                let mut graph = graph;
+                graph.set_parent_entity_id(context.as_ref().entity());
+
+                let mut restored = context.clone();
+                *restored.as_mut() = graph;
+                if let Some(project) = project {
+                    restored.project = Some(Project::from(project));
+                }
+                Some(restored)
+            }
+            None => {
+                event!(Level::WARN, "cache entry {key} is corrupt, invalidating");
+                self.invalidate(key);
+                None
+            }
+        }
+    }
+
+    /// Caches the result of a plugin, then evicts entries if the cache is over it's size limit
+    pub fn put(&self, key: &ContentHash, context: &ThunkContext) -> io::Result<()> {
+        let graph = context.as_ref();
+        let project = context.project.as_ref().map(|p| p.as_ref());
+
+        if graph.redacted() != *graph || project.map(|p| p.redacted() != *p).unwrap_or_default() {
+            event!(Level::DEBUG, "result has sensitive values, skipping cache");
+            return Ok(());
+        }
+
+        let graph = graph.to_bytes();
+        let mut entry = (graph.len() as u64).to_le_bytes().to_vec();
+        entry.extend(graph);
+        if let Some(project) = project {
+            entry.extend(project.to_bytes());
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.entry_path(key), entry)?;
+        self.evict()?;
+        Ok(())
+    }
+
+    /// Removes an entry, returns true if the entry existed
+    pub fn invalidate(&self, key: &ContentHash) -> bool {
+        fs::remove_file(self.entry_path(key)).is_ok()
+    }
+
+    /// Removes all entries
+    pub fn clear(&self) -> io::Result<()> {
+        for (path, ..) in self.entries()? {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the least recently used entries until the cache is within it's size limit, returns the number of bytes removed
+    pub fn evict(&self) -> io::Result<u64> {
+        let mut entries = self.entries()?;
+        let mut total = entries.iter().map(|(_, len, _)| len).sum::<u64>();
+        let mut removed = 0;
+
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, len, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+
+            fs::remove_file(&path)?;
+            total -= len;
+            removed += len;
+            event!(Level::DEBUG, "evicted cache entry {:?}", path);
+        }
+
+        Ok(removed)
+    }
+
+    /// Returns (path, len, modified) for each entry
+    fn entries(&self) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        if !self.dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut entries = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("lfcc") {
+                let metadata = fs::metadata(&path)?;
+                entries.push((path, metadata.len(), metadata.modified()?));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn entry_path(&self, key: &ContentHash) -> PathBuf {
+        self.dir.join(format!("{key}.lfcc"))
+    }
+
+    fn input_files(context: &ThunkContext) -> Vec<String> {
+        let graph = context.as_ref();
+        graph
+            .find_text("cache_input")
+            .into_iter()
+            .chain(
+                graph
+                    .find_symbol_values("cache_input")
+                    .into_iter()
+                    .filter_map(|(_, value)| match value {
+                        Value::TextBuffer(path) => Some(path),
+                        _ => None,
+                    }),
+            )
+            .collect()
+    }
+
+    fn decode(bytes: &[u8]) -> Option<(AttributeGraph, Option<AttributeGraph>)> {
+        if bytes.len() < 8 {
+            return None;
+        }
+        let (len, rest) = bytes.split_at(8);
+        let mut len_bytes = [0; 8];
+        len_bytes.copy_from_slice(len);
+
+        let len = u64::from_le_bytes(len_bytes);
+        if len > rest.len() as u64 {
+            return None;
+        }
+        let (graph, project) = rest.split_at(len as usize);
+
+        let graph = AttributeGraph::from_bytes(graph).ok()?;
+        let project = if project.is_empty() {
+            None
+        } else {
+            Some(AttributeGraph::from_bytes(project).ok()?)
+        };
+        Some((graph, project))
+    }
+}
+
+#[test]
+fn test_result_cache() {
+    let temp_dir = crate::testing::TempDir::new("cache");
+    let dir = temp_dir.join("cache");
+    let input = temp_dir.join("cache.input");
+    fs::write(&input, "hello").expect("written");
+
+    let mut context = ThunkContext::default();
+    context
+        .as_mut()
+        .with_bool("cache", true)
+        .with_text("command", "echo hello")
+        .with_text("cache_input", input.to_str().expect("path"));
+    assert!(ResultCache::is_enabled(&context));
+
+    let cache = ResultCache::new(&dir, ResultCache::DEFAULT_MAX_BYTES);
+    let key = ResultCache::key("process", &context).expect("key");
+    assert_eq!(ResultCache::key("process", &context), Some(key));
+    assert_ne!(ResultCache::key("println", &context), Some(key));
+    assert!(cache.get(&key, &context).is_none());
+
+    let mut result = context.clone();
+    result.as_mut().with_text("stdout", "hello");
+    cache.put(&key, &result).expect("cached");
+    let restored = cache.get(&key, &context).expect("hit");
+    assert_eq!(restored.as_ref().find_text("stdout"), Some("hello".to_string()));
+
+    // A hit is restored on to the entity of the context that's looking it up
+    let mut other = context.clone();
+    other.as_mut().set_parent_entity_id(7);
+    let restored = cache.get(&key, &other).expect("hit");
+    assert_eq!(restored.as_ref().entity(), 7);
+    assert_eq!(restored.as_ref().find_text("stdout"), Some("hello".to_string()));
+
+    // Changing an input file changes the key
+    fs::write(&input, "world").expect("written");
+    assert_ne!(ResultCache::key("process", &context), Some(key));
+
+    // Entries are evicted past the size limit
+    let cache = ResultCache::new(&dir, 0);
+    assert!(cache.evict().expect("evicted") > 0);
+    assert!(cache.get(&key, &context).is_none());
+}
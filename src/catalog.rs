@@ -3,6 +3,15 @@ use specs::prelude::*;
 use atlier::system::Value;
 use tracing::{event, Level};
 
+mod index;
+pub use index::Catalog;
+pub use index::CatalogSystem;
+pub use index::IndexKey;
+
+mod query;
+pub use query::CatalogQuery;
+pub use query::CatalogPredicate;
+
 /// A catalog is used to store and retrieve a collection of items. Typically data is retrieved using human-friendly
 /// concepts, 
 ///     such as tagging features or categories relevant to the data, 
@@ -144,4 +153,93 @@ where
             Value::Empty => unimplemented!("empty value is not implemented"),
         }
     }
-}
\ No newline at end of file
+}
+#[test]
+fn test_catalog() {
+    use crate::plugins::ThunkContext;
+    use crate::{AttributeGraph, RuntimeDispatcher};
+
+    #[derive(Debug, Default)]
+    struct Person {
+        name: String,
+        age: i32,
+    }
+
+    impl Item for Person {
+        fn visit_text(&mut self, name: impl AsRef<str>, value: impl AsRef<str>) {
+            if name.as_ref() == "name" {
+                self.name = value.as_ref().to_string();
+            }
+        }
+
+        fn visit_int(&mut self, name: impl AsRef<str>, value: i32) {
+            if name.as_ref() == "age" {
+                self.age = value;
+            }
+        }
+    }
+
+    let mut world = World::new();
+    world.register::<ThunkContext>();
+
+    let mut people = vec![];
+    for (name, age, tagged) in [("alice", 34, true), ("bob", 17, false), ("bella", 52, true)] {
+        let entity = world.entities().create();
+        let mut graph = AttributeGraph::from(entity.id());
+        graph.with_text("name", name).with_int("age", age);
+        if tagged {
+            graph.dispatch_mut("define active tag").expect("defined");
+        }
+        world
+            .write_component()
+            .insert(entity, ThunkContext::from(graph))
+            .ok();
+        people.push(entity);
+    }
+    world.maintain();
+
+    let mut catalog = Catalog::build::<ThunkContext>(&world);
+    assert_eq!(catalog.query().eq("name", "bob").entities(), vec![people[1]]);
+    assert_eq!(catalog.query().range("age", 18, 65).entities().len(), 2);
+    assert_eq!(
+        catalog.query().prefix("name", "b").sort_by_desc("age").entities(),
+        vec![people[2], people[1]]
+    );
+
+    let active = catalog
+        .query()
+        .has_tag("active")
+        .sort_by("age")
+        .limit(1)
+        .items::<Person>();
+    assert_eq!(active.len(), 1);
+    assert_eq!(active[0].1.name, "alice");
+    assert_eq!(active[0].1.age, 34);
+
+    // Updating an entity replaces its indexed values
+    let mut graph = AttributeGraph::from(people[1].id());
+    graph.with_text("name", "bob").with_int("age", 18);
+    catalog.update(people[1], &graph);
+    assert_eq!(catalog.query().range("age", 18, 65).entities().len(), 3);
+    assert!(catalog.query().eq("age", 17).entities().is_empty());
+
+    catalog.remove(people[0]);
+    assert_eq!(catalog.query().has_tag("active").entities(), vec![people[2]]);
+
+    // The catalog system indexes existing contexts on setup, and then only contexts that change
+    let mut system = CatalogSystem::default();
+    System::setup(&mut system, &mut world);
+    assert_eq!(world.read_resource::<Catalog>().query().range("age", 18, 65).entities().len(), 2);
+
+    if let Some(context) = world.write_component::<ThunkContext>().get_mut(people[1]) {
+        context.as_mut().with_int("age", 20);
+    }
+    world.delete_entity(people[2]).expect("deleted");
+    world.maintain();
+    system.run_now(&world);
+
+    let catalog = world.read_resource::<Catalog>();
+    assert_eq!(catalog.query().range("age", 18, 65).entities(), vec![people[0], people[1]]);
+    assert!(!catalog.contains(people[2]));
+    assert_eq!(catalog.query().has_tag("active").entities(), vec![people[0]]);
+}
@@ -0,0 +1,328 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+
+use atlier::system::Value;
+use specs::prelude::*;
+use specs::world::Index;
+
+use crate::plugins::ThunkContext;
+use crate::AttributeGraph;
+
+use super::CatalogQuery;
+
+/// Key used by catalog indexes, ordered so that range and prefix look-ups can be answered from the index
+///
+/// Ints and floats are both indexed as numbers, text and symbols are both indexed as text. For int and float
+/// ranges the value portion of the range is indexed.
+///
+#[derive(Debug, Clone)]
+pub enum IndexKey {
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl IndexKey {
+    /// Returns a key for a value, if the value can be indexed
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(b) => Some(IndexKey::Bool(*b)),
+            Value::Int(i) => Some(IndexKey::Number(*i as f64)),
+            Value::IntRange(i, ..) => Some(IndexKey::Number(*i as f64)),
+            Value::Float(f) => Some(IndexKey::Number(*f as f64)),
+            Value::FloatRange(f, ..) => Some(IndexKey::Number(*f as f64)),
+            Value::TextBuffer(t) => Some(IndexKey::Text(t.to_string())),
+            Value::Symbol(s) => Some(IndexKey::Text(s.to_string())),
+            _ => None,
+        }
+    }
+
+    /// Returns the text if this is a text key
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            IndexKey::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            IndexKey::Bool(_) => 0,
+            IndexKey::Number(_) => 1,
+            IndexKey::Text(_) => 2,
+        }
+    }
+}
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+impl Hash for IndexKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            IndexKey::Bool(b) => b.hash(state),
+            IndexKey::Number(n) => n.to_bits().hash(state),
+            IndexKey::Text(t) => t.hash(state),
+        }
+    }
+}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (IndexKey::Bool(a), IndexKey::Bool(b)) => a.cmp(b),
+            (IndexKey::Number(a), IndexKey::Number(b)) => a.total_cmp(b),
+            (IndexKey::Text(a), IndexKey::Text(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl From<bool> for IndexKey {
+    fn from(b: bool) -> Self {
+        IndexKey::Bool(b)
+    }
+}
+
+impl From<i32> for IndexKey {
+    fn from(i: i32) -> Self {
+        IndexKey::Number(i as f64)
+    }
+}
+
+impl From<f32> for IndexKey {
+    fn from(f: f32) -> Self {
+        IndexKey::Number(f as f64)
+    }
+}
+
+impl From<&str> for IndexKey {
+    fn from(t: &str) -> Self {
+        IndexKey::Text(t.to_string())
+    }
+}
+
+impl From<String> for IndexKey {
+    fn from(t: String) -> Self {
+        IndexKey::Text(t)
+    }
+}
+
+/// Catalog of attribute values across entities, w/ secondary indexes for look-ups
+///
+/// Each stable attribute owned by an entity's graph is indexed by name and value. An entity is tagged by
+/// defining a `tag` attribute, i.e. in runmd,
+///
+/// ```runmd
+/// define release tag
+/// ```
+///
+/// Sensitive attributes and resolved secrets are never indexed.
+///
+/// The runtime maintains a catalog of `ThunkContext` components as a resource, see `CatalogSystem`.
+///
+#[derive(Default, Debug, Clone)]
+pub struct Catalog {
+    /// Indexed values for each entity, by attribute name
+    values: HashMap<Entity, BTreeMap<String, Value>>,
+    /// Tags for each entity
+    entity_tags: HashMap<Entity, BTreeSet<String>>,
+    /// Secondary indexes, attribute name -> value -> entities
+    indexes: HashMap<String, BTreeMap<IndexKey, BTreeSet<Entity>>>,
+    /// Tag index, tag -> entities
+    tags: HashMap<String, BTreeSet<Entity>>,
+    /// Indexed entities by id, storage events only include the id of the entity
+    ids: HashMap<Index, Entity>,
+}
+
+impl Catalog {
+    /// Returns a catalog of all entities w/ component C
+    pub fn build<C>(world: &World) -> Self
+    where
+        C: Component + AsRef<AttributeGraph>,
+    {
+        let mut catalog = Catalog::default();
+        catalog.index_all(&world.entities(), &world.read_component::<C>());
+        catalog
+    }
+
+    /// Indexes all entities w/ component C
+    pub fn index_all<C>(&mut self, entities: &Entities, storage: &ReadStorage<C>)
+    where
+        C: Component + AsRef<AttributeGraph>,
+    {
+        for (entity, component) in (entities, storage).join() {
+            self.update(entity, component.as_ref());
+        }
+    }
+
+    /// Indexes the graph for an entity, replacing any previously indexed values
+    pub fn update(&mut self, entity: Entity, graph: &AttributeGraph) {
+        self.remove(entity);
+
+        let mut values = BTreeMap::default();
+        let mut entity_tags = BTreeSet::default();
+        for attr in graph
+            .iter_attributes()
+            .filter(|a| a.id() == graph.entity())
+            .filter(|a| !graph.is_sensitive(a.name()))
+            .filter(|a| *a.value() != Value::Empty)
+        {
+            if let Some(tag) = attr.name().strip_suffix("::tag") {
+                self.tags
+                    .entry(tag.to_string())
+                    .or_default()
+                    .insert(entity);
+                entity_tags.insert(tag.to_string());
+                continue;
+            }
+
+            if let Some(key) = IndexKey::from_value(attr.value()) {
+                self.indexes
+                    .entry(attr.name().to_string())
+                    .or_default()
+                    .entry(key)
+                    .or_default()
+                    .insert(entity);
+            }
+
+            values.insert(attr.name().to_string(), attr.value().clone());
+        }
+
+        self.values.insert(entity, values);
+        self.entity_tags.insert(entity, entity_tags);
+        self.ids.insert(entity.id(), entity);
+    }
+
+    /// Removes an entity from the catalog
+    pub fn remove(&mut self, entity: Entity) {
+        if self.ids.get(&entity.id()) == Some(&entity) {
+            self.ids.remove(&entity.id());
+        }
+
+        if let Some(values) = self.values.remove(&entity) {
+            for (name, value) in values {
+                if let (Some(index), Some(key)) =
+                    (self.indexes.get_mut(&name), IndexKey::from_value(&value))
+                {
+                    if let Some(entities) = index.get_mut(&key) {
+                        entities.remove(&entity);
+                        if entities.is_empty() {
+                            index.remove(&key);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(tags) = self.entity_tags.remove(&entity) {
+            for tag in tags {
+                if let Some(entities) = self.tags.get_mut(&tag) {
+                    entities.remove(&entity);
+                }
+            }
+        }
+    }
+
+    /// Removes the entity w/ id from the catalog
+    fn remove_id(&mut self, id: Index) {
+        if let Some(entity) = self.ids.get(&id).copied() {
+            self.remove(entity);
+        }
+    }
+
+    /// Returns true if the entity is in the catalog
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.values.contains_key(&entity)
+    }
+
+    /// Returns an iterator over all entities in the catalog
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.values.keys().cloned()
+    }
+
+    /// Returns the indexed value of an attribute for an entity
+    pub fn value(&self, entity: Entity, name: impl AsRef<str>) -> Option<&Value> {
+        self.values
+            .get(&entity)
+            .and_then(|values| values.get(name.as_ref()))
+    }
+
+    /// Returns all indexed values for an entity
+    pub fn values(&self, entity: Entity) -> Option<&BTreeMap<String, Value>> {
+        self.values.get(&entity)
+    }
+
+    /// Returns the index for an attribute name
+    pub fn index(&self, name: impl AsRef<str>) -> Option<&BTreeMap<IndexKey, BTreeSet<Entity>>> {
+        self.indexes.get(name.as_ref())
+    }
+
+    /// Returns the entities tagged w/ tag
+    pub fn tagged(&self, tag: impl AsRef<str>) -> BTreeSet<Entity> {
+        self.tags.get(tag.as_ref()).cloned().unwrap_or_default()
+    }
+
+    /// Returns a new query for this catalog
+    pub fn query(&self) -> CatalogQuery {
+        CatalogQuery::new(self)
+    }
+}
+
+/// System that keeps the world's `Catalog` resource up to date w/ the world's `ThunkContext` components,
+///
+/// Contexts are indexed when the system is set up, after that only contexts that were inserted, modified or
+/// removed since the last run are re-indexed, from the storage's component events.
+///
+#[derive(Default)]
+pub struct CatalogSystem(Option<ReaderId<ComponentEvent>>);
+
+impl<'a> System<'a> for CatalogSystem {
+    type SystemData = (
+        Entities<'a>,
+        Write<'a, Catalog>,
+        ReadStorage<'a, ThunkContext>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.0 = Some(world.write_storage::<ThunkContext>().register_reader());
+
+        let (entities, mut catalog, contexts) = Self::SystemData::fetch(world);
+        catalog.index_all(&entities, &contexts);
+    }
+
+    fn run(&mut self, (entities, mut catalog, contexts): Self::SystemData) {
+        if let Some(reader) = self.0.as_mut() {
+            let mut updated = BitSet::new();
+            for event in contexts.channel().read(reader) {
+                match event {
+                    ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) => {
+                        updated.add(*id);
+                    }
+                    ComponentEvent::Removed(id) => {
+                        updated.remove(*id);
+                        catalog.remove_id(*id);
+                    }
+                }
+            }
+
+            for (entity, context, _) in (&entities, &contexts, &updated).join() {
+                catalog.update(entity, context.as_ref());
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use specs::Entity;
+
+use super::{Catalog, IndexKey, Item};
+
+/// Predicate for a catalog query
+///
+#[derive(Debug, Clone)]
+pub enum CatalogPredicate {
+    /// Value of the attribute equals the key
+    Eq(String, IndexKey),
+    /// Value of the attribute is between min and max, inclusive
+    Range(String, IndexKey, IndexKey),
+    /// Text value of the attribute starts w/ the prefix
+    Prefix(String, String),
+    /// Entity is tagged w/ the tag
+    HasTag(String),
+}
+
+/// Query builder for a catalog,
+///
+/// Each predicate is answered from the catalog's indexes, and the results are intersected.
+///
+/// ```ignore
+/// let adults = catalog.query()
+///     .range("age", 18, 65)
+///     .has_tag("active")
+///     .sort_by("name")
+///     .limit(10)
+///     .entities();
+/// ```
+///
+pub struct CatalogQuery<'a> {
+    catalog: &'a Catalog,
+    predicates: Vec<CatalogPredicate>,
+    sort: Option<(String, bool)>,
+    limit: Option<usize>,
+    select: Vec<String>,
+}
+
+impl<'a> CatalogQuery<'a> {
+    /// Returns a new query for catalog
+    pub fn new(catalog: &'a Catalog) -> Self {
+        Self {
+            catalog,
+            predicates: vec![],
+            sort: None,
+            limit: None,
+            select: vec![],
+        }
+    }
+
+    /// Matches entities where the attribute equals value
+    pub fn eq(mut self, name: impl AsRef<str>, value: impl Into<IndexKey>) -> Self {
+        self.predicates
+            .push(CatalogPredicate::Eq(name.as_ref().to_string(), value.into()));
+        self
+    }
+
+    /// Matches entities where the attribute is between min and max, inclusive
+    pub fn range(
+        mut self,
+        name: impl AsRef<str>,
+        min: impl Into<IndexKey>,
+        max: impl Into<IndexKey>,
+    ) -> Self {
+        self.predicates.push(CatalogPredicate::Range(
+            name.as_ref().to_string(),
+            min.into(),
+            max.into(),
+        ));
+        self
+    }
+
+    /// Matches entities where the text attribute starts w/ prefix
+    pub fn prefix(mut self, name: impl AsRef<str>, prefix: impl AsRef<str>) -> Self {
+        self.predicates.push(CatalogPredicate::Prefix(
+            name.as_ref().to_string(),
+            prefix.as_ref().to_string(),
+        ));
+        self
+    }
+
+    /// Matches entities tagged w/ tag
+    pub fn has_tag(mut self, tag: impl AsRef<str>) -> Self {
+        self.predicates
+            .push(CatalogPredicate::HasTag(tag.as_ref().to_string()));
+        self
+    }
+
+    /// Sorts results by an attribute, ascending, entities w/o the attribute are last
+    pub fn sort_by(mut self, name: impl AsRef<str>) -> Self {
+        self.sort = Some((name.as_ref().to_string(), false));
+        self
+    }
+
+    /// Sorts results by an attribute, descending, entities w/o the attribute are last
+    pub fn sort_by_desc(mut self, name: impl AsRef<str>) -> Self {
+        self.sort = Some((name.as_ref().to_string(), true));
+        self
+    }
+
+    /// Limits the number of results
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Selects the attributes that are visited when materializing items, if nothing is selected
+    /// every indexed attribute is visited
+    pub fn select(mut self, name: impl AsRef<str>) -> Self {
+        self.select.push(name.as_ref().to_string());
+        self
+    }
+
+    /// Returns the entities that match the query
+    pub fn entities(&self) -> Vec<Entity> {
+        let mut matches: Option<BTreeSet<Entity>> = None;
+
+        for predicate in self.predicates.iter() {
+            let found = self.find(predicate);
+            matches = Some(match matches {
+                Some(matches) => matches.intersection(&found).cloned().collect(),
+                None => found,
+            });
+        }
+
+        let mut entities = match matches {
+            Some(matches) => matches.into_iter().collect::<Vec<_>>(),
+            None => {
+                let mut all = self.catalog.entities().collect::<Vec<_>>();
+                all.sort();
+                all
+            }
+        };
+
+        if let Some((name, descending)) = self.sort.as_ref() {
+            entities.sort_by(|a, b| {
+                let a = self.catalog.value(*a, name).and_then(IndexKey::from_value);
+                let b = self.catalog.value(*b, name).and_then(IndexKey::from_value);
+                match (a, b) {
+                    (Some(a), Some(b)) if *descending => b.cmp(&a),
+                    (Some(a), Some(b)) => a.cmp(&b),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                }
+            });
+        }
+
+        if let Some(limit) = self.limit {
+            entities.truncate(limit);
+        }
+
+        entities
+    }
+
+    /// Returns items materialized from the indexed values of each matching entity
+    pub fn items<I>(&self) -> Vec<(Entity, I)>
+    where
+        I: Item + Default,
+    {
+        self.entities()
+            .into_iter()
+            .map(|entity| {
+                let mut item = I::default();
+                if let Some(values) = self.catalog.values(entity) {
+                    for (name, value) in values
+                        .iter()
+                        .filter(|(name, _)| self.select.is_empty() || self.select.contains(name))
+                    {
+                        item.visit(name, value);
+                    }
+                }
+                (entity, item)
+            })
+            .collect()
+    }
+
+    /// Returns the entities that match a single predicate
+    fn find(&self, predicate: &CatalogPredicate) -> BTreeSet<Entity> {
+        let mut found = BTreeSet::default();
+        match predicate {
+            CatalogPredicate::Eq(name, key) => {
+                if let Some(entities) = self.catalog.index(name).and_then(|i| i.get(key)) {
+                    found.extend(entities);
+                }
+            }
+            CatalogPredicate::Range(name, min, max) => {
+                if let Some(index) = self.catalog.index(name) {
+                    if min <= max {
+                        for (_, entities) in index.range(min.clone()..=max.clone()) {
+                            found.extend(entities);
+                        }
+                    }
+                }
+            }
+            CatalogPredicate::Prefix(name, prefix) => {
+                if let Some(index) = self.catalog.index(name) {
+                    for (key, entities) in index.range(IndexKey::Text(prefix.to_string())..) {
+                        match key.text() {
+                            Some(text) if text.starts_with(prefix.as_str()) => {
+                                found.extend(entities);
+                            }
+                            _ => break,
+                        }
+                    }
+                }
+            }
+            CatalogPredicate::HasTag(tag) => {
+                found = self.catalog.tagged(tag);
+            }
+        }
+        found
+    }
+}
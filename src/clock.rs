@@ -0,0 +1,181 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+use tokio::sync::watch;
+
+/// Clock resource that plugins read the current time from,
+///
+/// By default the clock is the system clock. A virtual clock only moves when it's advanced, so tests that depend on time can
+/// control it w/o sleeping,
+///
+/// ```
+/// use lifec::Clock;
+///
+/// let clock = Clock::virtual_at(Clock::epoch());
+/// clock.advance(std::time::Duration::from_secs(60));
+/// assert_eq!(Some(clock.now()), Clock::from_timestamp(60));
+/// ```
+///
+/// A deterministic clock is a virtual clock that advances to the deadline when a task sleeps, so runs that sleep complete right away,
+/// and always observe the same times.
+///
+/// The clock is a cheap handle to shared state, so clones of a virtual clock all see the same time.
+///
+#[derive(Clone, Default)]
+pub struct Clock(Option<Arc<VirtualTime>>);
+
+/// Shared state of a virtual clock
+struct VirtualTime {
+    /// Current time of the clock
+    now: Mutex<DateTime<Utc>>,
+    /// Notifies sleeping tasks each time the clock is advanced
+    advanced: watch::Sender<DateTime<Utc>>,
+    /// If true, sleeping advances the clock to the deadline
+    auto_advance: bool,
+}
+
+impl std::fmt::Debug for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Clock")
+            .field("is_virtual", &self.is_virtual())
+            .field("now", &self.now())
+            .finish()
+    }
+}
+
+impl Clock {
+    /// Returns the system clock
+    pub fn system() -> Self {
+        Self(None)
+    }
+
+    /// Returns a virtual clock, starting at start
+    pub fn virtual_at(start: DateTime<Utc>) -> Self {
+        Self::new_virtual(start, false)
+    }
+
+    /// Returns a virtual clock, starting at start, that advances to the deadline when a task sleeps
+    pub fn deterministic(start: DateTime<Utc>) -> Self {
+        Self::new_virtual(start, true)
+    }
+
+    fn new_virtual(start: DateTime<Utc>, auto_advance: bool) -> Self {
+        let (advanced, _) = watch::channel(start);
+        Self(Some(Arc::new(VirtualTime {
+            now: Mutex::new(start),
+            advanced,
+            auto_advance,
+        })))
+    }
+
+    /// Returns true if this is a virtual clock
+    pub fn is_virtual(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Returns the current time
+    pub fn now(&self) -> DateTime<Utc> {
+        match &self.0 {
+            Some(virtual_time) => *virtual_time.now.lock().expect("not poisoned"),
+            None => Utc::now(),
+        }
+    }
+
+    /// Returns time in the local timezone, a virtual clock uses UTC so that the result doesn't depend on the machine it runs on
+    pub fn to_local(&self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        if self.is_virtual() {
+            time.with_timezone(&FixedOffset::east_opt(0).expect("valid offset"))
+        } else {
+            let local = time.with_timezone(&Local);
+            local.with_timezone(local.offset())
+        }
+    }
+
+    /// Advances a virtual clock, waking any tasks that are sleeping until a time that has passed
+    ///
+    /// Caveat: The system clock can't be advanced, so this is a no-op for the system clock
+    ///
+    pub fn advance(&self, duration: Duration) {
+        if let Some(virtual_time) = &self.0 {
+            let mut now = virtual_time.now.lock().expect("not poisoned");
+            *now = *now + chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::zero());
+            virtual_time.advanced.send(*now).ok();
+        }
+    }
+
+    /// Sets the time of a virtual clock, no-op for the system clock
+    pub fn set(&self, time: DateTime<Utc>) {
+        if let Some(virtual_time) = &self.0 {
+            *virtual_time.now.lock().expect("not poisoned") = time;
+            virtual_time.advanced.send(time).ok();
+        }
+    }
+
+    /// Waits until the clock reaches deadline, a virtual clock waits until it's advanced past the deadline
+    pub async fn sleep_until(&self, deadline: DateTime<Utc>) {
+        match &self.0 {
+            Some(virtual_time) if virtual_time.auto_advance => {
+                let mut now = virtual_time.now.lock().expect("not poisoned");
+                if *now < deadline {
+                    *now = deadline;
+                    virtual_time.advanced.send(deadline).ok();
+                }
+            }
+            Some(virtual_time) => {
+                // Subscribe before reading the time, so that an advance in between isn't missed
+                let mut advanced = virtual_time.advanced.subscribe();
+                while self.now() < deadline {
+                    if advanced.changed().await.is_err() {
+                        return;
+                    }
+                }
+            }
+            None => {
+                let remaining = deadline - Utc::now();
+                if let Ok(remaining) = remaining.to_std() {
+                    tokio::time::sleep(remaining).await;
+                }
+            }
+        }
+    }
+
+    /// Returns the time for a unix timestamp in seconds, or None if the timestamp is out of range
+    pub fn from_timestamp(secs: i64) -> Option<DateTime<Utc>> {
+        Utc.timestamp_opt(secs, 0).single()
+    }
+
+    /// Returns the unix epoch, which deterministic clocks start at
+    pub fn epoch() -> DateTime<Utc> {
+        DateTime::<Utc>::from(std::time::UNIX_EPOCH)
+    }
+}
+
+#[test]
+fn test_clock() {
+    assert_eq!(Clock::from_timestamp(0), Some(Clock::epoch()));
+    assert!(Clock::from_timestamp(i64::MAX).is_none());
+
+    let clock = Clock::virtual_at(Clock::epoch());
+    assert!(clock.is_virtual());
+    assert!(!Clock::default().is_virtual());
+    assert_eq!(clock.to_local(clock.now()).to_string(), "1970-01-01 00:00:00 +00:00");
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let sleeping = clock.clone();
+    let handle = runtime.spawn(async move {
+        sleeping.sleep_until(Clock::from_timestamp(10).expect("in range")).await;
+        sleeping.now()
+    });
+
+    clock.advance(Duration::from_secs(5));
+    assert!(!handle.is_finished());
+    clock.advance(Duration::from_secs(5));
+
+    let woke = runtime.block_on(handle).expect("woke");
+    assert_eq!(Some(woke), Clock::from_timestamp(10));
+
+    let clock = Clock::deterministic(Clock::epoch());
+    runtime.block_on(clock.sleep_until(Clock::from_timestamp(30).expect("in range")));
+    assert_eq!(Some(clock.now()), Clock::from_timestamp(30));
+}
@@ -5,6 +5,7 @@ use imgui::MenuItem;
 use imgui::TableFlags;
 use imgui::TreeNodeFlags;
 use imgui::Ui;
+use std::collections::HashMap;
 
 /// List-layout widget for thunk_context's
 #[derive(Component)]
@@ -129,10 +130,17 @@ where
         )
     }
 
+    /// Returns a view w/ an attribute table for the thunk context,
+    /// 
+    /// Edits are recorded in the `EditHistory` resource, while the table is hovered ctrl+z will undo the last edit, and ctrl+y redo it
+    /// 
     pub fn edit_attr_table() -> Self {
         List::<Item>(
             |context, item, world, ui| {
-                context.as_mut().edit_attr_table(ui);
+                ui.group(|| {
+                    context.as_mut().edit_attr_table(ui);
+                });
+                EditHistory::track(world, context.as_mut(), ui);
                 item.on_ui(world, ui);
             },
             None,
@@ -142,6 +150,10 @@ where
         )
     }
 
+    /// Returns a view for editing each block in a sequence,
+    /// 
+    /// Edits are recorded in the `EditHistory` resource, while the block is hovered ctrl+z will undo the last edit, and ctrl+y redo it
+    /// 
     pub fn edit_block_view(sequence: Option<Sequence>) -> Self {
         List::<Item>(
             |context, item, world, ui| {
@@ -185,28 +197,31 @@ where
                     let mut current_id = context.as_ref().entity();
 
                     let clone = context.as_ref().clone();
-                    for attr in context.as_mut().iter_mut_attributes() {
-                        if current_id != attr.id() {
-                            ui.new_line();
-                            if let Some(next_block) = clone.find_imported_graph(attr.id()) {
-                                let thunk_symbol =
-                                    next_block.find_text("thunk_symbol").unwrap_or_default();
-                                let block_symbol =
-                                    next_block.find_text("block_symbol").unwrap_or_default();
-                                ui.text(format!("{} - {}", thunk_symbol, block_symbol));
+                    ui.group(|| {
+                        for attr in context.as_mut().iter_mut_attributes() {
+                            if current_id != attr.id() {
+                                ui.new_line();
+                                if let Some(next_block) = clone.find_imported_graph(attr.id()) {
+                                    let thunk_symbol =
+                                        next_block.find_text("thunk_symbol").unwrap_or_default();
+                                    let block_symbol =
+                                        next_block.find_text("block_symbol").unwrap_or_default();
+                                    ui.text(format!("{} - {}", thunk_symbol, block_symbol));
+                                }
+                                current_id = attr.id();
                             }
-                            current_id = attr.id();
-                        }
 
-                        if attr.is_stable() {
-                            attr.edit_value(
-                                format!("{} {}:{:#04x}", attr.name(), attr.id(), item_index as u16),
-                                ui,
-                            );
-                        } else if edit_transient {
-                            attr.edit_ui(ui);
+                            if attr.is_stable() {
+                                attr.edit_value(
+                                    format!("{} {}:{:#04x}", attr.name(), attr.id(), item_index as u16),
+                                    ui,
+                                );
+                            } else if edit_transient {
+                                attr.edit_ui(ui);
+                            }
                         }
-                    }
+                    });
+                    EditHistory::track(world, context.as_mut(), ui);
                     item.on_ui(world, ui);
                     ui.text(format!("stable: {}", context.as_ref().is_stable()));
                     ui.separator();
@@ -253,6 +268,59 @@ where
     }
 }
 
+/// Resource w/ the undo/redo history of graphs edited from a list, by entity id
+/// 
+/// A step is recorded when an edit finishes, i.e. when an input loses focus, w/ the graph as it was when the edit started.
+/// 
+#[derive(Default)]
+pub struct EditHistory(
+    /// History of each graph
+    HashMap<u32, History<AttributeGraph>>,
+    /// Graph before the current edit, by entity id
+    HashMap<u32, AttributeGraph>,
+);
+
+impl EditHistory {
+    /// Returns the history for a graph
+    pub fn history(&self, entity: u32) -> Option<&History<AttributeGraph>> {
+        self.0.get(&entity)
+    }
+
+    /// Returns the history for a graph as mutable
+    pub fn history_mut(&mut self, entity: u32) -> &mut History<AttributeGraph> {
+        self.0.entry(entity).or_default()
+    }
+
+    /// Records a step when an edit to the last item finishes, and handles undo shortcuts if the last item is hovered
+    /// 
+    /// The graph is only cloned when an edit starts or finishes, so that idle frames don't clone or diff the graph.
+    /// 
+    fn track(world: &World, graph: &mut AttributeGraph, ui: &Ui) {
+        if let Some(mut edit_history) = world.try_fetch_mut::<EditHistory>() {
+            let EditHistory(histories, snapshots) = &mut *edit_history;
+            let entity = graph.entity();
+
+            // Focusing an input doesn't change the graph, so the snapshot is refreshed to include changes made
+            // outside of the editor, sliders can change the graph as they are activated so their snapshot is kept
+            if !snapshots.contains_key(&entity) || (ui.is_item_activated() && !ui.is_item_edited()) {
+                snapshots.insert(entity, graph.clone());
+            }
+
+            let history = histories.entry(entity).or_default();
+            if ui.is_item_deactivated_after_edit() {
+                if let Some(previous) = snapshots.insert(entity, graph.clone()) {
+                    history.edit(previous, graph);
+                    history.seal();
+                }
+            }
+
+            if ui.is_item_hovered() && history.edit_shortcuts(graph, ui) {
+                snapshots.insert(entity, graph.clone());
+            }
+        }
+    }
+}
+
 impl<Item> Default for List<Item>
 where
     Item: Extension + Component,
@@ -271,6 +339,7 @@ where
         world.register::<ThunkContext>();
         world.register::<Item>();
         world.register::<List<Item>>();
+        world.entry::<EditHistory>().or_insert_with(EditHistory::default);
 
         Item::configure_app_world(world);
     }
@@ -21,6 +21,7 @@ pub use task::Task;
 
 mod list;
 pub use list::List;
+pub use list::EditHistory;
 
 pub use specs::prelude::WorldExt;
 pub use specs::prelude::Builder;
@@ -29,10 +30,25 @@ pub use atlier::system::Attribute;
 pub use atlier::system::Value;
 pub use atlier::system::WindowEvent;
 
-/// Generate a unique title
-pub fn unique_title(title: impl AsRef<str>) -> String {
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
+/// Generate a unique title, w/ randomness from the world's Random resource, see `Random::unique_title`
+///
+/// If the world doesn't have a Random resource yet, randomness is read from the thread rng.
+///
+pub fn unique_title(world: &specs::World, title: impl AsRef<str>) -> String {
+    world
+        .try_fetch::<crate::Random>()
+        .map(|random| random.clone())
+        .unwrap_or_default()
+        .unique_title(title)
+}
+
+#[test]
+fn test_unique_title() {
+    let mut a = specs::World::new();
+    a.insert(crate::Random::seeded(7));
+    let mut b = specs::World::new();
+    b.insert(crate::Random::seeded(7));
+    assert_eq!(unique_title(&a, "timer"), unique_title(&b, "timer"));
 
-    format!("{}_{:#04x}", title.as_ref(), rng.gen::<u16>())
+    assert!(unique_title(&specs::World::new(), "timer").starts_with("timer_"));
 }
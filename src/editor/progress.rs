@@ -1,10 +1,12 @@
 use atlier::system::{App, Extension};
 use specs::storage::HashMapStorage;
-use specs::{Component, WorldExt};
+use specs::{Component, Join, WorldExt};
 use std::cmp::min;
 use std::fmt::Write;
+use chrono::Utc;
+use tracing::{event, Level};
 
-use crate::plugins::{EventRuntime, StatusUpdate};
+use crate::plugins::{EventRuntime, LogBuffer};
 use crate::AttributeGraph;
 
 #[derive(Component, Clone, Default)]
@@ -16,14 +18,15 @@ pub struct ProgressStatusBar(
     pub String,
     /// log_display
     pub String, 
-    /// history.log
-    /// TODO: change to binary vec
-    pub String
+    /// history.log, copied from the entity's log buffer
+    pub String,
+    /// number of lines pushed to the entity's log buffer, when the log buffer was last read
+    pub usize,
 );
 
 impl Into<AttributeGraph> for ProgressStatusBar {
     fn into(self) -> AttributeGraph {
-        let Self(progress, status, log_display, log_history) = self;
+        let Self(progress, status, log_display, log_history, _) = self;
 
         AttributeGraph::from(0)
             .with_float("progress", progress)
@@ -54,7 +57,8 @@ impl From<AttributeGraph> for ProgressStatusBar {
                 graph.find_binary("history.log")
                     .and_then(|b| String::from_utf8(b).ok())
                     .unwrap_or_default()
-            })
+            },
+            0)
     }
 }
 
@@ -66,7 +70,7 @@ impl App for ProgressStatusBar {
     fn edit_ui(&mut self, _: &imgui::Ui) {}
 
     fn display_ui(&self, ui: &imgui::Ui) {
-        let ProgressStatusBar(progress, status, log_display, log_full) = self;
+        let ProgressStatusBar(progress, status, log_display, log_full, _) = self;
 
         if *progress > 0.0 {
             imgui::ProgressBar::new(*progress)
@@ -105,6 +109,14 @@ impl App for ProgressStatusBar {
                 if ui.button("dump to console out") {
                     println!("{}", &log_full);
                 }
+                ui.same_line();
+                if ui.button("save to file") {
+                    let path = format!("lifec-{}.log", Utc::now().timestamp_millis());
+                    match std::fs::write(&path, &log_full) {
+                        Ok(_) => event!(Level::INFO, "saved log history to {path}"),
+                        Err(err) => event!(Level::ERROR, "could not save log history, {err}"),
+                    }
+                }
                 ui.input_text_multiline("output_log", &mut log_full.clone(), [1360.0, 35.0 * 16.0])
                     .read_only(true)
                     .build();
@@ -120,6 +132,7 @@ impl App for ProgressStatusBar {
 impl Extension for ProgressStatusBar {
     fn configure_app_world(world: &mut specs::World) {
         world.register::<ProgressStatusBar>();
+        world.register::<LogBuffer>();
     }
 
     fn configure_app_systems(dispatcher: &mut specs::DispatcherBuilder) {
@@ -136,37 +149,43 @@ impl Extension for ProgressStatusBar {
     }
 
     fn on_run(&'_ mut self, app_world: &specs::World) {
-        let mut rx = app_world.write_resource::<tokio::sync::mpsc::Receiver<StatusUpdate>>();
-        let mut progress = app_world.write_storage::<ProgressStatusBar>();
+        // Status updates are recorded to log buffers by the event runtime, the status bar only reads them
+        let entities = app_world.entities();
+        let log_buffers = app_world.read_storage::<LogBuffer>();
+        let mut progress_bars = app_world.write_storage::<ProgressStatusBar>();
+
+        for (entity, log_buffer) in (&entities, &log_buffers).join() {
+            if let Some(ProgressStatusBar(progress, status, log_display, log, read)) = progress_bars.get_mut(entity) {
+                *progress = log_buffer.progress();
+                if *read == log_buffer.pushed() {
+                    continue;
+                }
 
-        if let Some((entity, p, s)) = rx.try_recv().ok() {
-            if let Some(ProgressStatusBar(progress, status, log_display, log)) =
-                progress.get_mut(entity)
-            {
-                *progress = p;
                 // The idea here is to show at max 10 lines with 85 chars on each line
                 // this gets dynamically sized so that small log messages get more that 10 lines automatically
-                let limit = 10 * min(85, status.len());
-                if log_display.len() > limit {
-                    if let Some((_, remaining)) = log_display.split_once("\n") {
-                        *log_display = remaining.to_string();
+                let new_lines = log_buffer.pushed() - *read;
+                for (_, line) in log_buffer.lines().skip(log_buffer.len().saturating_sub(new_lines)) {
+                    let limit = 10 * min(85, status.len());
+                    if log_display.len() > limit {
+                        if let Some((_, remaining)) = log_display.split_once("\n") {
+                            *log_display = remaining.to_string();
+                        }
                     }
+                    writeln!(log_display, "{}", line).ok();
+                    *status = line.to_string();
                 }
-                if !s.is_empty() {
-                    writeln!(log_display, "{}", &s).ok();
 
-                    // TODO need to have caps on log-size, or compressor
-                    writeln!(log, "{}", &s).ok();
-                    *status = s;
-                }
+                // The full history is kept by the entity's bounded log buffer
+                *log = log_buffer.to_string();
+                *read = log_buffer.pushed();
             } else {
-                match progress.insert(
-                    entity,
-                    ProgressStatusBar(p, s, String::default(), String::default()),
-                ) {
-                    Ok(_) => {}
-                    Err(_) => {}
-                }
+                let status = log_buffer.last().map(|(_, l)| l.to_string()).unwrap_or_default();
+                progress_bars
+                    .insert(
+                        entity,
+                        ProgressStatusBar(log_buffer.progress(), status, String::default(), log_buffer.to_string(), log_buffer.pushed()),
+                    )
+                    .ok();
             }
         }
     }
@@ -1,10 +1,12 @@
-use super::{unique_title, Call, Fix, List, Task};
+use super::{Call, Fix, List, Task};
 use crate::plugins::*;
 use crate::*;
 
 use atlier::system::WindowEvent;
 use imgui::{Condition, Slider, StyleVar, Ui, Window};
 use specs::{Join, World, WorldExt};
+use std::path::Path;
+use std::time::{Duration, Instant};
 pub use tokio::sync::broadcast::{channel, Receiver, Sender};
 
 /// Listener function, called when a thunk completes
@@ -18,6 +20,12 @@ pub struct RuntimeEditor {
     enable_complex: bool,
     show_all_engines: bool,
     task_window_size: [f32; 2],
+    /// Path to the .runmd file the project was loaded from
+    project_src: Option<String>,
+    /// Watches the project source, so that the project is reloaded when it changes
+    project_watcher: Option<FileWatcher>,
+    /// When the project should be reloaded, changes are debounced so that a burst of writes reloads once
+    reload_at: Option<Instant>,
 }
 
 /// Allows runtime editor to use `crate::start` method
@@ -63,14 +71,72 @@ impl RuntimeEditor {
         );
     }
 
-    /// Loads a project from a file
+    /// Loads a project from a file, and watches the file so that the project is reloaded when the file changes
+    ///
+    /// The project is loaded w/ `Project::load_file`, the same as `reload_project`.
+    ///
     pub fn load_project(&mut self, file_path: impl AsRef<str>) -> Option<()> {
-        if let Some(file) = AttributeGraph::load_from_file(file_path) {
-            *self.project_mut().as_mut() = file;
-            *self.project_mut() = self.project_mut().reload_source();
-            Some(())
-        } else {
-            None
+        let project = Project::load_file(&file_path)?;
+        *self.project_mut() = project;
+        self.watch_project(file_path.as_ref());
+        Some(())
+    }
+
+    /// Reloads the project from the file it was loaded from
+    pub fn reload_project(&mut self) -> Option<()> {
+        let project_src = self.project_src.clone()?;
+        let project = Project::load_file(&project_src)?;
+        event!(
+            Level::INFO,
+            "reloaded project {project_src}, {}", project.as_ref().hash_code()
+        );
+        *self.project_mut() = project;
+        Some(())
+    }
+
+    /// Watches the parent dir of the project source, since editors usually replace files instead of writing to them
+    fn watch_project(&mut self, project_src: &str) {
+        if self.project_src.as_deref() == Some(project_src) && self.project_watcher.is_some() {
+            return;
+        }
+
+        let dir = match Path::new(project_src).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => Path::new(".").to_path_buf(),
+        };
+
+        self.project_src = Some(project_src.to_string());
+        self.project_watcher = FileWatcher::new(
+            Duration::from_millis(Watch::DEFAULT_POLL_INTERVAL_MS as u64),
+            false,
+        )
+        .and_then(|mut watcher| watcher.watch(&dir, false).map(|_| watcher))
+        .map_err(|err| event!(Level::WARN, "could not watch {project_src}, hot-reload is disabled, {err}"))
+        .ok();
+    }
+
+    /// Reloads the project if the project source changed
+    fn hot_reload(&mut self) {
+        if let (Some(watcher), Some(file_name)) = (
+            self.project_watcher.as_mut(),
+            self.project_src.as_ref().and_then(|src| Path::new(src).file_name()),
+        ) {
+            if watcher
+                .try_changed()
+                .iter()
+                .any(|changed| changed.file_name() == Some(file_name))
+            {
+                self.reload_at = Some(
+                    Instant::now() + Duration::from_millis(Watch::DEFAULT_DEBOUNCE_MS as u64),
+                );
+            }
+        }
+
+        if let Some(reload_at) = self.reload_at {
+            if reload_at <= Instant::now() {
+                self.reload_at = None;
+                self.reload_project();
+            }
         }
     }
 
@@ -109,6 +175,9 @@ impl Default for RuntimeEditor {
             show_all_engines: false,
             enable_complex: false,
             task_window_size: [580.0, 700.0],
+            project_src: None,
+            project_watcher: None,
+            reload_at: None,
         };
         default.runtime.install::<Call, Timer>();
         default.runtime.install::<Call, Remote>();
@@ -119,8 +188,19 @@ impl Default for RuntimeEditor {
         default.runtime.install::<Call, Runtime>();
         default.runtime.install::<Call, Println>();
         default.runtime.install::<Call, Expect>();
+        default.runtime.install::<Call, Assert>();
         default.runtime.install::<Fix, Missing>();
         default.runtime.install::<Call, Redirect>();
+        default.runtime.install::<Call, Secure>();
+        default.runtime.install::<Call, Pipeline>();
+        default.runtime.install::<Call, If>();
+        default.runtime.install::<Call, Switch>();
+        default.runtime.install::<Call, While>();
+        default.runtime.install::<Call, ForEach>();
+        default.runtime.install::<Call, Watch>();
+        default.runtime.install::<Call, ArchiveFiles>();
+        default.runtime.install::<Call, ExtractFiles>();
+        default.runtime.install::<Call, Schedule>();
         default
     }
 }
@@ -217,6 +297,8 @@ impl Extension for RuntimeEditor {
             (listener)(self, world);
         }
 
+        self.hot_reload();
+
         let mut rx = world.write_resource::<tokio::sync::mpsc::Receiver<AttributeGraph>>();
         if let Some(graph) = rx.try_recv().ok() {
             let project = Project::from(graph);
@@ -227,12 +309,11 @@ impl Extension for RuntimeEditor {
                             Level::DEBUG, 
                             "got dispatch w/ {project_src}"
                         );
-                        if let Some(project) = Project::load_file(project_src) {
+                        if let Some(_) = self.load_project(&project_src) {
                             event!(
                                 Level::DEBUG,
-                                "setting active project, {}", project.as_ref().hash_code()
+                                "setting active project, {}", self.project().as_ref().hash_code()
                             );
-                            *self.project_mut() = project;
                             if let Some(engine) = self
                                 .runtime()
                                 .create_engine::<Call>(world, config_block.block_name.to_string())
@@ -396,7 +477,7 @@ impl RuntimeEditor {
             app_world,
             &Call::event::<Timer>(),
             |c| {
-                let title = unique_title("new_timer");
+                let title = c.random().unique_title("new_timer");
                 c.block.block_name = title.to_string();
                 c.as_mut()
                     .with_text("node_title", title)
@@ -412,7 +493,7 @@ impl RuntimeEditor {
             app_world,
             &Call::event::<Process>(),
             |c| {
-                let title = unique_title("new_process");
+                let title = c.random().unique_title("new_process");
                 c.block.block_name = title.to_string();
                 c.as_mut()
                     .with_text("node_title", title)
@@ -428,7 +509,7 @@ impl RuntimeEditor {
             app_world,
             &Call::event::<Remote>(),
             |c| {
-                let title = unique_title("new_remote");
+                let title = c.random().unique_title("new_remote");
                 c.block.block_name = title.to_string();
                 c.as_mut()
                     .with_text("node_title", title)
@@ -444,7 +525,7 @@ impl RuntimeEditor {
             app_world,
             &Call::event::<OpenFile>(),
             |c| {
-                let title = unique_title("new_open_file");
+                let title = c.random().unique_title("new_open_file");
                 c.block.block_name = title.to_string();
                 c.as_mut()
                     .with_text("node_title", title)
@@ -460,7 +541,7 @@ impl RuntimeEditor {
             app_world,
             &Call::event::<OpenDir>(),
             |c| {
-                let title = unique_title("new_open_dir");
+                let title = c.random().unique_title("new_open_dir");
                 c.block.block_name = title.to_string();
                 c.as_mut()
                     .with_text("node_title", title)
@@ -476,7 +557,7 @@ impl RuntimeEditor {
             app_world,
             &Call::event::<WriteFile>(),
             |c| {
-                let title = unique_title("new_write_file");
+                let title = c.random().unique_title("new_write_file");
                 c.block.block_name = title.to_string();
                 c.as_mut()
                     .with_text("node_title", title)
@@ -492,7 +573,7 @@ impl RuntimeEditor {
             app_world,
             &Call::event::<Println>(),
             |c| {
-                let title = unique_title("new_println");
+                let title = c.random().unique_title("new_println");
                 c.block.block_name = title.to_string();
                 c.as_mut()
                     .with_text("node_title", title)
@@ -10,10 +10,11 @@ pub use specs::{Entities, Join, ReadStorage, WriteStorage};
 use tracing::{event, Level};
 use imgui::{ChildWindow, MenuItem, Ui, Window};
 use plugins::{
-    AsyncContext, BlockContext, Config, Connection, Engine, Event, Expect, OpenDir, OpenFile,
-    Plugin, Println, Process, Project, Remote, Sequence, ThunkContext, Timer, WriteFile,
+    ArchiveFiles, AsyncContext, BlockContext, Config, Connection, Control, Engine, Event, EventRuntime, Expect, Assert,
+    ExtractFiles, ForEach, If, OpenDir, OpenFile, Pipeline, Plugin, Println, Process, Project, Remote,
+    RuntimeSpan, Schedule, Secure, Sequence, Switch, Thunk, ThunkContext, Timer, Watch, While, WriteFile,
 };
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::Display;
 use std::time::Duration;
 use std::{any::Any, collections::BTreeMap};
@@ -29,18 +30,55 @@ pub use start::start;
 
 pub mod editor;
 pub mod plugins;
+pub mod testing;
+
+mod trace;
+pub use trace::TraceExporter;
+
+mod metrics;
+pub use metrics::Histogram;
+pub use metrics::Metrics;
+pub use metrics::MetricsSnapshot;
 
 mod catalog;
+pub use catalog::Catalog;
+pub use catalog::CatalogQuery;
+pub use catalog::CatalogPredicate;
+pub use catalog::CatalogSystem;
+pub use catalog::IndexKey;
 pub use catalog::CatalogReader;
 pub use catalog::CatalogWriter;
 pub use catalog::Item;
 
+mod cache;
+pub use cache::ResultCache;
+
+mod clock;
+pub use clock::Clock;
+
+mod random;
+pub use random::Random;
+
 mod state;
 pub use state::AttributeGraph;
 pub use state::AttributeGraphEvents;
 pub use state::AttributeGraphElements;
 pub use state::AttributeGraphErrors;
+pub use state::AttributeChange;
+pub use state::GraphDiff;
+pub use state::History;
+pub use state::Step;
+pub use state::Journal;
+pub use state::JournalEntry;
+pub use state::BinaryErrors;
+pub use state::Encoding;
+pub use state::BINARY_VERSION;
+pub use state::ContentHash;
 pub use state::Query;
+pub use state::Predicate;
+pub use state::QuerySubscriber;
+pub use state::QuerySubscriptions;
+pub use state::QueryUpdate;
 pub use state::AttributeIndex;
 
 use crate::plugins::ProxyDispatcher;
@@ -116,18 +154,26 @@ pub trait RuntimeState:
 {
     type Dispatcher: RuntimeDispatcher;
 
-    /// Try to save the current state to a String
+    /// Try to save the current state to a String, sensitive values are not saved
+    /// 
+    /// Saved as json, or as a base64 binary encoded graph if the state selects the binary encoding
+    /// 
     fn save(&self) -> Option<String> {
-        match serde_json::to_string(self.state()) {
-            Ok(val) => Some(val),
-            Err(_) => None,
+        match Encoding::find(self.state()) {
+            Encoding::Binary => Some(self.state().save_base64()),
+            Encoding::Text => match serde_json::to_string(&self.state().redacted()) {
+                Ok(val) => Some(val),
+                Err(_) => None,
+            },
         }
     }
 
     /// Load should take the serialized form of this state
     /// and create a new instance of Self
     fn load(&self, init: impl AsRef<str>) -> Self {
-        if let Some(attribute_graph) = serde_json::from_str::<AttributeGraph>(init.as_ref()).ok() {
+        if let Some(attribute_graph) = AttributeGraph::load_base64(init.as_ref()) {
+            Self::from(attribute_graph)
+        } else if let Some(attribute_graph) = serde_json::from_str::<AttributeGraph>(init.as_ref()).ok() {
             Self::from(attribute_graph)
         } else {
             self.clone()
@@ -179,6 +225,8 @@ pub struct Runtime {
     engine_plugin: BTreeMap<String, CreateFn>,
     /// Table for thunk configurations
     config: BTreeMap<String, ConfigFn>,
+    /// Table of installed plugins by symbol, used to resolve pipeline stages
+    thunks: BTreeMap<String, Thunk>,
 }
 
 /// Consolidates elements to start and create events into a struct
@@ -235,9 +283,15 @@ impl Runtime {
             project,
             engine_plugin: BTreeMap::default(),
             config: BTreeMap::default(),
+            thunks: BTreeMap::default(),
         }
     }
 
+    /// Returns the project this runtime was created from
+    pub fn project(&self) -> &Project {
+        &self.project
+    }
+
     /// Creates a new event builder
     /// 
     pub fn event_builder<'a, E, P>(&'a self) -> EventBuilder 
@@ -260,10 +314,128 @@ impl Runtime {
     {
         let event = E::event::<P>();
         self.engine_plugin.insert(event.to_string(), E::create::<P>);
+        self.thunks.insert(P::symbol().to_string(), Thunk::from_plugin::<P>());
 
         event!(Level::INFO, "install event: {}", event.to_string());
     }
 
+    /// Returns a pipeline w/ each stage resolved from the installed plugins, see `Pipeline`
+    ///
+    /// Returns None if the pipeline is empty, or if a stage is not installed.
+    ///
+    pub fn pipeline(&self, pipeline: impl AsRef<str>) -> Option<Pipeline> {
+        let stages = Pipeline::parse(pipeline.as_ref());
+        if stages.is_empty() {
+            return None;
+        }
+
+        let mut resolved = vec![];
+        for stage in stages {
+            match self.thunks.get(stage) {
+                Some(thunk) if stage != Pipeline::symbol() => resolved.push(thunk.clone()),
+                _ => {
+                    event!(Level::ERROR, "pipeline stage {stage} is not installed");
+                    return None;
+                }
+            }
+        }
+
+        Some(Pipeline::new(resolved))
+    }
+
+    /// If the created event has a `pipeline` attribute, resolves the pipeline and sets it on the event
+    fn attach_pipeline(&self, world: &World, created: Entity, block_address: impl AsRef<str>) {
+        let pipeline = world
+            .read_component::<ThunkContext>()
+            .get(created)
+            .and_then(|tc| tc.as_ref().find_text("pipeline"))
+            .or_else(|| {
+                block_address
+                    .as_ref()
+                    .split_once("::")
+                    .and_then(|(name, symbol)| self.project.find_block(name).and_then(|b| b.get_block(symbol)))
+                    .and_then(|b| b.find_text("pipeline"))
+            });
+
+        if let Some(pipeline) = pipeline {
+            match (self.pipeline(&pipeline), world.write_component::<Event>().get_mut(created)) {
+                (Some(pipeline), Some(event)) => {
+                    event!(Level::DEBUG, "pipeline for {}: {:?}", created.id(), pipeline.stages());
+                    event.set_pipeline(pipeline);
+                }
+                _ => {
+                    event!(Level::ERROR, "could not resolve pipeline `{pipeline}` for {}", block_address.as_ref());
+                }
+            }
+        }
+    }
+
+    /// If the project declares a schema for the created event's block symbol, validates the block and applies defaults to the event's context
+    ///
+    /// Defaults only apply to the event's context, the project isn't changed.
+    ///
+    fn attach_schema(&self, world: &World, created: Entity, block_address: impl AsRef<str>) {
+        let (block_name, block_symbol) = match block_address.as_ref().split_once("::") {
+            Some(parts) => parts,
+            None => return,
+        };
+
+        if let Some(schema) = self.project.schemas().remove(block_symbol) {
+            if let Some(tc) = world.write_component::<ThunkContext>().get_mut(created) {
+                schema.apply_defaults(tc.as_mut());
+            }
+
+            let errors = self
+                .project
+                .find_block(block_name)
+                .and_then(|b| b.get_block(block_symbol))
+                .map(|block| schema.validate(&block))
+                .unwrap_or_default();
+            if !errors.is_empty() {
+                if let Some(event) = world.write_component::<Event>().get_mut(created) {
+                    event.set_schema_errors(errors);
+                }
+            }
+        }
+    }
+
+    /// If the created event is a control flow event, creates the sequence for each target, see `Control`
+    fn create_control_targets<E>(&self, world: &World, created: Entity, parents: &mut BTreeSet<String>)
+    where
+        E: Engine,
+    {
+        let is_control = world
+            .read_component::<Event>()
+            .get(created)
+            .map(|e| Control::symbols().contains(&e.plugin_symbol()))
+            .unwrap_or_default();
+        if !is_control {
+            return;
+        }
+
+        let targets = world
+            .read_component::<ThunkContext>()
+            .get(created)
+            .map(|tc| Control::targets(tc.as_ref()))
+            .unwrap_or_default();
+
+        for target in targets {
+            // A target that is already being created, i.e. a while body that calls it's parent, would never finish
+            if parents.contains(&target) {
+                event!(Level::ERROR, "control target {target} is a parent of {}, use a loop instead", created.id());
+                continue;
+            }
+
+            if let Some(first) = self.create_engine_with_parents::<E>(world, target.to_string(), parents) {
+                if let Some(tc) = world.write_component::<ThunkContext>().get_mut(created) {
+                    Control::set_target(tc.as_mut(), &target, first.id());
+                }
+            } else {
+                event!(Level::ERROR, "could not create control target {target} for {}", created.id());
+            }
+        }
+    }
+
     /// Registers a config w/ this runtime
     pub fn add_config(&mut self, config: Config) {
         let Config(name, config_fn) = config;
@@ -467,6 +639,14 @@ impl Runtime {
     /// Creates a new engine,
     /// an engine is defined by a sequence of events.
     pub fn create_engine<E>(&self, world: &World, sequence_block_name: String) -> Option<Entity>
+    where
+        E: Engine,
+    {
+        self.create_engine_with_parents::<E>(world, sequence_block_name, &mut BTreeSet::default())
+    }
+
+    /// Creates a new engine, parents are the sequences that are being created w/ control flow targets that lead to this one
+    fn create_engine_with_parents<E>(&self, world: &World, sequence_block_name: String, parents: &mut BTreeSet<String>) -> Option<Entity>
     where
         E: Engine,
     {
@@ -487,8 +667,14 @@ impl Runtime {
                         let engine_plugin_key = format!("{} {}", "call", block_symbol);
                         if let Some(create_fn) = self.engine_plugin.get(&engine_plugin_key) {
                             if let Some(created) =
-                                self.create_plugin(world, block_address, value.clone(), *create_fn)
+                                self.create_plugin(world, &block_address, value.clone(), *create_fn)
                             {
+                                self.attach_pipeline(world, created, &block_address);
+                                self.attach_schema(world, created, &block_address);
+
+                                parents.insert(sequence_block_name.to_string());
+                                self.create_control_targets::<E>(world, created, parents);
+                                parents.remove(&sequence_block_name);
                                 event!(
                                     Level::DEBUG, 
                                     "create event:\n\t{}\n\t{}\n\t{}\n\tconfig: {:?}",
@@ -744,6 +930,10 @@ impl Plugin<ThunkContext> for Runtime {
         "Starts a runtime w/ it's own standalone world"
     }
 
+    fn caveats() -> &'static str {
+        "If `deterministic` is enabled, the world uses a virtual clock and randomness seeded w/ `seed`"
+    }
+
     fn call_with_context(context: &mut ThunkContext) -> Option<AsyncContext> {
         context.clone().task(|cancel_source| {
             let tc = context.clone();
@@ -759,7 +949,18 @@ impl Plugin<ThunkContext> for Runtime {
                         runtime.install::<Call, Timer>();
                         runtime.install::<Call, Runtime>();
                         runtime.install::<Call, Expect>();
+                        runtime.install::<Call, Assert>();
                         runtime.install::<Call, Println>();
+                        runtime.install::<Call, Secure>();
+                        runtime.install::<Call, Pipeline>();
+                        runtime.install::<Call, If>();
+                        runtime.install::<Call, Switch>();
+                        runtime.install::<Call, While>();
+                        runtime.install::<Call, ForEach>();
+                        runtime.install::<Call, Watch>();
+                        runtime.install::<Call, ArchiveFiles>();
+                        runtime.install::<Call, ExtractFiles>();
+                        runtime.install::<Call, Schedule>();
 
                         // TODO - add some built in configs -
 
@@ -827,6 +1028,19 @@ impl Runtime {
         let mut dispatcher = dispatcher_builder.build();
         dispatcher.setup(&mut world);
 
+        if tc.as_ref().is_enabled("deterministic").unwrap_or_default() {
+            let seed = tc.as_ref().find_int("seed").unwrap_or_default();
+            event!(Level::INFO, "deterministic mode is enabled, seed: {seed}");
+            EventRuntime::enable_deterministic(&mut world, seed as u64);
+        }
+
+        // Events started by this world are nested under the span of the caller, i.e. the event that started this runtime
+        world.insert(RuntimeSpan(tracing::info_span!(
+            "runtime",
+            block_symbol = %block_symbol,
+            engine = E::event_name(),
+        )));
+
         let mut engine_table = HashMap::<String, Entity>::default();
 
         for engine in call_names {
@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing::{event, Level};
+
+/// Metrics resource that runtime systems update as they run,
+///
+/// Counters and histograms are keyed by metric name and plugin symbol. Gauges are keyed only by name, and are
+/// used for things like the current queue depth of a channel.
+///
+/// The resource is a cheap handle to shared state, so a clone can be moved to another thread to scrape it,
+/// i.e. by `serve`.
+///
+/// ```ignore
+/// let metrics = world.read_resource::<Metrics>().clone();
+/// let addr = metrics.serve("127.0.0.1:0")?;
+/// // GET http://{addr}/metrics
+/// metrics.save("lifec.prom")?;
+/// ```
+///
+#[derive(Clone, Default)]
+pub struct Metrics(Arc<Mutex<MetricsSnapshot>>);
+
+/// Copy of all metrics at a point in time
+///
+/// Display writes the snapshot in the prometheus text exposition format.
+///
+#[derive(Clone, Debug, Default)]
+pub struct MetricsSnapshot {
+    counters: BTreeMap<(String, String), u64>,
+    gauges: BTreeMap<String, f64>,
+    histograms: BTreeMap<(String, String), Histogram>,
+}
+
+/// Histogram w/ fixed buckets, bucket counts are cumulative
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// Upper bounds of each bucket
+    bounds: Vec<f64>,
+    /// Number of observations less than or equal to each bound
+    buckets: Vec<u64>,
+    /// Sum of all observations
+    sum: f64,
+    /// Number of observations
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BOUNDS.to_vec())
+    }
+}
+
+impl Histogram {
+    /// Default bucket bounds, in seconds
+    pub const DEFAULT_BOUNDS: [f64; 11] = [
+        0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+    ];
+
+    /// Returns a new histogram w/ the bucket bounds
+    pub fn new(bounds: Vec<f64>) -> Self {
+        Self {
+            buckets: vec![0; bounds.len()],
+            bounds,
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    /// Records an observation
+    pub fn observe(&mut self, value: f64) {
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter_mut()) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+
+        self.sum += value;
+        self.count += 1;
+    }
+
+    /// Returns an iterator over (upper bound, cumulative count)
+    pub fn buckets(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        self.bounds.iter().cloned().zip(self.buckets.iter().cloned())
+    }
+
+    /// Returns the sum of all observations
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Returns the number of observations
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl Metrics {
+    /// Increments a counter by 1
+    pub fn increment(&self, name: impl AsRef<str>, plugin: impl AsRef<str>) {
+        self.add(name, plugin, 1);
+    }
+
+    /// Adds value to a counter
+    pub fn add(&self, name: impl AsRef<str>, plugin: impl AsRef<str>, value: u64) {
+        self.update(|m| {
+            *m.counters
+                .entry((name.as_ref().to_string(), plugin.as_ref().to_string()))
+                .or_default() += value;
+        });
+    }
+
+    /// Sets the current value of a gauge
+    pub fn set_gauge(&self, name: impl AsRef<str>, value: f64) {
+        self.update(|m| {
+            m.gauges.insert(name.as_ref().to_string(), value);
+        });
+    }
+
+    /// Records an observation in a histogram w/ the default bounds
+    pub fn observe(&self, name: impl AsRef<str>, plugin: impl AsRef<str>, value: f64) {
+        self.update(|m| {
+            m.histograms
+                .entry((name.as_ref().to_string(), plugin.as_ref().to_string()))
+                .or_default()
+                .observe(value);
+        });
+    }
+
+    /// Records a duration, in seconds, in a histogram
+    pub fn observe_duration(&self, name: impl AsRef<str>, plugin: impl AsRef<str>, duration: Duration) {
+        self.observe(name, plugin, duration.as_secs_f64());
+    }
+
+    /// Returns a copy of the current metrics
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        self.0.lock().map(|m| m.clone()).unwrap_or_default()
+    }
+
+    /// Writes the current metrics to a file in the prometheus text format
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.snapshot().to_string())
+    }
+
+    /// Serves the current metrics in the prometheus text format over http, from a background thread,
+    /// returns the address the listener was bound to, so that port 0 can be used to pick a free port
+    ///
+    pub fn serve(&self, address: impl AsRef<str>) -> std::io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address.as_ref())?;
+        let local_addr = listener.local_addr()?;
+        let metrics = self.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(mut stream) => {
+                        // The request isn't parsed, every request gets the current metrics
+                        let mut request = [0; 1024];
+                        if let Err(err) = stream.read(&mut request) {
+                            event!(Level::WARN, "error reading metrics request, {err}");
+                        }
+
+                        let body = metrics.snapshot().to_string();
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: text/plain; version=0.0.4\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                            body.len()
+                        );
+                        stream.write_all(response.as_bytes()).ok();
+                    }
+                    Err(err) => {
+                        event!(Level::ERROR, "error accepting metrics connection, {err}");
+                    }
+                }
+            }
+        });
+
+        event!(Level::DEBUG, "serving metrics at {local_addr}");
+        Ok(local_addr)
+    }
+
+    fn update(&self, update: impl FnOnce(&mut MetricsSnapshot)) {
+        match self.0.lock() {
+            Ok(mut metrics) => update(&mut metrics),
+            Err(err) => {
+                event!(Level::ERROR, "could not update metrics, {err}");
+            }
+        }
+    }
+}
+
+impl MetricsSnapshot {
+    /// Returns the value of a counter, 0 if the counter doesn't exist
+    pub fn counter(&self, name: impl AsRef<str>, plugin: impl AsRef<str>) -> u64 {
+        self.counters
+            .get(&(name.as_ref().to_string(), plugin.as_ref().to_string()))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Returns the value of a gauge
+    pub fn gauge(&self, name: impl AsRef<str>) -> Option<f64> {
+        self.gauges.get(name.as_ref()).cloned()
+    }
+
+    /// Returns a histogram
+    pub fn histogram(&self, name: impl AsRef<str>, plugin: impl AsRef<str>) -> Option<&Histogram> {
+        self.histograms
+            .get(&(name.as_ref().to_string(), plugin.as_ref().to_string()))
+    }
+
+    /// Returns an iterator over (name, plugin, value) of each counter
+    pub fn iter_counters(&self) -> impl Iterator<Item = (&String, &String, u64)> {
+        self.counters
+            .iter()
+            .map(|((name, plugin), value)| (name, plugin, *value))
+    }
+
+    /// Formats labels for a metric, if the plugin is empty no plugin label is written
+    fn labels(plugin: &str, extra: Option<(&str, String)>) -> String {
+        let mut labels = vec![];
+        if !plugin.is_empty() {
+            let plugin = plugin
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            labels.push(format!("plugin=\"{plugin}\""));
+        }
+
+        if let Some((name, value)) = extra {
+            labels.push(format!("{name}=\"{value}\""));
+        }
+
+        if labels.is_empty() {
+            String::default()
+        } else {
+            format!("{{{}}}", labels.join(","))
+        }
+    }
+}
+
+impl Display for MetricsSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut last = None;
+        for ((name, plugin), value) in self.counters.iter() {
+            if last != Some(name) {
+                writeln!(f, "# TYPE {name} counter")?;
+                last = Some(name);
+            }
+            writeln!(f, "{name}{} {value}", Self::labels(plugin, None))?;
+        }
+
+        for (name, value) in self.gauges.iter() {
+            writeln!(f, "# TYPE {name} gauge")?;
+            writeln!(f, "{name} {value}")?;
+        }
+
+        let mut last = None;
+        for ((name, plugin), histogram) in self.histograms.iter() {
+            if last != Some(name) {
+                writeln!(f, "# TYPE {name} histogram")?;
+                last = Some(name);
+            }
+
+            for (bound, count) in histogram.buckets() {
+                let labels = Self::labels(plugin, Some(("le", bound.to_string())));
+                writeln!(f, "{name}_bucket{labels} {count}")?;
+            }
+            let labels = Self::labels(plugin, Some(("le", "+Inf".to_string())));
+            writeln!(f, "{name}_bucket{labels} {}", histogram.count())?;
+            writeln!(f, "{name}_sum{} {}", Self::labels(plugin, None), histogram.sum())?;
+            writeln!(f, "{name}_count{} {}", Self::labels(plugin, None), histogram.count())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_metrics() {
+    let metrics = Metrics::default();
+    metrics.increment("lifec_events_started_total", "println");
+    metrics.increment("lifec_events_started_total", "println");
+    metrics.add("lifec_proxy_bytes_total", "", 11);
+    metrics.set_gauge("lifec_status_update_queue_depth", 3.0);
+    metrics.observe("lifec_event_duration_seconds", "println", 0.2);
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.counter("lifec_events_started_total", "println"), 2);
+    assert_eq!(snapshot.gauge("lifec_status_update_queue_depth"), Some(3.0));
+
+    let histogram = snapshot
+        .histogram("lifec_event_duration_seconds", "println")
+        .expect("exists");
+    assert_eq!(histogram.count(), 1);
+    assert_eq!(histogram.buckets().find(|(b, _)| *b == 0.1), Some((0.1, 0)));
+    assert_eq!(histogram.buckets().find(|(b, _)| *b == 0.25), Some((0.25, 1)));
+
+    let exposition = snapshot.to_string();
+    assert!(exposition.contains("# TYPE lifec_events_started_total counter"));
+    assert!(exposition.contains("lifec_events_started_total{plugin=\"println\"} 2"));
+    assert!(exposition.contains("lifec_proxy_bytes_total 11"));
+    assert!(exposition.contains("lifec_event_duration_seconds_bucket{plugin=\"println\",le=\"+Inf\"} 1"));
+
+    // Scrape from a local port
+    let addr = metrics.serve("127.0.0.1:0").expect("listening");
+    let mut stream = std::net::TcpStream::connect(addr).expect("connected");
+    stream.write_all(b"GET /metrics HTTP/1.1\r\n\r\n").expect("sent");
+    let mut response = String::new();
+    stream.read_to_string(&mut response).expect("received");
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.ends_with(&exposition));
+}
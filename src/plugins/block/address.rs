@@ -38,7 +38,8 @@ use crate::AttributeGraph;
 #[derive(Debug, Component, Default, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[storage(DenseVecStorage)]
 pub struct BlockAddress {
-    /// The hash code is the hash_code of the runmd block that initated this address to be created
+    /// The hash code is the content hash of the runmd block that initated this address to be created,
+    /// truncated to a u64, see `ContentHash::to_u64`
     ///
     hash_code: u64,
     /// An entity id is typically a u32 int (provided by specs)
@@ -94,7 +95,7 @@ impl BlockAddress {
 
         match world.read_component::<ThunkContext>().get(entity) {
             // The original hash code must match the hash code this address was created at
-            Some(ref tc) if tc.as_ref().hash_code() == self.hash_code => {
+            Some(ref tc) if tc.as_ref().content_hash().to_u64() == self.hash_code => {
                 let proxy_entity = world.entities().create();
                 let mut hosting = tc.to_owned().clone();
                 hosting.as_mut().set_parent_entity(proxy_entity);
@@ -156,7 +157,7 @@ impl BlockAddress {
     /// as the entity for this address
     ///
     pub fn new(graph: impl AsRef<AttributeGraph>) -> BlockAddress {
-        let hash_code = graph.as_ref().hash_code();
+        let hash_code = graph.as_ref().content_hash().to_u64();
 
         let mut new_address = BlockAddress {
             hash_code,
@@ -568,7 +569,7 @@ fn test_block_address() {
     );
 
     assert_eq!(addr.entity(), 100);
-    assert_eq!(addr.hash_code, graph.hash_code());
+    assert_eq!(addr.hash_code, graph.content_hash().to_u64());
 
     addr.set_ip_v4([127, 0, 0, 1], 50871);
     assert_eq!(addr.ip_addr_v4(), Ipv4Addr::new(127, 0, 0, 1));
@@ -628,13 +629,13 @@ fn test_block_connection() {
         if let Some((to_e, to_addr)) = from.connected() {
             assert_eq!(to_e, 1421);
             assert_eq!(to_addr, ip_v4_b);
-            assert_eq!(to.hash_code, b.hash_code());
+            assert_eq!(to.hash_code, b.content_hash().to_u64());
             eprintln!("{:?}, {:?}, {}", to_e, to_addr, to.hash_code);
         }
 
         if let Some((from_e, from_addr)) = to.connected() {
             assert_eq!(from_e, 100);
-            assert_eq!(from.hash_code, a.hash_code());
+            assert_eq!(from.hash_code, a.content_hash().to_u64());
             assert_eq!(from_addr, ip_v4_a);
             eprintln!("{:?}, {:?}, {}", from_e, from_addr, from.hash_code);
         }
@@ -650,14 +651,14 @@ fn test_block_connection() {
         if let Some((to_e, to_addr)) = from.connected() {
             assert_eq!(to_e, 1421);
             assert_eq!(to_addr, ip_v6_b);
-            assert_eq!(to.hash_code, b.hash_code());
+            assert_eq!(to.hash_code, b.content_hash().to_u64());
             eprintln!("{:?}, {:?}, {}", to_e, to_addr, to.hash_code);
         }
 
         if let Some((from_e, from_addr)) = to.connected() {
             assert_eq!(from_e, 100);
             assert_eq!(from_addr, ip_v6_a);
-            assert_eq!(from.hash_code, a.hash_code());
+            assert_eq!(from.hash_code, a.content_hash().to_u64());
             eprintln!("{:?}, {:?}, {}", from_e, from_addr, from.hash_code);
         }
     }
@@ -6,11 +6,16 @@ pub use address::BlockAddress;
 mod project;
 pub use project::Project;
 
-use crate::AttributeGraph;
+mod schema;
+pub use schema::Schema;
+pub use schema::SchemaErrors;
+
+use crate::{AttributeGraph, ContentHash};
 use atlier::system::{Attribute, Value};
 use imgui::{ChildWindow, MenuItem, Ui};
 use specs::storage::DenseVecStorage;
 use specs::Component;
+use std::collections::BTreeMap;
 use std::fmt::Write;
 use std::{collections::BTreeSet, fmt::Error};
 
@@ -25,6 +30,29 @@ pub struct BlockContext {
 }
 
 impl BlockContext {
+    /// Returns a stable hash of the content of this block, independent of entity ids, see `AttributeGraph::content_hash_without_ids`
+    pub fn content_hash(&self) -> ContentHash {
+        self.graph.content_hash_without_ids()
+    }
+
+    /// Returns the schema declared by this block context, if it has a `schema` block
+    pub fn schema(&self) -> Option<Schema> {
+        self.get_block("schema")
+            .map(|block| Schema::new(&self.block_name, &block))
+    }
+
+    /// Validates each block that has a schema, keyed by block symbol, returns the errors found
+    ///
+    /// The block context isn't changed, defaults are applied to the context of events created from the block instead.
+    ///
+    pub fn validate(&self, schemas: &BTreeMap<String, Schema>) -> Vec<SchemaErrors> {
+        self.block_symbols
+            .iter()
+            .filter_map(|block_symbol| schemas.get(block_symbol).zip(self.get_block(block_symbol)))
+            .flat_map(|(schema, block)| schema.validate(&block))
+            .collect()
+    }
+
     /// Converts self into vec of blocks
     pub fn to_blocks(&self) -> Vec<(String, AttributeGraph)> {
         let clone = self.clone();
@@ -201,9 +229,9 @@ impl BlockContext {
     pub fn transpile_block(&self, block_symbol: impl AsRef<str>) -> Result<String, Error> {
         let mut src = String::new();
         writeln!(src, "``` {} {}", self.block_name, block_symbol.as_ref())?;
-        if let Some(mut block) = self.get_block(block_symbol) {
+        if let Some(mut block) = self.get_block(block_symbol).map(|b| b.redacted()) {
             for attr in Self::iter_block_attrs_mut(block.as_mut()) {
-                if attr.name().starts_with("block_") {
+                if attr.name().starts_with("block_") || attr.name().ends_with("::secret") {
                     continue;
                 }
 
@@ -303,6 +331,15 @@ impl BlockContext {
                     val
                 )?;
             }
+            atlier::system::Value::Symbol(val) if val.starts_with("secret::") => {
+                writeln!(
+                    src,
+                    "{} {} .secret {}",
+                    event.as_ref(),
+                    name.as_ref(),
+                    val.trim_start_matches("secret::")
+                )?;
+            }
             atlier::system::Value::Symbol(val) => {
                 if !val.ends_with("::block") {
                     write!(src, "define")?;
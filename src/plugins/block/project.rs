@@ -1,5 +1,5 @@
-use super::BlockContext;
-use crate::state::AttributeGraph;
+use super::{BlockContext, Schema, SchemaErrors};
+use crate::state::{AttributeGraph, ContentHash, Encoding};
 use crate::RuntimeDispatcher;
 use imgui::Ui;
 use specs::storage::HashMapStorage;
@@ -26,6 +26,37 @@ impl Project {
         Self::load_file(".runmd")
     }
 
+    /// Returns a stable hash of the content of this project, independent of entity ids, see `AttributeGraph::content_hash_without_ids`
+    pub fn content_hash(&self) -> ContentHash {
+        self.source.content_hash_without_ids()
+    }
+
+    /// Returns the schemas declared in this project, keyed by the block symbol each schema applies to
+    pub fn schemas(&self) -> BTreeMap<String, Schema> {
+        self.block_index
+            .values()
+            .filter_map(BlockContext::schema)
+            .map(|schema| (schema.symbol().to_string(), schema))
+            .collect()
+    }
+
+    /// Validates each block against the schemas declared in this project, see `BlockContext::validate`
+    ///
+    /// Returns the errors found, keyed by block name, the project isn't changed.
+    ///
+    pub fn validate(&self) -> BTreeMap<String, Vec<SchemaErrors>> {
+        let schemas = self.schemas();
+        if schemas.is_empty() {
+            return BTreeMap::default();
+        }
+
+        self.block_index
+            .iter()
+            .map(|(name, block)| (name.to_string(), block.validate(&schemas)))
+            .filter(|(_, errors)| !errors.is_empty())
+            .collect()
+    }
+
     pub fn index_hash_code(&self) -> u64 {
         let mut hasher = DefaultHasher::default();
         let hasher = &mut hasher;
@@ -67,11 +98,29 @@ impl Project {
         Ok(src)
     }
 
+    /// Returns the blocks of this project encoded for the `previous` hand-off, None if there is nothing to hand off
+    /// 
+    /// The receiving context unpacks the blocks w/ `.apply("previous")`, which accepts either encoding.
+    /// 
+    pub fn encode_blocks(&self, encoding: Encoding) -> Option<Vec<u8>> {
+        match encoding {
+            Encoding::Text => self
+                .transpile_blocks()
+                .ok()
+                .map(|blocks| blocks.trim().to_string())
+                .filter(|blocks| !blocks.is_empty())
+                .map(String::into_bytes),
+            Encoding::Binary if !self.block_index.is_empty() => Some(self.source.redacted().to_bytes()),
+            Encoding::Binary => None,
+        }
+    }
+
     pub fn transpile_root(&self) -> Result<String, Error> {
         let mut src = String::new();
         writeln!(src, "```")?;
-        for attr in self.as_ref().iter_attributes().filter(|a| a.id() == 0) {
-            if attr.name().starts_with("block_") {
+        let root = self.as_ref().redacted();
+        for attr in root.iter_attributes().filter(|a| a.id() == 0) {
+            if attr.name().starts_with("block_") || attr.name().ends_with("::secret") {
                 continue;
             }
 
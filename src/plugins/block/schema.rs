@@ -0,0 +1,293 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use atlier::system::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::AttributeGraph;
+
+/// Schema for the blocks of a block symbol, declared in runmd w/ a `schema` block
+///
+/// The block name of the schema block is the block symbol the schema applies to. Each attribute is declared w/ `define`, i.e.
+///
+/// ````runmd
+/// ``` timer schema
+/// define duration kind .text int
+/// define duration required .enable
+/// define duration default .int 1
+/// define duration range .int2 0, 3600
+/// define duration_ms kind .text float
+/// ```
+/// ````
+///
+/// Kinds use the same names as runmd value types w/o the leading `.`, i.e. `text`, `bool`, `int`, `int2`, `int3`, `float`, `float2`,
+/// `float3`, `bin`, `symbol` and `empty`. Ranges are inclusive and only apply to `int` and `float` values.
+///
+/// Attributes that are not declared are reported as unknown, unless the schema enables `allow_unknown`.
+///
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schema {
+    /// Block symbol this schema applies to
+    symbol: String,
+    /// Declared attributes, keyed by name
+    fields: BTreeMap<String, Field>,
+    /// If true, attributes that are not declared are allowed
+    allow_unknown: bool,
+}
+
+/// Declaration of an attribute in a schema
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+struct Field {
+    kind: Option<String>,
+    required: bool,
+    default: Option<Value>,
+    range: Option<(f64, f64)>,
+}
+
+/// Errors found when validating a block against a schema
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaErrors {
+    /// A required attribute is missing, and the schema doesn't declare a default
+    Missing { symbol: String, name: String },
+    /// The value of an attribute is not the declared kind
+    InvalidKind {
+        symbol: String,
+        name: String,
+        expected: String,
+        found: String,
+    },
+    /// The value of an attribute is outside the declared range
+    OutOfRange {
+        symbol: String,
+        name: String,
+        range: (f64, f64),
+    },
+    /// The attribute is not declared by the schema
+    Unknown { symbol: String, name: String },
+}
+
+impl SchemaErrors {
+    /// Returns the name of the attribute in the error block this error is recorded as
+    pub fn name(&self) -> String {
+        match self {
+            SchemaErrors::Missing { symbol, name }
+            | SchemaErrors::InvalidKind { symbol, name, .. }
+            | SchemaErrors::OutOfRange { symbol, name, .. }
+            | SchemaErrors::Unknown { symbol, name } => format!("{symbol}.{name}"),
+        }
+    }
+}
+
+impl Display for SchemaErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaErrors::Missing { symbol, name } => {
+                write!(f, "{symbol} requires `{name}`")
+            }
+            SchemaErrors::InvalidKind {
+                symbol,
+                name,
+                expected,
+                found,
+            } => {
+                write!(f, "{symbol} expects `{name}` to be .{expected}, found .{found}")
+            }
+            SchemaErrors::OutOfRange {
+                symbol,
+                name,
+                range: (min, max),
+            } => {
+                write!(f, "{symbol} expects `{name}` to be between {min} and {max}")
+            }
+            SchemaErrors::Unknown { symbol, name } => {
+                write!(f, "{symbol} does not declare `{name}`")
+            }
+        }
+    }
+}
+
+impl Schema {
+    /// Returns a schema for symbol, from the attributes of a schema block
+    pub fn new(symbol: impl AsRef<str>, block: &AttributeGraph) -> Self {
+        let mut schema = Schema {
+            symbol: symbol.as_ref().to_string(),
+            allow_unknown: block.is_enabled("allow_unknown").unwrap_or_default(),
+            ..Default::default()
+        };
+
+        for (name, property, value) in block.iter_attributes().filter_map(|a| match (a.value(), a.transient()) {
+            (Value::Symbol(symbol), Some((_, value))) if !symbol.ends_with("::block") => a
+                .name()
+                .split_once("::")
+                .map(|(name, property)| (name, property, value)),
+            _ => None,
+        }) {
+            let field = schema.fields.entry(name.to_string()).or_default();
+            match (property, value) {
+                ("kind", Value::TextBuffer(kind)) => field.kind = Some(kind.trim_start_matches('.').to_string()),
+                ("required", Value::Bool(required)) => field.required = *required,
+                ("required", Value::Empty) => field.required = true,
+                ("default", value) => field.default = Some(value.clone()),
+                ("range", Value::IntPair(min, max)) => field.range = Some((*min as f64, *max as f64)),
+                ("range", Value::FloatPair(min, max)) => field.range = Some((*min as f64, *max as f64)),
+                _ => {}
+            }
+        }
+
+        schema
+    }
+
+    /// Returns the block symbol this schema applies to
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    /// Returns the declared attribute names
+    pub fn fields(&self) -> impl Iterator<Item = &String> {
+        self.fields.keys()
+    }
+
+    /// Adds missing attributes that have a default to a graph, i.e. the context of an event created from a block
+    pub fn apply_defaults(&self, graph: &mut AttributeGraph) {
+        for (name, default) in self
+            .fields
+            .iter()
+            .filter_map(|(name, field)| field.default.as_ref().map(|default| (name, default)))
+        {
+            if graph.find_attr_value(name).is_none() {
+                graph.with(name, default.clone());
+            }
+        }
+    }
+
+    /// Validates a block, returns any errors found
+    ///
+    /// Missing attributes that have a default are not errors, see `Schema::apply_defaults`
+    ///
+    pub fn validate(&self, block: &AttributeGraph) -> Vec<SchemaErrors> {
+        let mut errors = vec![];
+
+        for (name, field) in self.fields.iter() {
+            match block.find_attr_value(name).cloned() {
+                Some(value) => {
+                    let found = Self::kind(&value);
+                    match &field.kind {
+                        Some(expected) if expected != found => {
+                            errors.push(SchemaErrors::InvalidKind {
+                                symbol: self.symbol.to_string(),
+                                name: name.to_string(),
+                                expected: expected.to_string(),
+                                found: found.to_string(),
+                            });
+                            continue;
+                        }
+                        _ => {}
+                    }
+
+                    let number = match value {
+                        Value::Int(int) => Some(int as f64),
+                        Value::Float(float) => Some(float as f64),
+                        _ => None,
+                    };
+                    if let (Some(number), Some((min, max))) = (number, field.range) {
+                        if number < min || number > max {
+                            errors.push(SchemaErrors::OutOfRange {
+                                symbol: self.symbol.to_string(),
+                                name: name.to_string(),
+                                range: (min, max),
+                            });
+                        }
+                    }
+                }
+                None if field.required && field.default.is_none() => {
+                    errors.push(SchemaErrors::Missing {
+                        symbol: self.symbol.to_string(),
+                        name: name.to_string(),
+                    });
+                }
+                None => {}
+            }
+        }
+
+        if !self.allow_unknown {
+            let entity = block.entity();
+            for attr in block.iter_attributes().filter(|a| {
+                a.id() == entity
+                    && a.is_stable()
+                    && !a.name().starts_with("block_")
+                    && !matches!(a.value(), Value::Symbol(_))
+            }) {
+                if !self.fields.contains_key(attr.name()) {
+                    errors.push(SchemaErrors::Unknown {
+                        symbol: self.symbol.to_string(),
+                        name: attr.name().to_string(),
+                    });
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Returns the runmd type name of a value
+    pub fn kind(value: &Value) -> &'static str {
+        match value {
+            Value::Empty => "empty",
+            Value::Bool(_) => "bool",
+            Value::TextBuffer(_) => "text",
+            Value::Int(_) => "int",
+            Value::IntPair(_, _) => "int2",
+            Value::IntRange(_, _, _) => "int3",
+            Value::Float(_) => "float",
+            Value::FloatPair(_, _) => "float2",
+            Value::FloatRange(_, _, _) => "float3",
+            Value::BinaryVector(_) => "bin",
+            Value::Reference(_) => "reference",
+            Value::Symbol(_) => "symbol",
+        }
+    }
+}
+
+#[test]
+fn test_schema() {
+    use crate::plugins::Project;
+
+    let project = Project::load_content(
+        r#"
+``` timer schema
+define duration kind .text int
+define duration range .int2 0, 3600
+define duration_ms kind .text float
+define quiet default .disable
+```
+
+``` demo timer
+add duraton .int 5
+add duration_ms .int 100
+```
+
+``` other timer
+add duration .int 5
+```
+"#,
+    )
+    .expect("valid");
+
+    let errors = project.validate();
+    assert_eq!(errors.len(), 1);
+    let demo = errors.get("demo").expect("has errors");
+    assert!(demo.iter().any(|e| e.name() == "timer.duraton"));
+    assert!(demo.iter().any(|e| e.name() == "timer.duration_ms"));
+
+    // Errors and defaults are not written back to the project
+    let other = project.find_block("other").expect("exists");
+    assert!(other.get_block("error").is_none());
+    assert!(project.transpile_blocks().expect("transpiles").find("quiet").is_none());
+
+    // Defaults apply to the context of an event created from the block
+    let schema = project.schemas().remove("timer").expect("declared");
+    let mut context = other.get_block("timer").expect("exists");
+    schema.apply_defaults(&mut context);
+    assert_eq!(context.is_enabled("quiet"), Some(false));
+    assert!(schema.validate(&context).is_empty());
+}
@@ -0,0 +1,587 @@
+use std::path::PathBuf;
+
+use atlier::system::Value;
+use chrono::{DateTime, Utc};
+use tracing::{event, Level};
+
+use crate::plugins::{AsyncContext, Plugin, Schedule, ThunkContext};
+use crate::{AttributeGraph, IndexKey, Predicate};
+
+/// Helpers for control flow plugins, i.e. `if`, `switch`, `while` and `for_each`
+///
+/// Control flow plugins choose the next sequence to call, by block name, i.e.
+///
+/// ````runmd
+/// ``` deploy call
+/// define a_build process .symbol build
+/// define b_check if      .symbol check
+/// ```
+///
+/// ``` check if
+/// add condition .text process.code == 0
+/// add then .text on_success
+/// add else .text on_failure
+/// ```
+///
+/// ``` on_success call
+/// define a_hello println
+/// ```
+/// ````
+///
+/// When the event is created, the runtime creates the sequence for each target and records the first entity as `entity.{target}`.
+/// When the plugin completes, it records the entity to jump to as `control_next`. The event runtime then calls that sequence, and
+/// continues w/ the rest of the current sequence afterwards. If `control_loop` is enabled, the event runtime calls the control event
+/// again after the sequence completes instead, which is how `while` and `for_each` loop. If `control_deadline` is set, the event runtime
+/// cancels the sequence at the deadline and calls the control event again, which is how `schedule` cancels an overlapping run.
+///
+/// Conditions compare an attribute of the previous context to a literal, w/ `==`, `!=`, `>`, `>=`, `<`, `<=` or `~=` for a glob.
+/// An attribute is either `{block_symbol}.{name}` for an attribute in a block handed off as `previous`, or a name at the root. A
+/// condition w/o an operator is true if the attribute exists, and is not false or 0.
+///
+pub struct Control;
+
+impl Control {
+    /// Attribute w/ the id of the entity to call next
+    pub const NEXT: &'static str = "control_next";
+    /// Attribute that is enabled if the control event should be called again after the next sequence
+    pub const LOOP: &'static str = "control_loop";
+    /// Attribute w/ the time the next sequence is cancelled at, as rfc3339
+    pub const DEADLINE: &'static str = "control_deadline";
+
+    /// Returns the symbols of the control flow plugins
+    pub fn symbols() -> [&'static str; 5] {
+        [
+            If::symbol(),
+            Switch::symbol(),
+            While::symbol(),
+            ForEach::symbol(),
+            Schedule::symbol(),
+        ]
+    }
+
+    /// Returns the names of the sequences a control flow context can jump to
+    pub fn targets(graph: &AttributeGraph) -> Vec<String> {
+        let mut targets = ["then", "else", "body", "default"]
+            .iter()
+            .filter_map(|name| graph.find_text(name))
+            .collect::<Vec<_>>();
+
+        for (name, _) in graph.find_symbol_values("case") {
+            if let Some((target, _)) = name.split_once("::") {
+                targets.push(target.to_string());
+            }
+        }
+
+        targets.sort();
+        targets.dedup();
+        targets
+    }
+
+    /// Records the entity created for a target
+    pub fn set_target(graph: &mut AttributeGraph, target: impl AsRef<str>, entity: u32) {
+        graph.with_int(format!("entity.{}", target.as_ref()), entity as i32);
+    }
+
+    /// Returns the entity created for a target
+    pub fn find_target(graph: &AttributeGraph, target: impl AsRef<str>) -> Option<u32> {
+        graph
+            .find_int(format!("entity.{}", target.as_ref()))
+            .map(|entity| entity as u32)
+    }
+
+    /// Returns the entity a completed control event jumps to
+    pub fn next_event(context: &ThunkContext) -> Option<u32> {
+        context
+            .as_ref()
+            .find_int(Self::NEXT)
+            .filter(|next| *next >= 0)
+            .map(|next| next as u32)
+    }
+
+    /// Returns true if the completed control event should be called again after the next sequence completes
+    pub fn is_loop(context: &ThunkContext) -> bool {
+        context.as_ref().is_enabled(Self::LOOP).unwrap_or_default()
+    }
+
+    /// Returns the time the sequence a completed control event jumps to should be cancelled at
+    pub fn deadline(context: &ThunkContext) -> Option<DateTime<Utc>> {
+        context
+            .as_ref()
+            .find_text(Self::DEADLINE)
+            .and_then(|deadline| DateTime::parse_from_rfc3339(&deadline).ok())
+            .map(|deadline| deadline.with_timezone(&Utc))
+    }
+
+    /// Sets the time the next sequence is cancelled at, or clears it if None
+    pub fn set_deadline(graph: &mut AttributeGraph, deadline: Option<DateTime<Utc>>) {
+        match deadline {
+            Some(deadline) => {
+                graph.with_text(Self::DEADLINE, deadline.to_rfc3339());
+            }
+            None => {
+                graph.find_remove(Self::DEADLINE);
+            }
+        }
+    }
+
+    /// Sets the next sequence to call by target name, or clears it if None
+    pub fn jump(graph: &mut AttributeGraph, target: Option<&str>, repeat: bool) {
+        let next = target.and_then(|target| {
+            let next = Self::find_target(graph, target);
+            if next.is_none() {
+                event!(Level::ERROR, "control target {target} was not created");
+            }
+            next
+        });
+
+        graph
+            .with_int(Self::NEXT, next.map(|n| n as i32).unwrap_or(-1))
+            .with_bool(Self::LOOP, next.is_some() && repeat);
+    }
+
+    /// Evaluates a condition against a graph, see `Control`
+    pub fn evaluate(graph: &AttributeGraph, condition: impl AsRef<str>) -> bool {
+        let condition = condition.as_ref().trim();
+        let operators = ["==", "!=", ">=", "<=", "~=", ">", "<"];
+
+        match operators
+            .iter()
+            .find_map(|op| condition.split_once(op).map(|(lhs, rhs)| (lhs.trim(), *op, rhs.trim())))
+        {
+            Some((lhs, op, rhs)) => {
+                let expected = Self::literal(rhs);
+                let predicate = match op {
+                    "==" => Predicate::Eq(expected),
+                    "!=" => Predicate::Ne(expected),
+                    ">=" => Predicate::Ge(expected),
+                    "<=" => Predicate::Le(expected),
+                    ">" => Predicate::Gt(expected),
+                    "<" => Predicate::Lt(expected),
+                    _ => Predicate::Matches(rhs.trim_matches('"').to_string()),
+                };
+
+                Self::find_value(graph, lhs)
+                    .map(|value| predicate.test(&value))
+                    .unwrap_or_default()
+            }
+            None => match Self::find_value(graph, condition).and_then(|v| IndexKey::from_value(&v)) {
+                Some(IndexKey::Bool(b)) => b,
+                Some(IndexKey::Number(n)) => n != 0.0,
+                Some(IndexKey::Text(_)) => true,
+                None => false,
+            },
+        }
+    }
+
+    /// Finds the value for `{block_symbol}.{name}`, or `name`
+    pub fn find_value(graph: &AttributeGraph, name: &str) -> Option<Value> {
+        if let Some((symbol, attr)) = name.split_once('.') {
+            // The latest block handed off has the highest id
+            let latest = graph
+                .iter_blocks()
+                .filter(|b| b.find_text("block_symbol").as_deref() == Some(symbol))
+                .filter(|b| b.find_attr_value(attr).is_some())
+                .max_by_key(|b| b.entity());
+            if let Some(block) = latest {
+                return block.find_attr_value(attr).cloned();
+            }
+        }
+
+        graph.find_attr_value(name).cloned()
+    }
+
+    fn literal(literal: &str) -> IndexKey {
+        match literal {
+            "true" => IndexKey::Bool(true),
+            "false" => IndexKey::Bool(false),
+            _ => match literal.parse::<f64>() {
+                Ok(number) => IndexKey::Number(number),
+                Err(_) => IndexKey::Text(literal.trim_matches('"').to_string()),
+            },
+        }
+    }
+
+    /// Returns a task that applies `previous` and then calls control on the context
+    fn task(context: &mut ThunkContext, control: fn(&mut ThunkContext)) -> Option<AsyncContext> {
+        context.clone().task(|_| {
+            let mut tc = context.clone();
+            async move {
+                tc.as_mut().apply("previous");
+                control(&mut tc);
+                Some(tc)
+            }
+        })
+    }
+}
+
+/// Control flow plugin, calls the `then` sequence if `condition` is true, otherwise the `else` sequence
+#[derive(Default)]
+pub struct If;
+
+impl Plugin<ThunkContext> for If {
+    fn symbol() -> &'static str {
+        "if"
+    }
+
+    fn description() -> &'static str {
+        "Calls the `then` sequence if `condition` is true, otherwise calls the `else` sequence"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<AsyncContext> {
+        Control::task(context, |tc| {
+            let condition = tc.as_ref().find_text("condition").unwrap_or_default();
+            let branch = if Control::evaluate(tc.as_ref(), &condition) {
+                tc.as_ref().find_text("then")
+            } else {
+                tc.as_ref().find_text("else")
+            };
+
+            event!(Level::DEBUG, "if {condition} -> {:?}", branch);
+            Control::jump(tc.as_mut(), branch.as_deref(), false);
+        })
+    }
+}
+
+/// Control flow plugin, calls the sequence of the first `case` that is equal to the value of `on`
+#[derive(Default)]
+pub struct Switch;
+
+impl Plugin<ThunkContext> for Switch {
+    fn symbol() -> &'static str {
+        "switch"
+    }
+
+    fn description() -> &'static str {
+        "Calls the sequence of the first case equal to the value of `on`, i.e. `define on_zero case .text 0`, otherwise calls `default`"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<AsyncContext> {
+        Control::task(context, |tc| {
+            let on = tc.as_ref().find_text("on").unwrap_or_default();
+            let branch = tc
+                .as_ref()
+                .find_symbol_values("case")
+                .into_iter()
+                .find_map(|(name, case)| match (name.split_once("::"), case) {
+                    (Some((target, _)), Value::TextBuffer(case))
+                        if Control::evaluate(tc.as_ref(), format!("{on} == {case}")) =>
+                    {
+                        Some(target.to_string())
+                    }
+                    _ => None,
+                })
+                .or_else(|| tc.as_ref().find_text("default"));
+
+            event!(Level::DEBUG, "switch {on} -> {:?}", branch);
+            Control::jump(tc.as_mut(), branch.as_deref(), false);
+        })
+    }
+}
+
+/// Control flow plugin, calls the `body` sequence while `condition` is true, up to `max_iterations` times
+#[derive(Default)]
+pub struct While;
+
+impl While {
+    /// Iteration limit, if `max_iterations` isn't set
+    pub const DEFAULT_MAX_ITERATIONS: i32 = 100;
+}
+
+impl Plugin<ThunkContext> for While {
+    fn symbol() -> &'static str {
+        "while"
+    }
+
+    fn description() -> &'static str {
+        "Calls the `body` sequence while `condition` is true, up to `max_iterations` times"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<AsyncContext> {
+        Control::task(context, |tc| {
+            let condition = tc.as_ref().find_text("condition").unwrap_or_default();
+            let max_iterations = tc
+                .as_ref()
+                .find_int("max_iterations")
+                .unwrap_or(While::DEFAULT_MAX_ITERATIONS);
+            let iteration = tc.as_ref().find_int("iteration").unwrap_or_default();
+
+            let body = tc.as_ref().find_text("body");
+            if !Control::evaluate(tc.as_ref(), &condition) {
+                tc.as_mut().with_int("iteration", 0);
+                Control::jump(tc.as_mut(), None, false);
+            } else if iteration >= max_iterations {
+                event!(Level::WARN, "while {condition} reached max_iterations {max_iterations}, exiting loop");
+                tc.as_mut().with_int("iteration", 0);
+                Control::jump(tc.as_mut(), None, false);
+            } else {
+                tc.as_mut().with_int("iteration", iteration + 1);
+                Control::jump(tc.as_mut(), body.as_deref(), true);
+            }
+        })
+    }
+}
+
+/// Control flow plugin, calls the `body` sequence for each value in `items`, or each entry in the directory `dir`
+///
+/// Values are comma separated, i.e. `add items .text a, b, c`. The current value is handed off to the body in a `for_each` block,
+/// w/ `item` and `index`.
+///
+#[derive(Default)]
+pub struct ForEach;
+
+impl ForEach {
+    /// Returns the items to iterate over
+    pub fn items(graph: &AttributeGraph) -> Vec<String> {
+        if let Some(items) = graph.find_text("items") {
+            return items
+                .split(',')
+                .map(str::trim)
+                .filter(|i| !i.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+
+        if let Some(dir) = graph.find_text("dir") {
+            let mut entries = std::fs::read_dir(&dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.path())
+                        .map(|p: PathBuf| p.to_string_lossy().to_string())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_else(|err| {
+                    event!(Level::ERROR, "could not read dir {dir}, {err}");
+                    vec![]
+                });
+            entries.sort();
+            return entries;
+        }
+
+        vec![]
+    }
+}
+
+impl Plugin<ThunkContext> for ForEach {
+    fn symbol() -> &'static str {
+        "for_each"
+    }
+
+    fn description() -> &'static str {
+        "Calls the `body` sequence for each value in `items`, or for each entry in `dir`"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<AsyncContext> {
+        Control::task(context, |tc| {
+            let items = ForEach::items(tc.as_ref());
+            let index = tc.as_ref().find_int("index").unwrap_or_default().max(0) as usize;
+
+            match items.get(index) {
+                Some(item) => {
+                    let block_name = tc.block.block_name.to_string();
+                    if let Some(project) = tc.project.as_mut() {
+                        *project = project.with_block(block_name, "for_each", |b| {
+                            b.with_text("item", item).with_int("index", index as i32);
+                        });
+                    }
+
+                    tc.as_mut().with_int("index", index as i32 + 1);
+                    let body = tc.as_ref().find_text("body");
+                    Control::jump(tc.as_mut(), body.as_deref(), true);
+                }
+                None => {
+                    tc.as_mut().with_int("index", 0);
+                    Control::jump(tc.as_mut(), None, false);
+                }
+            }
+        })
+    }
+}
+
+#[test]
+fn test_control() {
+    use crate::RuntimeDispatcher;
+
+    let mut graph = AttributeGraph::from(0);
+    graph
+        .batch_mut(
+            r#"
+add name .text lifec
+add enabled .enable
+``` make process
+add code .int 0
+add command .text make build
+```
+"#,
+        )
+        .expect("valid");
+
+    assert!(Control::evaluate(&graph, "process.code == 0"));
+    assert!(!Control::evaluate(&graph, "process.code != 0"));
+    assert!(Control::evaluate(&graph, "process.code < 1"));
+    assert!(Control::evaluate(&graph, "process.command ~= make *"));
+    assert!(Control::evaluate(&graph, "name == lifec"));
+    assert!(Control::evaluate(&graph, "enabled"));
+    assert!(!Control::evaluate(&graph, "missing"));
+
+    let mut graph = AttributeGraph::from(0);
+    graph
+        .batch_mut(
+            r#"
+add then .text on_success
+add else .text on_failure
+define on_zero case .text 0
+"#,
+        )
+        .expect("valid");
+    assert_eq!(Control::targets(&graph), vec!["on_failure", "on_success", "on_zero"]);
+
+    Control::set_target(&mut graph, "on_success", 5);
+    let mut context = ThunkContext::from(graph);
+    Control::jump(context.as_mut(), Some("on_success"), true);
+    assert_eq!(Control::next_event(&context), Some(5));
+    assert!(Control::is_loop(&context));
+
+    Control::jump(context.as_mut(), None, true);
+    assert_eq!(Control::next_event(&context), None);
+    assert!(!Control::is_loop(&context));
+
+    let mut items = AttributeGraph::from(0);
+    items.with_text("items", "a, b,c");
+    assert_eq!(ForEach::items(&items), vec!["a", "b", "c"]);
+}
+
+#[test]
+fn test_control_events() {
+    use crate::editor::{Call, RuntimeEditor};
+    use crate::plugins::{Event, Println, Project};
+    use specs::{Entity, WorldExt};
+    use std::time::{Duration, Instant};
+
+    let (mut world, dispatcher) = Call::standalone::<RuntimeEditor>();
+    let mut dispatcher = dispatcher.build();
+    dispatcher.setup(&mut world);
+
+    let project = Project::load_content(
+        r#"
+``` demo call
+define a_then if       .symbol check_then
+define b_else if       .symbol check_else
+define c_spin while    .symbol spin
+define d_each for_each .symbol each
+define e_done println  .symbol done
+```
+
+``` check_then if
+add always .enable
+add condition .text always
+add then .text on_then
+add else .text on_skipped
+```
+
+``` check_else if
+add condition .text never
+add then .text on_skipped
+add else .text on_else
+```
+
+``` spin while
+add always .enable
+add condition .text always
+add max_iterations .int 2
+add body .text on_spin
+```
+
+``` each for_each
+add items .text a, b
+add body .text on_each
+```
+
+``` on_then call
+define a_mark println .symbol mark_then
+```
+
+``` on_else call
+define a_mark println .symbol mark_else
+```
+
+``` on_skipped call
+define a_mark println .symbol mark_skipped
+```
+
+``` on_spin call
+define a_mark println .symbol mark_spin
+```
+
+``` on_each call
+define a_mark println .symbol mark_each
+```
+
+``` mark_then println
+add marker .text then
+```
+
+``` mark_else println
+add marker .text else
+```
+
+``` mark_skipped println
+add marker .text skipped
+```
+
+``` mark_spin println
+add marker .text spin
+```
+
+``` mark_each println
+add marker .text each
+```
+
+``` done println
+add marker .text done
+```
+"#,
+    )
+    .expect("valid");
+
+    let mut runtime = crate::Runtime::new(project);
+    runtime.install::<Call, If>();
+    runtime.install::<Call, While>();
+    runtime.install::<Call, ForEach>();
+    runtime.install::<Call, Println>();
+
+    let mut completions = world
+        .read_resource::<tokio::sync::broadcast::Sender<Entity>>()
+        .subscribe();
+    let start = runtime.create_engine::<Call>(&world, "demo".to_string()).expect("created");
+    crate::Runtime::start_event(start, &world);
+
+    // Each completed event is recorded by it's marker, or by it's plugin if it's a control event
+    let mut completed = vec![];
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while completed.last().map(String::as_str) != Some("done") && Instant::now() < deadline {
+        dispatcher.dispatch(&world);
+        world.maintain();
+
+        while let Ok(entity) = completions.try_recv() {
+            let marker = world
+                .read_component::<ThunkContext>()
+                .get(entity)
+                .and_then(|tc| tc.as_ref().find_text("marker"));
+            let plugin = world
+                .read_component::<Event>()
+                .get(entity)
+                .map(|e| e.plugin_symbol().to_string());
+            completed.extend(marker.or(plugin));
+        }
+    }
+
+    // Both branches of if, while up to max_iterations, each item of for_each, and then the rest of the parent sequence
+    assert_eq!(
+        completed,
+        vec![
+            "if", "then", "if", "else", "while", "spin", "while", "spin", "while", "for_each", "each", "for_each",
+            "each", "for_each", "done"
+        ]
+    );
+}
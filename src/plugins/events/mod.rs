@@ -1,11 +1,15 @@
+use chrono::{DateTime, Utc};
 use hyper::Client;
 use hyper_tls::HttpsConnector;
 use specs::Entity;
 use specs::ReadStorage;
 use specs::World;
-use specs::{shred::SetupHandler, Component, Entities, Join, Read, System, WorldExt, WriteStorage};
+use specs::{shred::SetupHandler, Component, Entities, Join, Read, System, WorldExt, Write, WriteStorage};
 use tracing::Level;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Display;
+use std::time::Instant;
 use tokio::sync::broadcast;
 use tokio::{
     runtime::Runtime,
@@ -16,17 +20,30 @@ use tokio::{
     task::JoinHandle,
 };
 use tracing::event;
+use tracing::info_span;
+use tracing::Instrument;
+use tracing::Span;
 
 use crate::AttributeGraph;
+use crate::CatalogSystem;
+use crate::Clock;
+use crate::Random;
+use crate::Encoding;
 use crate::Extension;
+use crate::Metrics;
+use crate::ResultCache;
+use crate::ContentHash;
+use crate::QuerySubscriber;
 
 use super::Archive;
 use super::BlockAddress;
 use super::Project;
 use super::thunks::CancelThunk;
 use super::thunks::ErrorContext;
+use super::thunks::FileWatchers;
+use super::thunks::LogBuffer;
 use super::thunks::StatusUpdate;
-use super::{Plugin, Thunk, ThunkContext};
+use super::{Control, Pipeline, Plugin, SchemaErrors, Thunk, ThunkContext};
 use crate::plugins::thunks::Config;
 use specs::storage::VecStorage;
 
@@ -49,6 +66,8 @@ pub struct Event(
     Option<Config>,
     Option<ThunkContext>,
     Option<JoinHandle<ThunkContext>>,
+    Option<Pipeline>,
+    Vec<SchemaErrors>,
 );
 
 impl Display for Event {
@@ -66,7 +85,25 @@ impl Event {
     where
         P: Plugin<ThunkContext> + Default + Send,
     {
-        Self(event_name, Thunk::from_plugin::<P>(), None, None, None)
+        Self(event_name, Thunk::from_plugin::<P>(), None, None, None, None, vec![])
+    }
+
+    /// Sets the pipeline to call instead of the plugin's thunk, see `Pipeline`
+    pub fn set_pipeline(&mut self, pipeline: Pipeline) {
+        self.5 = Some(pipeline);
+    }
+
+    /// Returns the pipeline this event calls, if set
+    pub fn pipeline(&self) -> Option<&Pipeline> {
+        self.5.as_ref()
+    }
+
+    /// Sets the schema errors of the block this event was created from, see `Schema`
+    ///
+    /// If there are errors, the event runtime records them as the event's errors instead of calling the plugin.
+    ///
+    pub fn set_schema_errors(&mut self, errors: Vec<SchemaErrors>) {
+        self.6 = errors;
     }
 
     /// Sets the config to use w/ this event
@@ -107,6 +144,11 @@ impl Event {
             .unwrap_or_default()
     }
 
+    /// returns true if the event isn't waiting to start, running, or waiting for the event runtime to handle it's result
+    pub fn is_idle(&self) -> bool {
+        self.3.is_none() && self.4.is_none()
+    }
+
     /// subscribe to get a notification when the runtime editor has updated an entity
     pub fn subscribe(world: &World) -> sync::broadcast::Receiver<Entity> {
         let sender = world.write_resource::<sync::broadcast::Sender<Entity>>();
@@ -128,15 +170,77 @@ impl Event {
         }
     }
 
+    /// Returns the symbol of the plugin this event calls
+    pub fn plugin_symbol(&self) -> &'static str {
+        self.1 .0
+    }
+
     /// Creates a duplicate of this event
     pub fn duplicate(&self) -> Self {
-        Self(self.0, self.1.clone(), self.2.clone(), None, None)
+        Self(self.0, self.1.clone(), self.2.clone(), None, None, self.5.clone(), self.6.clone())
     }
 }
 
 /// Event runtime drives the tokio::Runtime and schedules/monitors/orchestrates task entities
+/// 
+/// Each event is started inside of a tracing span w/ the entity, block name, plugin symbol and hash_code of the context.
+/// If the entity is part of a sequence, the event span is nested under a span for the sequence, which stays open until the
+/// sequence completes.
+/// 
+/// If an event enables `cache`, the plugin result is looked up in the ResultCache resource before the plugin is called.
+/// 
+/// Contexts are started w/ the Clock and Random resources, so that plugins read time from a virtual clock, and randomness from a seeded
+/// source when they are inserted in the world, see `EventRuntime::enable_deterministic`. Contexts also share the FileWatchers resource,
+/// and the watchers of deleted entities are dropped each run.
+/// 
 #[derive(Default)]
-pub struct EventRuntime;
+pub struct EventRuntime {
+    /// Spans for sequences that are in progress, keyed by the entity w/ the next event in the sequence
+    sequence_spans: HashMap<Entity, Span>,
+    /// Time each running event was started, used to record event latency
+    started: HashMap<Entity, Instant>,
+    /// Entities whose last event returned an error, the next event started for these entities is counted as a retry
+    failed: HashSet<Entity>,
+    /// Cache keys of running events that opted in to caching, the result is cached when the event completes w/o errors
+    cache_keys: HashMap<Entity, ContentHash>,
+    /// Deadlines of control events, when the deadline passes the sequence the control event jumped to is cancelled
+    deadlines: HashMap<Entity, DateTime<Utc>>,
+    /// Events cancelled by a deadline, when these complete the control event is called instead of the rest of the sequence
+    preempted: HashSet<Entity>,
+}
+
+impl EventRuntime {
+    /// Capacity of the status update channel
+    pub const STATUS_UPDATE_CAPACITY: usize = 30;
+    /// Capacity of the broadcast channel for completed entities
+    pub const COMPLETED_CAPACITY: usize = 100;
+    /// Capacity of the channel for dispatching attribute graphs
+    pub const DISPATCHER_CAPACITY: usize = 10;
+    /// Capacity of the channel for error contexts
+    pub const ERROR_CAPACITY: usize = 10;
+
+    /// Inserts a deterministic clock starting at the unix epoch, and a seeded source of randomness,
+    /// 
+    /// Built-in plugins read time and randomness from these resources, so running the same project twice produces the same output graphs.
+    /// 
+    pub fn enable_deterministic(world: &mut World, seed: u64) {
+        world.insert(Clock::deterministic(Clock::epoch()));
+        world.insert(Random::seeded(seed));
+    }
+}
+
+/// Resource w/ the parent span for events started by this world,
+/// 
+/// The runtime plugin sets this to a span nested under the event that started the runtime, so that events 
+/// in nested `runtime` worlds are nested under their caller.
+/// 
+pub struct RuntimeSpan(pub Span);
+
+impl Default for RuntimeSpan {
+    fn default() -> Self {
+        Self(Span::none())
+    }
+}
 
 impl Extension for EventRuntime {
     fn configure_app_world(world: &mut specs::World) {
@@ -144,10 +248,13 @@ impl Extension for EventRuntime {
         world.register::<ThunkContext>();
         world.register::<CancelThunk>();
         world.register::<ErrorContext>();
+        world.register::<LogBuffer>();
     }
 
     fn configure_app_systems(dispatcher: &mut specs::DispatcherBuilder) {
         dispatcher.add(EventRuntime::default(), "event_runtime", &[]);
+        dispatcher.add(CatalogSystem::default(), "catalog", &["event_runtime"]);
+        dispatcher.add(QuerySubscriber::default(), "query_subscriber", &["event_runtime"]);
     }
 }
 
@@ -163,16 +270,26 @@ impl SetupHandler<Runtime> for EventRuntime {
 /// Setup for tokio-mulitple-producers single-consumer channel for status updates
 impl SetupHandler<sync::mpsc::Sender<StatusUpdate>> for EventRuntime {
     fn setup(world: &mut specs::World) {
-        let (tx, rx) = mpsc::channel::<StatusUpdate>(30);
+        let (tx, rx) = mpsc::channel::<StatusUpdate>(EventRuntime::STATUS_UPDATE_CAPACITY);
         world.insert(tx);
         world.insert(rx);
     }
 }
 
+/// Setup for the receiving end of the status update channel, the event runtime drains it into each entity's log buffer
+impl SetupHandler<sync::mpsc::Receiver<StatusUpdate>> for EventRuntime {
+    fn setup(world: &mut specs::World) {
+        // The channel is created w/ the sender, replacing the receiver would disconnect the sender
+        if !world.has_value::<sync::mpsc::Receiver<StatusUpdate>>() {
+            <EventRuntime as SetupHandler<Sender<StatusUpdate>>>::setup(world);
+        }
+    }
+}
+
 /// Setup for tokio-broadcast channel for entity updates
 impl SetupHandler<sync::broadcast::Sender<Entity>> for EventRuntime {
     fn setup(world: &mut specs::World) {
-        let (tx, rx) = broadcast::channel::<Entity>(100);
+        let (tx, rx) = broadcast::channel::<Entity>(EventRuntime::COMPLETED_CAPACITY);
         world.insert(rx);
         world.insert(tx);
     }
@@ -181,7 +298,7 @@ impl SetupHandler<sync::broadcast::Sender<Entity>> for EventRuntime {
 /// Setup for tokio-mulitple-producers single-consumer channel for status updates
 impl SetupHandler<sync::mpsc::Sender<AttributeGraph>> for EventRuntime {
     fn setup(world: &mut specs::World) {
-        let (tx, rx) = mpsc::channel::<AttributeGraph>(10);
+        let (tx, rx) = mpsc::channel::<AttributeGraph>(EventRuntime::DISPATCHER_CAPACITY);
         world.insert(tx);
         world.insert(rx);
     }
@@ -190,7 +307,7 @@ impl SetupHandler<sync::mpsc::Sender<AttributeGraph>> for EventRuntime {
 /// Setup for tokio-mulitple-producers single-consumer channel for status updates
 impl SetupHandler<sync::mpsc::Receiver<AttributeGraph>> for EventRuntime {
     fn setup(world: &mut specs::World) {
-        let (tx, rx) = mpsc::channel::<AttributeGraph>(10);
+        let (tx, rx) = mpsc::channel::<AttributeGraph>(EventRuntime::DISPATCHER_CAPACITY);
         world.insert(tx);
         world.insert(rx);
     }
@@ -199,7 +316,7 @@ impl SetupHandler<sync::mpsc::Receiver<AttributeGraph>> for EventRuntime {
 /// Setup for tokio-mulitple-producers single-consumer channel for status updates
 impl SetupHandler<sync::mpsc::Sender<ErrorContext>> for EventRuntime {
     fn setup(world: &mut specs::World) {
-        let (tx, rx) = mpsc::channel::<ErrorContext>(10);
+        let (tx, rx) = mpsc::channel::<ErrorContext>(EventRuntime::ERROR_CAPACITY);
         world.insert(tx);
         world.insert(rx);
     }
@@ -219,10 +336,17 @@ impl<'a> System<'a> for EventRuntime {
     type SystemData = (
         Read<'a, Runtime, EventRuntime>,
         Read<'a, Sender<StatusUpdate>, EventRuntime>,
+        Write<'a, mpsc::Receiver<StatusUpdate>, EventRuntime>,
         Read<'a, Sender<AttributeGraph>, EventRuntime>,
         Read<'a, Sender<ErrorContext>, EventRuntime>,
         Read<'a, sync::broadcast::Sender<Entity>, EventRuntime>,
         Read<'a, Project>,
+        Read<'a, RuntimeSpan>,
+        Read<'a, Metrics>,
+        Read<'a, ResultCache>,
+        Read<'a, Clock>,
+        Read<'a, Random>,
+        Read<'a, FileWatchers>,
         Entities<'a>,
         ReadStorage<'a, Connection>,
         WriteStorage<'a, Event>,
@@ -232,6 +356,7 @@ impl<'a> System<'a> for EventRuntime {
         WriteStorage<'a, ErrorContext>,
         WriteStorage<'a, Archive>,
         WriteStorage<'a, BlockAddress>,
+        WriteStorage<'a, LogBuffer>,
     );
 
     fn run(
@@ -239,10 +364,17 @@ impl<'a> System<'a> for EventRuntime {
         (
             runtime,
             status_update_channel,
+            mut status_updates,
             dispatcher,
             error_dispatcher,
             thunk_complete_channel,
             project,
+            runtime_span,
+            metrics,
+            result_cache,
+            clock,
+            random,
+            file_watchers,
             entities,
             connections,
             mut events,
@@ -252,16 +384,71 @@ impl<'a> System<'a> for EventRuntime {
             mut error_contexts,
             mut archives,
             mut block_addresses,
+            mut log_buffers,
         ): Self::SystemData,
     ) {
         let mut dispatch_queue = vec![];
 
+        file_watchers.maintain(&entities);
+
+        metrics.set_gauge(
+            "lifec_status_update_queue_depth",
+            Self::STATUS_UPDATE_CAPACITY.saturating_sub(status_update_channel.capacity()) as f64,
+        );
+        metrics.set_gauge(
+            "lifec_dispatcher_queue_depth",
+            Self::DISPATCHER_CAPACITY.saturating_sub(dispatcher.capacity()) as f64,
+        );
+        metrics.set_gauge(
+            "lifec_error_queue_depth",
+            Self::ERROR_CAPACITY.saturating_sub(error_dispatcher.capacity()) as f64,
+        );
+
+        // Record status updates to each entity's log buffer, so that runners w/o an editor can show or save the log
+        while let Ok((entity, progress, status)) = status_updates.try_recv() {
+            if let Some(log_buffer) = log_buffers.get_mut(entity) {
+                log_buffer.update(progress, status);
+            } else {
+                let mut log_buffer = LogBuffer::default();
+                log_buffer.update(progress, status);
+                log_buffers.insert(entity, log_buffer).ok();
+            }
+        }
+
+        // Cancel sequences that are still running past the deadline of the control event that called them
+        let now = clock.now();
+        let expired = self
+            .deadlines
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(control, _)| *control)
+            .collect::<Vec<_>>();
+        for control in expired {
+            self.deadlines.remove(&control);
+            for (entity, sequence, event) in (&entities, &sequences, &events).join() {
+                if entity != control && sequence.cursor() == Some(control) && event.is_running() {
+                    if let Some(CancelThunk(cancel)) = cancel_tokens.remove(entity) {
+                        event!(Level::DEBUG, "deadline of {} passed, cancelling {}", control.id(), entity.id());
+                        cancel.send(()).ok();
+                        self.preempted.insert(entity);
+                    }
+                }
+            }
+        }
+
         for (entity, _connection, event) in (&entities, connections.maybe(), &mut events).join() {
             let event_name = event.to_string();
-            let Event(_, thunk, _, initial_context, task) = event;
+            let Event(_, thunk, _, initial_context, task, pipeline, schema_errors) = event;
             if let Some(current_task) = task.take() {
                 if current_task.is_finished() {
+                    if let Some(started) = self.started.remove(&entity) {
+                        metrics.observe_duration("lifec_event_duration_seconds", thunk.0, started.elapsed());
+                    }
+                    let cache_key = self.cache_keys.remove(&entity);
+
                     if let Some(thunk_context) = runtime.block_on(async { current_task.await.ok() }) {
+                        metrics.increment("lifec_events_completed_total", thunk.0);
+
                         // If the context enabled it's address, add the block address to world storage
                         if thunk_context.socket_address().is_some() && !block_addresses.contains(entity) {
                             if let Some(block_address) = thunk_context.to_block_address() {
@@ -278,6 +465,8 @@ impl<'a> System<'a> for EventRuntime {
 
                         if let Some(error_context) = thunk_context.get_errors() {
                             event!(Level::ERROR, "plugin error context generated");
+                            metrics.increment("lifec_event_errors_total", thunk.0);
+                            self.failed.insert(entity);
                             let thunk_context = thunk_context.clone();
 
                             if let Some(previous) = error_contexts.insert(entity, error_context.clone()).ok() {
@@ -319,19 +508,71 @@ impl<'a> System<'a> for EventRuntime {
                                     );
 
                                     contexts.insert(entity, clone).ok();
+                                    self.sequence_spans.remove(&entity);
                                     continue;
                                 }
                             }
                         }
 
+                        if let (Some(key), None) = (cache_key, thunk_context.get_errors()) {
+                            if let Err(err) = result_cache.put(&key, &thunk_context) {
+                                event!(Level::WARN, "could not cache result for {}, {err}", &event_name);
+                            }
+                        }
+
                         match contexts.insert(entity, thunk_context.clone()) {
                             Ok(_) => {
                                 thunk_complete_channel.send(entity).ok();
 
-                                // if the entity has a sequence, dispatch the next event
-                                if let Some(sequence) = sequences.get(entity) {
+                                // if the event was cancelled by a deadline, skip the rest of the sequence and call the control event again
+                                let preempted = self.preempted.remove(&entity)
+                                    .then(|| sequences.get(entity).and_then(|s| s.cursor()))
+                                    .flatten();
+                                if let Some(control) = preempted {
+                                    self.sequence_spans.remove(&entity);
+                                    event!(Level::DEBUG, "{} was preempted, returning to {}", entity.id(), control.id());
+                                    dispatch_queue.push((control, thunk_context));
+                                } else if let Some(jump) = Control::next_event(&thunk_context).map(|id| entities.entity(id)) {
+                                    let mut continuation = sequences.get(entity).cloned().unwrap_or_default();
+
+                                    // after the jumped to sequence completes, either call the control event again, or continue the current sequence
+                                    let cursor = if Control::is_loop(&thunk_context) {
+                                        Some(entity)
+                                    } else if let Some(next_event) = continuation.next() {
+                                        sequences.insert(next_event, continuation).ok();
+                                        Some(next_event)
+                                    } else {
+                                        continuation.cursor()
+                                    };
+
+                                    let mut branch = sequences.get(jump).cloned().unwrap_or_default();
+                                    match cursor {
+                                        Some(cursor) => branch.set_cursor(cursor),
+                                        None => branch.clear_cursor(),
+                                    }
+                                    sequences.insert(jump, branch).ok();
+
+                                    if let Some(sequence_span) = self.sequence_spans.remove(&entity) {
+                                        self.sequence_spans.insert(jump, sequence_span);
+                                    }
+                                    match Control::deadline(&thunk_context) {
+                                        Some(deadline) => {
+                                            self.deadlines.insert(entity, deadline);
+                                        }
+                                        None => {
+                                            self.deadlines.remove(&entity);
+                                        }
+                                    }
+                                    event!(Level::DEBUG, "{} jumping to {}", entity.id(), jump.id());
+                                    dispatch_queue.push((jump, thunk_context));
+                                } else if let Some(sequence) = sequences.get(entity) {
+                                    let sequence_span = self.sequence_spans.remove(&entity);
                                     let mut next = sequence.clone();
                                     if let Some(next_event) = next.next() {
+                                        if let Some(sequence_span) = sequence_span {
+                                            self.sequence_spans.insert(next_event, sequence_span);
+                                        }
+
                                         match sequences.insert(next_event, next.clone()).ok() {
                                             Some(_) => {
                                                 dispatch_queue.push((next_event, thunk_context));
@@ -381,12 +622,70 @@ impl<'a> System<'a> for EventRuntime {
                         Some(dispatcher.clone()),
                     );
 
+                context.set_clock(clock.clone());
+                context.set_random(random.clone());
+                context.set_file_watchers(file_watchers.clone());
+
                 let Thunk(thunk_name, thunk) = thunk;
-                // TODO it would be really helpful to add a macro for these status updates
-                // OR could implement AsyncWrite, so you can do:
-                // ``` writeln!(context, "# event received", &event_name, hash_code).await.ok();
 
-                if let Some((handle, cancel_token)) = thunk(&mut context) {
+                // If the entity is part of a sequence, the event is nested under the sequence's span
+                let parent = if sequences.contains(entity) {
+                    self.sequence_spans
+                        .entry(entity)
+                        .or_insert_with(|| info_span!(
+                            parent: &runtime_span.0,
+                            "sequence",
+                            entity = entity.id(),
+                            block_name = %initial_context.block.block_name,
+                        ))
+                        .clone()
+                } else {
+                    runtime_span.0.clone()
+                };
+
+                let span = info_span!(
+                    parent: &parent,
+                    "event",
+                    entity = entity.id(),
+                    block_name = %initial_context.block.block_name,
+                    plugin = thunk_name,
+                    hash_code = initial_context.as_ref().hash_code(),
+                );
+
+                // If the block this event was created from is invalid, record the errors and skip the plugin
+                let invalid = !schema_errors.is_empty();
+                if invalid {
+                    event!(parent: &span, Level::ERROR, "{} is invalid, skipping {}", initial_context.block.block_name, &event_name);
+                    context.error(|g| {
+                        for error in schema_errors.iter() {
+                            g.with_text(error.name(), error.to_string());
+                        }
+                    });
+                }
+
+                // If the event opted in to caching, and the result is cached, skip the plugin and restore the result
+                let cache_key = if !invalid && ResultCache::is_enabled(&context) {
+                    ResultCache::key(thunk_name, &context)
+                } else {
+                    None
+                };
+                let cached = cache_key.as_ref().and_then(|key| result_cache.get(key, &context));
+                if cached.is_some() {
+                    event!(parent: &span, Level::DEBUG, "cache hit, skipping {}", &event_name);
+                    metrics.increment("lifec_cache_hits_total", thunk_name);
+                } else if let Some(key) = cache_key {
+                    metrics.increment("lifec_cache_misses_total", thunk_name);
+                    self.cache_keys.insert(entity, key);
+                }
+
+                // A cached result is returned from a task, so that it completes the same way as the plugin would have
+                let call = || match (cached, pipeline) {
+                    _ if invalid => context.task(|_| async { None }),
+                    (Some(cached), _) => context.task(|_| async move { Some(cached) }),
+                    (None, Some(pipeline)) => pipeline.call(&mut context),
+                    (None, None) => thunk(&mut context),
+                };
+                if let Some((handle, cancel_token)) = span.in_scope(call) {
                     match cancel_tokens.insert(entity, CancelThunk::from(cancel_token)) {
                         Ok(existing) => {
                             // If an existing cancel token existed, send a message now
@@ -395,7 +694,13 @@ impl<'a> System<'a> for EventRuntime {
                                 cancel.send(()).ok();
                             }
 
-                            let mut started = context.clone();                            
+                            metrics.increment("lifec_events_started_total", thunk_name);
+                            if self.failed.remove(&entity) {
+                                metrics.increment("lifec_event_retries_total", thunk_name);
+                            }
+                            self.started.insert(entity, Instant::now());
+
+                            let mut started = context.clone();
                             started.as_mut()
                                 .with_text(
                                     "thunk_symbol", 
@@ -404,41 +709,40 @@ impl<'a> System<'a> for EventRuntime {
 
                             // Initializes and starts the task by spawning it on the runtime
                             *task = Some(runtime.spawn(async move {
-                                context
-                                    .update_status_only(format!(
-                                        "# event received: {}, {}",
-                                        &event_name,
-                                        initial_context.as_ref().hash_code()
-                                    ))
-                                    .await;
+                                let mut log = context.writer();
+                                log.writeln(format!(
+                                    "# event received: {}, {}",
+                                    &event_name,
+                                    initial_context.as_ref().hash_code()
+                                ))
+                                .await
+                                .ok();
 
                                 match handle.await {
                                     Ok(mut updated_context) => {
-                                        context
-                                            .update_status_only(format!(
-                                                "# completed: {}",
-                                                &event_name
-                                            ))
-                                            .await;
+                                        log.writeln(format!("# completed: {}", &event_name))
+                                            .await
+                                            .ok();
                                         updated_context
                                             .as_mut()
                                             .add_text_attr("thunk_symbol", thunk_name);
                                         updated_context
                                     }
                                     Err(err) => {
+                                        log.writeln(format!(
+                                            "[error] # event error: {}, {}",
+                                            &event_name, err
+                                        ))
+                                        .await
+                                        .ok();
                                         context.error(|g| {
                                             g.with_text("event_runtime", format!("{}", err));
                                         });
                                         context
-                                            .update_status_only(format!(
-                                                "# event error: {}, {}",
-                                                &event_name, err
-                                            ))
-                                            .await;
-                                        context
                                     }
                                 }
-                            }));
+                            }
+                            .instrument(span)));
 
                             contexts.insert(entity, started).ok();
                         }
@@ -475,14 +779,9 @@ impl<'a> System<'a> for EventRuntime {
                         (events.get_mut(next), contexts.get_mut(next))
                     {
                         let last_id = last.as_ref().entity();
-                        let previous = last.project
-                                .and_then(|p| p.transpile_blocks().ok())
-                                .unwrap_or_default()
-                                .trim()
-                                .to_string();
-
-                        if !previous.trim().is_empty() {
-                            context.as_mut().add_message(
+                        let encoding = Encoding::find(context.as_ref());
+                        if let Some(previous) = last.project.and_then(|p| p.encode_blocks(encoding)) {
+                            context.as_mut().add_binary_message(
                                 event.to_string(),
                                 "previous",
                                 previous,
@@ -508,3 +807,83 @@ impl<'a> System<'a> for EventRuntime {
         }
     }
 }
+
+#[test]
+fn test_deterministic() {
+    use crate::editor::{Call, RuntimeEditor};
+    use crate::plugins::{Engine, Process, Timer};
+
+    let run = || {
+        let (mut world, dispatcher) = Call::standalone::<RuntimeEditor>();
+        let mut dispatcher = dispatcher.build();
+        dispatcher.setup(&mut world);
+        EventRuntime::enable_deterministic(&mut world, 7);
+
+        let project = Project::load_content(
+            r#"
+``` demo call
+define a_timer   timer   .symbol wait
+define b_process process .symbol echo
+```
+
+``` wait timer
+add duration .int 2
+```
+
+``` echo process
+add command .text echo hello
+```
+"#,
+        )
+        .expect("valid");
+
+        let mut runtime = super::Runtime::new(project);
+        runtime.install::<Call, Timer>();
+        runtime.install::<Call, Process>();
+
+        let mut completions = world.read_resource::<broadcast::Sender<Entity>>().subscribe();
+        let start = runtime.create_engine::<Call>(&world, "demo".to_string()).expect("created");
+        super::Runtime::start_event(start, &world);
+
+        let mut completed = 0;
+        let deadline = Instant::now() + std::time::Duration::from_secs(10);
+        while completed < 2 && Instant::now() < deadline {
+            dispatcher.dispatch(&world);
+            world.maintain();
+
+            while completions.try_recv().is_ok() {
+                completed += 1;
+            }
+        }
+        assert_eq!(completed, 2);
+
+        let contexts = world.read_component::<ThunkContext>();
+        let process = (&contexts)
+            .join()
+            .find_map(|tc| {
+                tc.project
+                    .as_ref()
+                    .and_then(|p| p.find_block("echo"))
+                    .and_then(|b| b.get_block("process"))
+            })
+            .expect("process completed");
+        // The timer advanced the clock by 2 seconds, local time is UTC so it doesn't depend on the machine
+        assert_eq!(process.find_text("timestamp_utc").expect("recorded"), "1970-01-01 00:00:02 UTC");
+        assert_eq!(process.find_text("timestamp_local").expect("recorded"), "1970-01-01 00:00:02 +00:00");
+
+        // Status updates are recorded to log buffers w/o an editor
+        dispatcher.dispatch(&world);
+        let log_buffers = world.read_component::<LogBuffer>();
+        assert!((&log_buffers).join().any(|l| l.lines().any(|(_, line)| line.contains("hello"))));
+
+        (&world.entities(), &contexts)
+            .join()
+            .map(|(entity, tc)| {
+                let project = tc.project.as_ref().map(|p| p.as_ref().to_bytes());
+                (entity.id(), tc.as_ref().to_bytes(), project)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(run(), run());
+}
@@ -3,6 +3,7 @@ use specs::{System, ReadStorage, Entities, WriteStorage, Join};
 use tracing::{event, Level};
 use crate::plugins::network::Proxy;
 use crate::plugins::{ThunkContext, Project, BlockContext};
+use crate::{AttributeGraph, Encoding};
 
 /// Proxy dispatcher is a system for use in the standalone runtime context,
 /// 
@@ -32,11 +33,13 @@ impl<'a> System<'a> for ProxyDispatcher {
                 match proxies.insert(entity, Proxy::default()) {
                     Ok(_) => {
                         if let Some(dispatcher) = self.0.dispatcher() {
-                            let mut graph = context.as_ref().clone(); 
-                            let mut message = Project::default();
+                            // Sensitive values are not forwarded to the owning runtime
+                            let mut graph = context.as_ref().redacted(); 
+                            let mut message = AttributeGraph::default();
 
                             if let (Some(block_name), Some(block_symbol)) = (graph.find_text("block_name"), graph.find_text("block_symbol")) {
-                                message = message.with_block(block_name, block_symbol, |c| {
+                                let encoding = Encoding::find(&graph);
+                                let mut copy_block = |c: &mut AttributeGraph| {
                                     for attr in BlockContext::iter_block_attrs_mut(&mut graph) {
                                         // TODO - this is copied from lib.rs, need to consolidate
                                         if !attr.is_stable() {
@@ -45,8 +48,7 @@ impl<'a> System<'a> for ProxyDispatcher {
                                                     let symbol = symbol.trim_end_matches("::");
                                                     let name = attr.name().trim_end_matches(&format!("::{symbol}"));
                         
-                                                    c.as_mut()
-                                                        .define(name, symbol)
+                                                    c.define(name, symbol)
                                                         .edit_as(value.clone());
                                                 }
                                             }
@@ -55,10 +57,24 @@ impl<'a> System<'a> for ProxyDispatcher {
                                             c.with(name, value.clone());
                                         }
                                     }
-                                });
+                                };
+
+                                message = match encoding {
+                                    Encoding::Text => Project::default()
+                                        .with_block(block_name, block_symbol, copy_block)
+                                        .as_ref()
+                                        .clone(),
+                                    // The block is sent as is, w/o the runmd round trip of `with_block`
+                                    Encoding::Binary => {
+                                        message.start_block_mode(block_name, block_symbol);
+                                        copy_block(&mut message);
+                                        message.end_block_mode();
+                                        message
+                                    }
+                                };
                             }
 
-                            match dispatcher.try_send(message.as_ref().clone()) {
+                            match dispatcher.try_send(message) {
                                 Ok(_) => {
                                     event!(Level::DEBUG, "proxied {:?}", entity);
                                 },
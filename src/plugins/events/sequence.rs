@@ -177,6 +177,11 @@ impl Sequence {
         self.1 = Some(cursor);
     }
 
+    /// Removes the cursor, so that nothing is dispatched after the sequence completes
+    pub fn clear_cursor(&mut self) {
+        self.1 = None;
+    }
+
     /// Takes the next event and returns a sequence with only that event
     pub fn fork(&self) -> Option<Sequence> {
         let mut clone = self.clone();
@@ -9,11 +9,16 @@ use specs::EntityBuilder;
 
 mod secure;
 pub use secure::Secure;
+pub use secure::Vault;
+pub use secure::VaultErrors;
+pub use secure::TokenProvider;
 
 mod block;
 pub use block::Project;
 pub use block::BlockContext;
 pub use block::BlockAddress;
+pub use block::Schema;
+pub use block::SchemaErrors;
 
 mod network;
 pub use network::NetworkEvent;
@@ -24,15 +29,35 @@ pub use network::ProxiedMessage;
 mod events;
 pub use events::Event;
 pub use events::EventRuntime;
+pub use events::RuntimeSpan;
 pub use events::Listen;
 pub use events::Sequence;
 pub use events::Connection;
 pub use events::ProxyDispatcher;
 
+mod pipeline;
+pub use pipeline::Pipeline;
+
+mod control;
+pub use control::Control;
+pub use control::If;
+pub use control::Switch;
+pub use control::While;
+pub use control::ForEach;
+
+mod schedule;
+pub use schedule::Schedule;
+pub use schedule::Cron;
+pub use schedule::Trigger;
+pub use schedule::Overlap;
+pub use schedule::Missed;
+pub use schedule::Next;
+
 mod process;
 pub use process::Process;
 pub use process::Remote;
 pub use process::Expect;
+pub use process::Assert;
 pub use process::Missing;
 pub use process::Redirect;
 
@@ -47,8 +72,17 @@ pub use thunks::StatusUpdate;
 pub use thunks::Thunk;
 pub use thunks::ThunkContext;
 pub use thunks::Timer;
+pub use thunks::Watch;
+pub use thunks::FileWatchers;
+pub use thunks::FileWatcher;
+pub use thunks::ArchiveFiles;
+pub use thunks::ExtractFiles;
+pub use thunks::ArchiveFormat;
+pub use thunks::safe_path;
 pub use thunks::Println;
 pub use thunks::Dispatch;
+pub use thunks::ContextWriter;
+pub use thunks::LogBuffer;
 
 #[derive(Component, Default)]
 #[storage(DefaultVecStorage)]
@@ -166,6 +200,12 @@ pub trait Engine {
                 Self::create_event(entity, world);
 
                 let mut initial_context = ThunkContext::default();
+                if let Some(clock) = world.try_fetch::<Clock>() {
+                    initial_context.set_clock(clock.clone());
+                }
+                if let Some(random) = world.try_fetch::<Random>() {
+                    initial_context.set_random(random.clone());
+                }
                 config(&mut initial_context);
                 initial_context.as_mut().set_parent_entity(entity);
 
@@ -352,15 +392,12 @@ where
 
                         let previous = tc.project
                                 .as_ref()
-                                .and_then(|p| p.transpile_blocks().ok())
-                                .unwrap_or_default()
-                                .trim()
-                                .to_string();
+                                .and_then(|p| p.encode_blocks(Encoding::find(tc.as_ref())));
 
                         let mut next_tc = tc.clone();
-                        if !previous.trim().is_empty() {
+                        if let Some(previous) = previous {
                             let block_name = tc.block.block_name.to_string();
-                            next_tc.as_mut().add_message(
+                            next_tc.as_mut().add_binary_message(
                                 block_name,
                                 "previous",
                                 previous,
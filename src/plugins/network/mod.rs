@@ -7,6 +7,7 @@ use tokio::task::JoinHandle;
 use tracing::{event, Level};
 
 use super::{BlockAddress, CancelThunk, ErrorContext, Event, EventRuntime, ThunkContext};
+use crate::Metrics;
 
 mod proxy;
 pub use proxy::ProxiedMessage;
@@ -112,6 +113,7 @@ impl<'a> System<'a> for NetworkRuntime {
     type SystemData = (
         Entities<'a>,
         Read<'a, tokio::runtime::Runtime, EventRuntime>,
+        Read<'a, Metrics>,
         ReadStorage<'a, ThunkContext>,
         WriteStorage<'a, NetworkTask>,
         WriteStorage<'a, Event>,
@@ -119,7 +121,7 @@ impl<'a> System<'a> for NetworkRuntime {
 
     fn run(
         &mut self,
-        (entities, tokio_runtime, contexts, mut network_tasks, mut events): Self::SystemData,
+        (entities, tokio_runtime, metrics, contexts, mut network_tasks, mut events): Self::SystemData,
     ) {
         for (entity, task) in (&entities, &mut network_tasks).join() {
             if task.is_ready() {
@@ -131,6 +133,10 @@ impl<'a> System<'a> for NetworkRuntime {
                                 if let (Some(upstream_event), Some(upstream_context)) =
                                     (events.get_mut(upstream), contexts.get(upstream))
                                 {
+                                    let plugin = upstream_event.plugin_symbol();
+                                    metrics.increment("lifec_proxy_messages_total", plugin);
+                                    metrics.add("lifec_proxy_bytes_total", plugin, sent as u64);
+
                                     event!(
                                         Level::TRACE,
                                         "proxied message\n{sent} bytes\n{} -> {upstream_id}\n{}",
@@ -141,6 +147,7 @@ impl<'a> System<'a> for NetworkRuntime {
                                     // By going through the proxy instead of receiving the message directly,
                                     // the plugin can interpret the transient value to figure out what type of message
                                     // it need's to process next.
+                                    // The plugin reads the message w/ `ThunkContext::receive_proxied`.
                                     let mut upstream_context = upstream_context.clone();
                                     upstream_context.as_mut()
                                         .define("proxy", "received")
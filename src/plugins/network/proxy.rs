@@ -1,12 +1,13 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use serde::{Serialize, Deserialize};
-use specs::{Component, System, WriteStorage, Entities, Join, Read};
+use specs::{Component, System, WriteStorage, Entities, Join, Read, ReadStorage};
 use specs::storage::DenseVecStorage;
 use tokio::net::UdpSocket;
 use tracing::{event, Level};
 
-use crate::plugins::{ThunkContext, BlockAddress, EventRuntime};
+use crate::plugins::{ThunkContext, BlockAddress, Event, EventRuntime};
+use crate::{AttributeGraph, Encoding, Metrics};
 
 use super::NetworkEvent;
 
@@ -31,6 +32,46 @@ pub struct ProxiedMessage {
     data: Vec<u8>,
 }
 
+impl ProxiedMessage {
+    /// Returns the source of the message
+    pub fn src(&self) -> SocketAddr {
+        self.src
+    }
+
+    /// Returns the data that was sent
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the message encoded to send upstream, 
+    /// 
+    /// The binary encoding is a graph w/ `src` and `data` attributes, see `AttributeGraph::to_bytes`
+    /// 
+    pub fn encode(&self, encoding: Encoding) -> Vec<u8> {
+        match encoding {
+            Encoding::Text => serde_json::ser::to_vec(self).ok().unwrap_or_default(),
+            Encoding::Binary => AttributeGraph::default()
+                .with_text("src", self.src.to_string())
+                .with_binary("data", self.data.to_vec())
+                .to_bytes(),
+        }
+    }
+
+    /// Decodes a message sent upstream, in either encoding
+    /// 
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if AttributeGraph::is_binary(bytes) {
+            let graph = AttributeGraph::from_bytes(bytes).ok()?;
+            Some(Self {
+                src: graph.find_text("src")?.parse().ok()?,
+                data: graph.find_binary("data")?,
+            })
+        } else {
+            serde_json::from_slice(bytes).ok()
+        }
+    }
+}
+
 impl Proxy {
     /// Returns the underlying udp socket
     /// 
@@ -50,9 +91,8 @@ impl Proxy {
                 data
             };
     
-            let proxied_message = serde_json::ser::to_vec(
-                &proxied_message
-            ).ok().unwrap_or_default();
+            let encoding = self.0.as_ref().map(|tc| Encoding::find(tc.as_ref())).unwrap_or_default();
+            let proxied_message = proxied_message.encode(encoding);
 
             if let Some(NetworkEvent::Proxied(upstream_entity, sent)) = self.send_upstream(&proxied_message.to_vec()).await {
                 if sent != proxied_message.len() {
@@ -231,25 +271,21 @@ fn test_socket_proxies() {
                 let mut received = [0; 1024];
                 let received = &mut received;
 
-                // Checks that `b` can receive the message
-                let upstream_b = b.socket().unwrap();
-                let (read, addr) = upstream_b.recv_from(received).await.expect("read");
-                
-                 assert_eq!(addr, sock_b_addr);
-        
-                upstream_b.send_to(&received[..read], sock_a_addr).await.ok();
+                // Checks that `b` can receive and decode the message
+                let message = b.receive_proxied(received).await.expect("decoded");
+                assert_eq!(message.src(), sock_a_addr);
+                assert_eq!(message.data(), b"hello world");
+
+                b.socket().unwrap().send_to(message.data(), sock_a_addr).await.ok();
             });
         
             let proxy_a_recv = runtime.spawn(async move { 
                 let mut received = [0; 1024];
                 let received = &mut received;
 
-                // Checks that `a` can receive the message
-                let upstream_a = a.socket().unwrap();
-                let (_, addr) = upstream_a.recv_from(received).await.expect("read");
-
-                assert_eq!(addr, sock_a_addr, "received a message from the proxy");
-
+                // Checks that `a` can receive and decode the message
+                let message = a.receive_proxied(received).await.expect("decoded");
+                assert_eq!(message.data(), b"hello world", "received a message from the proxy");
             });
 
             // Write to b
@@ -291,12 +327,14 @@ impl<'a> System<'a> for ProxyRuntime {
     type SystemData = (
         Entities<'a>,
         Read<'a, tokio::runtime::Runtime, EventRuntime>,
+        Read<'a, Metrics>,
+        ReadStorage<'a, Event>,
         WriteStorage<'a, ThunkContext>,
         WriteStorage<'a, BlockAddress>,
         WriteStorage<'a, Proxy>,
     );
 
-    fn run(&mut self, (entities, tokio_runtime, mut contexts, mut block_addresses, mut proxies): Self::SystemData) {
+    fn run(&mut self, (entities, tokio_runtime, metrics, events, mut contexts, mut block_addresses, mut proxies): Self::SystemData) {
         for (entity, context) in (&entities, &mut contexts).join() {
             
             // Enables a proxy for the socket, if a socket doesn't already exist, one is created
@@ -324,7 +362,11 @@ impl<'a> System<'a> for ProxyRuntime {
                             if let Some((from, _)) = proxy_address.open().connect(&block_address.open()) {
                                 let proxy = Proxy::from((proxy_context, from.clone()));
                                 match proxies.insert(proxy_entity, proxy) {
-                                    Ok(_) => event!(Level::TRACE, "inserted proxy for {:?}", proxy_entity),
+                                    Ok(_) => {
+                                        let plugin = events.get(entity).map(|e| e.plugin_symbol()).unwrap_or_default();
+                                        metrics.increment("lifec_proxies_created_total", plugin);
+                                        event!(Level::TRACE, "inserted proxy for {:?}", proxy_entity)
+                                    },
                                     Err(err) => event!(Level::ERROR, "could not insert proxy component {err}"),
                                 }
 
@@ -401,25 +443,21 @@ fn test_proxy_runtime() {
                 let mut received = [0; 1024];
                 let received = &mut received;
     
-                // Checks that `b` can receive the message
-                let upstream_b = b.socket().unwrap();
-                let (read, addr) = upstream_b.recv_from(received).await.expect("read");
-                
-                 assert_eq!(addr, sock_b_addr);
-        
-                upstream_b.send_to(&received[..read], sock_a_addr).await.ok();
+                // Checks that `b` can receive and decode the message
+                let message = b.receive_proxied(received).await.expect("decoded");
+                assert_eq!(message.src(), sock_a_addr);
+                assert_eq!(message.data(), b"hello world");
+
+                b.socket().unwrap().send_to(message.data(), sock_a_addr).await.ok();
             });
         
             let proxy_a_recv = tokio_runtime.spawn(async move { 
                 let mut received = [0; 1024];
                 let received = &mut received;
     
-                // Checks that `a` can receive the message
-                let upstream_a = a.socket().unwrap();
-                let (_, addr) = upstream_a.recv_from(received).await.expect("read");
-    
-                assert_eq!(addr, sock_a_addr, "received a message from the proxy");
-    
+                // Checks that `a` can receive and decode the message
+                let message = a.receive_proxied(received).await.expect("decoded");
+                assert_eq!(message.data(), b"hello world", "received a message from the proxy");
             });
     
             // Write to b
@@ -446,4 +484,18 @@ fn test_proxy_runtime() {
             assert!(false, "failed");
         }
     });
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_proxied_message_encoding() {
+    let message = ProxiedMessage {
+        src: "127.0.0.1:5000".parse().expect("valid"),
+        data: b"hello".to_vec(),
+    };
+
+    for encoding in [Encoding::Text, Encoding::Binary] {
+        let decoded = ProxiedMessage::decode(&message.encode(encoding)).expect("decodes");
+        assert_eq!(decoded.src(), message.src());
+        assert_eq!(decoded.data(), message.data());
+    }
+}
@@ -0,0 +1,255 @@
+use tokio::select;
+use tracing::{event, Level};
+
+use crate::plugins::{AsyncContext, Plugin, Thunk, ThunkContext};
+use crate::Encoding;
+
+/// Composes plugins at runtime, declared in runmd w/ a `pipeline` text attribute, i.e.
+///
+/// ````runmd
+/// ``` copy pipeline
+/// add pipeline .text open_file | write_file | println
+/// ```
+/// ````
+///
+/// Each stage is resolved by symbol from the plugins installed w/ the `Runtime`, see `Runtime::pipeline`. Stages are called
+/// one by one like `combine`, where the blocks of each stage's project are handed off to the next stage as `previous`.
+///
+/// If a stage records an error, the remaining stages are skipped. Progress is reported for each stage as it starts.
+///
+#[derive(Clone, Default)]
+pub struct Pipeline(Vec<Thunk>);
+
+impl Pipeline {
+    /// Returns a pipeline from a list of stages
+    pub fn new(stages: Vec<Thunk>) -> Self {
+        Self(stages)
+    }
+
+    /// Returns the symbols of each stage
+    pub fn stages(&self) -> Vec<&'static str> {
+        self.0.iter().map(|Thunk(symbol, _)| *symbol).collect()
+    }
+
+    /// Parses the stages of a pipeline, i.e. `open_file | write_file | println`
+    pub fn parse(pipeline: &str) -> Vec<&str> {
+        pipeline
+            .split('|')
+            .map(str::trim)
+            .filter(|stage| !stage.is_empty())
+            .collect()
+    }
+
+    /// Calls each stage of the pipeline w/ context
+    pub fn call(&self, context: &mut ThunkContext) -> Option<AsyncContext> {
+        let stages = self.0.clone();
+        context.clone().task(|mut cancel_source| {
+            let mut tc = context.clone();
+            async move {
+                let total = stages.len();
+                for (index, Thunk(symbol, thunk)) in stages.into_iter().enumerate() {
+                    tc.update_progress(
+                        format!("stage {}/{total}, {symbol}", index + 1),
+                        index as f32 / total as f32,
+                    )
+                    .await;
+
+                    if let Some((handle, cancel)) = thunk(&mut tc) {
+                        select! {
+                            next = handle => {
+                                match next {
+                                    Ok(next) => {
+                                        tc = next;
+                                    },
+                                    Err(err) => {
+                                        event!(Level::ERROR, "error in pipeline stage {symbol}, {err}");
+                                        let block_name = tc.block.block_name.to_string();
+                                        tc.error(|g| {
+                                            g.with_text(&block_name, symbol);
+                                        });
+                                    },
+                                }
+                            }
+                            _ = &mut cancel_source => {
+                                cancel.send(()).ok();
+                                return None;
+                            }
+                        }
+                    }
+
+                    if tc.get_errors().is_some() {
+                        event!(Level::WARN, "pipeline stage {symbol} has errors, skipping remaining stages");
+                        return Some(tc);
+                    }
+
+                    let previous = tc
+                        .project
+                        .as_ref()
+                        .and_then(|p| p.encode_blocks(Encoding::find(tc.as_ref())));
+                    if let Some(previous) = previous {
+                        let block_name = tc.block.block_name.to_string();
+                        tc.as_mut().add_binary_message(block_name, "previous", previous);
+                    }
+                }
+
+                tc.update_progress("pipeline completed", 1.0).await;
+                Some(tc)
+            }
+        })
+    }
+}
+
+impl Plugin<ThunkContext> for Pipeline {
+    fn symbol() -> &'static str {
+        "pipeline"
+    }
+
+    fn description() -> &'static str {
+        "Calls each plugin in the `pipeline` attribute one by one, i.e. open_file | write_file"
+    }
+
+    fn caveats() -> &'static str {
+        "Stages are resolved by the runtime when the event is created, only plugins installed w/ the runtime can be used as a stage"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<AsyncContext> {
+        // Events for this plugin are created w/ the resolved pipeline, which replaces this thunk
+        event!(
+            Level::WARN,
+            "pipeline for {} was not resolved by the runtime",
+            context.block.block_name
+        );
+        None
+    }
+}
+
+#[test]
+fn test_pipeline() {
+    use crate::editor::Call;
+    use crate::plugins::{OpenFile, Println, WriteFile};
+    use crate::Runtime;
+
+    assert_eq!(
+        Pipeline::parse("open_file | write_file |println |"),
+        vec!["open_file", "write_file", "println"]
+    );
+
+    let mut runtime = Runtime::default();
+    runtime.install::<Call, OpenFile>();
+    runtime.install::<Call, WriteFile>();
+    runtime.install::<Call, Println>();
+    runtime.install::<Call, Pipeline>();
+
+    let pipeline = runtime
+        .pipeline("open_file | write_file | println")
+        .expect("resolves");
+    assert_eq!(pipeline.stages(), vec!["open_file", "write_file", "println"]);
+
+    assert!(runtime.pipeline("open_file | missing_plugin").is_none());
+    assert!(runtime.pipeline("open_file | pipeline").is_none());
+    assert!(runtime.pipeline("").is_none());
+}
+
+#[test]
+fn test_pipeline_call() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::{Duration, Instant};
+    use crate::plugins::Project;
+    use crate::testing::PluginHarness;
+
+    static HANG_CANCELLED: AtomicBool = AtomicBool::new(false);
+
+    fn emit(context: &mut ThunkContext) -> Option<AsyncContext> {
+        context.clone().task(|_| {
+            let mut tc = context.clone();
+            async move {
+                tc.project = Some(Project::default().with_block("greeting", "file", |c| {
+                    c.add_text_attr("message", "hello");
+                }));
+                Some(tc)
+            }
+        })
+    }
+
+    fn receive(context: &mut ThunkContext) -> Option<AsyncContext> {
+        context.clone().task(|_| {
+            let mut tc = context.clone();
+            async move {
+                tc.as_mut().apply("previous");
+                let messages = tc
+                    .as_ref()
+                    .find_blocks("file")
+                    .iter()
+                    .filter_map(|b| b.find_text("message"))
+                    .collect::<Vec<_>>();
+                tc.as_mut().add_text_attr("received", messages.join(","));
+                Some(tc)
+            }
+        })
+    }
+
+    fn fail(context: &mut ThunkContext) -> Option<AsyncContext> {
+        context.clone().task(|_| {
+            let mut tc = context.clone();
+            async move {
+                tc.error(|g| g.add_text_attr("failed", "stage failed"));
+                Some(tc)
+            }
+        })
+    }
+
+    fn hang(context: &mut ThunkContext) -> Option<AsyncContext> {
+        context.clone().task(|cancel_source| {
+            let tc = context.clone();
+            async move {
+                select! {
+                    _ = tokio::time::sleep(Duration::from_secs(60)) => Some(tc),
+                    _ = cancel_source => {
+                        HANG_CANCELLED.store(true, Ordering::SeqCst);
+                        None
+                    }
+                }
+            }
+        })
+    }
+
+    // The project of each stage is handed off to the next stage as `previous`, w/ progress for each stage
+    let pipeline = Pipeline::new(vec![Thunk("emit", emit), Thunk("receive", receive)]);
+    let run = PluginHarness::new("").run_with(|tc| pipeline.call(tc));
+    run.assert_completed()
+        .assert_no_errors()
+        .assert_text("received", "hello");
+
+    let progress = run
+        .status_updates
+        .iter()
+        .map(|(_, progress, status)| (*progress, status.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        progress,
+        vec![
+            (0.0, "stage 1/2, emit"),
+            (0.5, "stage 2/2, receive"),
+            (1.0, "pipeline completed")
+        ]
+    );
+
+    // Stages after a stage w/ an error are skipped
+    let pipeline = Pipeline::new(vec![Thunk("emit", emit), Thunk("fail", fail), Thunk("receive", receive)]);
+    let run = PluginHarness::new("").run_with(|tc| pipeline.call(tc));
+    run.assert_completed().assert_error("failed");
+    assert!(run.context().as_ref().find_text("received").is_none());
+    assert!(!run.status_updates.iter().any(|(_, _, status)| status.contains("receive")));
+
+    // Cancelling the pipeline cancels the current stage
+    let pipeline = Pipeline::new(vec![Thunk("emit", emit), Thunk("hang", hang)]);
+    let harness = PluginHarness::new("").with_timeout(Duration::from_millis(100));
+    let run = harness.run_with(|tc| pipeline.call(tc));
+    assert!(run.timed_out);
+
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !HANG_CANCELLED.load(Ordering::SeqCst) && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(HANG_CANCELLED.load(Ordering::SeqCst));
+}
@@ -0,0 +1,200 @@
+use atlier::system::Value;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use tracing::{event, Level};
+
+use crate::plugins::{Plugin, ThunkContext};
+use crate::AttributeGraph;
+
+/// Evaluates declarative checks against the current context, and the `previous` context if it was passed,
+///
+/// Each check is a define, where the name is the attribute being checked, and the symbol is the check,
+///
+/// ````runmd
+/// ``` verify assert
+/// add stop_on_error .enable
+/// define process.code one_of      .text 0, 1
+/// define process.stdout contains  .text hello
+/// define command      matches     .text ^echo
+/// define status       equals      .text ok
+/// define out.txt      sha256      .text 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824
+/// ```
+/// ````
+///
+/// An attribute name of the form `symbol.name` is read from the last block w/ that symbol, i.e. `process.code` is
+/// the exit code of the last process.
+///
+#[derive(Default)]
+pub struct Assert;
+
+impl Assert {
+    /// Symbols of each check
+    pub const CHECKS: [&'static str; 6] = ["equals", "contains", "matches", "one_of", "exists", "sha256"];
+
+    /// Returns the value of an attribute from the context, or the last block w/ the symbol for `symbol.name`
+    pub fn find_value(tc: &ThunkContext, name: impl AsRef<str>) -> Option<Value> {
+        let name = name.as_ref();
+        if let Some(value) = tc.as_ref().find_attr_value(name) {
+            return Some(value.clone());
+        }
+
+        let (symbol, attr) = name.split_once('.')?;
+        let find_in = |graph: &AttributeGraph| {
+            graph
+                .find_blocks(symbol)
+                .iter()
+                .rev()
+                .find_map(|block| block.find_attr_value(attr).cloned())
+        };
+
+        find_in(tc.as_ref()).or_else(|| tc.project.as_ref().and_then(|p| find_in(p.as_ref())))
+    }
+
+    /// Evaluates a check, returns an error message if the check failed
+    pub fn check(tc: &ThunkContext, name: impl AsRef<str>, check: impl AsRef<str>, expected: &Value) -> Result<(), String> {
+        let name = name.as_ref();
+        let expected_text = Self::text(expected).unwrap_or_default();
+
+        match check.as_ref() {
+            "exists" => return Self::check_file(name, None),
+            "sha256" => return Self::check_file(name, Some(&expected_text)),
+            _ => {}
+        }
+
+        let actual = Self::find_value(tc, name).ok_or(format!("expected {name}, but it was not found"))?;
+        let actual_text = Self::text(&actual).unwrap_or_default();
+
+        match check.as_ref() {
+            "equals" if &actual == expected || actual_text == expected_text => Ok(()),
+            "equals" => Err(format!("expected {name} to equal `{expected_text}`, found `{actual_text}`")),
+            "contains" if actual_text.contains(&expected_text) => Ok(()),
+            "contains" => Err(format!("expected {name} to contain `{expected_text}`, found `{actual_text}`")),
+            "matches" => match Regex::new(&expected_text) {
+                Ok(regex) if regex.is_match(&actual_text) => Ok(()),
+                Ok(_) => Err(format!("expected {name} to match `{expected_text}`, found `{actual_text}`")),
+                Err(err) => Err(format!("invalid regex `{expected_text}` for {name}, {err}")),
+            },
+            "one_of" if expected_text.split(',').any(|e| e.trim() == actual_text.trim()) => Ok(()),
+            "one_of" => Err(format!("expected {name} to be one of `{expected_text}`, found `{actual_text}`")),
+            check => Err(format!("unknown check `{check}` for {name}")),
+        }
+    }
+
+    /// Checks that a file exists, and if hash is set, that the sha256 of the file's content is hash
+    fn check_file(path: &str, hash: Option<&str>) -> Result<(), String> {
+        let content = std::fs::read(path).map_err(|err| format!("expected file {path} to exist, {err}"))?;
+
+        match hash {
+            Some(hash) => {
+                let actual = Sha256::digest(&content)
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<String>();
+
+                if actual.eq_ignore_ascii_case(hash.trim()) {
+                    Ok(())
+                } else {
+                    Err(format!("expected sha256 of {path} to be `{hash}`, found `{actual}`"))
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the text of a value, binary values are read as utf8
+    fn text(value: &Value) -> Option<String> {
+        match value {
+            Value::TextBuffer(text) | Value::Symbol(text) => Some(text.to_string()),
+            Value::Int(i) => Some(i.to_string()),
+            Value::Float(f) => Some(f.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            Value::BinaryVector(bytes) => Some(String::from_utf8_lossy(bytes).to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl Plugin<ThunkContext> for Assert {
+    fn symbol() -> &'static str {
+        "assert"
+    }
+
+    fn description() -> &'static str {
+        "Evaluates declarative checks against the current or previous context."
+    }
+
+    fn caveats() -> &'static str {
+        "Failed checks are added to the error block, so `stop_on_error` and `fix` engines handle them"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<crate::plugins::AsyncContext> {
+        context.clone().task(|_| {
+            let mut tc = context.clone();
+            async move {
+                tc.as_mut().apply("previous");
+
+                let mut passed = 0;
+                let mut failed = 0;
+                for check in Self::CHECKS {
+                    for (name, expected) in tc.as_ref().find_symbol_values(check) {
+                        let name = name.trim_end_matches(&format!("::{check}")).to_string();
+
+                        match Self::check(&tc, &name, check, &expected) {
+                            Ok(_) => {
+                                passed += 1;
+                                tc.update_status_only(format!("ok {name} {check}")).await;
+                            }
+                            Err(message) => {
+                                failed += 1;
+                                event!(Level::ERROR, "`assert` plugin check failed, {message}");
+                                tc.update_status_only(format!("failed {name} {check}, {message}")).await;
+                                tc.error(|g| {
+                                    g.add_text_attr(format!("{name}_{check}"), &message);
+                                });
+                            }
+                        }
+                    }
+                }
+
+                tc.as_mut().with_int("passed", passed).with_int("failed", failed);
+                Some(tc)
+            }
+        })
+    }
+}
+
+#[test]
+fn test_assert() {
+    use crate::testing::{PluginHarness, TempDir};
+
+    let dir = TempDir::new("assert");
+    let path = dir.join("assert.txt");
+    std::fs::write(&path, "hello").expect("can write file");
+    let path = path.to_str().expect("path");
+
+    let run = PluginHarness::new(format!(
+        r#"
+    add status .text ok
+    add command .text echo hello
+    define status equals .text ok
+    define command matches .text ^echo
+    define command contains .text hello
+    define {path} sha256 .text 2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824
+    "#
+    ))
+    .run::<Assert>();
+    run.assert_completed().assert_no_errors().assert_int("passed", 4);
+
+    let run = PluginHarness::new(
+        r#"
+    add code .int 2
+    define code one_of .text 0, 1
+    define missing equals .text value
+    "#,
+    )
+    .run::<Assert>();
+    run.assert_completed()
+        .assert_error("code_one_of")
+        .assert_error("missing_equals")
+        .assert_int("failed", 2);
+}
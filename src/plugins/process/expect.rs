@@ -1,15 +1,48 @@
 use std::env::consts::OS;
+use std::net::TcpListener;
+use std::path::Path;
 
 use atlier::system::Value;
+use regex::Regex;
+use semver::{Version, VersionReq};
 use tracing::{event, Level};
 use which::which;
 
 use crate::plugins::{Plugin, ThunkContext};
 
+/// Checks expectations for the current environment, results are recorded to the project's `env` block,
+///
+/// ````runmd
+/// ``` env expect
+/// define cargo  which     .text cargo
+/// define cargo  version   .text >=1.60
+/// define HOME   env_var   .enable
+/// define LANG   env_var   .text ^en_
+/// define target exists    .text dir
+/// define target disk      .int 1024
+/// define http   port      .int 8080
+/// define linux  os        .text linux
+/// define linux  kernel    .text >=5.4
+/// define http   fix       .text stop_server
+/// ```
+/// ````
+///
+/// A `version` check runs `{program} --version`, where program is the `which` command declared for the same name, or a `program`
+/// define, i.e. `define compiler program .text rustc`, otherwise the name itself.
+///
+/// Each failed check is added to the error block as `{fix_name} missing`, so that a `fix` block w/ that name can resolve it.
+/// The fix name is the name of the check, or the command for `which`, unless a `fix` define for the name sets it.
+///
 #[derive(Default)]
 pub struct Expect;
 
 impl Expect {
+    /// Symbols of each check
+    pub const CHECKS: [&'static str; 8] = ["which", "version", "env_var", "exists", "disk", "port", "os", "kernel"];
+
+    /// Problem failed checks are reported as, resolved by the `missing` fix plugin
+    pub const PROBLEM: &'static str = "missing";
+
     pub fn should_expect(name: impl AsRef<str>, symbol: impl AsRef<str>) -> bool {
         let symbol = symbol.as_ref();
         if let Some((_, os)) = name.as_ref().trim_end_matches(&format!("::{symbol}")).split_once("::") {
@@ -18,6 +51,160 @@ impl Expect {
 
         true
     }
+
+    /// Returns the name of the fix block for a failed check, a `fix` define for the name overrides the default
+    pub fn fix_name(tc: &ThunkContext, name: impl AsRef<str>, default: impl AsRef<str>) -> String {
+        let key = format!("{}::fix", name.as_ref());
+        tc.as_ref()
+            .find_symbol_values("fix")
+            .into_iter()
+            .find_map(|(n, v)| match v {
+                Value::TextBuffer(fix) if n == key => Some(fix),
+                _ => None,
+            })
+            .unwrap_or(default.as_ref().to_string())
+    }
+
+    /// Returns the program a `version` check for name runs, see `Expect`
+    pub fn version_program(tc: &ThunkContext, name: impl AsRef<str>) -> String {
+        let name = name.as_ref();
+        let find = |symbol: &str| {
+            tc.as_ref()
+                .find_symbol_values(symbol)
+                .into_iter()
+                .find_map(|(n, v)| match v {
+                    Value::TextBuffer(program)
+                        if n.starts_with(&format!("{name}::"))
+                            && n.ends_with(&format!("::{symbol}"))
+                            && Self::should_expect(&n, symbol) =>
+                    {
+                        Some(program)
+                    }
+                    _ => None,
+                })
+        };
+
+        find("which")
+            .or_else(|| find("program"))
+            .unwrap_or(name.to_string())
+    }
+
+    /// Parses the first version in text, i.e. `cargo 1.65.0 (4bc8f24d3 2022-10-20)` -> 1.65.0, a missing patch is 0
+    pub fn parse_version(text: impl AsRef<str>) -> Option<Version> {
+        let regex = Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?").ok()?;
+        let captures = regex.captures(text.as_ref())?;
+
+        Some(Version::new(
+            captures.get(1)?.as_str().parse().ok()?,
+            captures.get(2)?.as_str().parse().ok()?,
+            captures.get(3).and_then(|p| p.as_str().parse().ok()).unwrap_or(0),
+        ))
+    }
+
+    /// Runs `{program} --version`, and compares the version to a semver requirement
+    async fn check_version(program: &str, requirement: &str) -> Result<Value, String> {
+        let requirement = VersionReq::parse(requirement).map_err(|err| format!("invalid version requirement `{requirement}`, {err}"))?;
+        let output = tokio::process::Command::new(program)
+            .arg("--version")
+            .output()
+            .await
+            .map_err(|err| format!("could not run `{program} --version`, {err}"))?;
+
+        // Some programs print the version to stderr
+        let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+        match Self::parse_version(&text) {
+            Some(version) if requirement.matches(&version) => Ok(Value::TextBuffer(version.to_string())),
+            Some(version) => Err(format!("{program} {version} does not match `{requirement}`")),
+            None => Err(format!("could not parse a version from `{}`", text.trim())),
+        }
+    }
+
+    /// Checks that an env var is set, if expected is text, the value must match it as a regex
+    fn check_env_var(name: &str, expected: &Value) -> Result<Value, String> {
+        let value = std::env::var(name).map_err(|err| format!("env var {name} is not set, {err}"))?;
+
+        match expected {
+            Value::TextBuffer(pattern) => match Regex::new(pattern) {
+                Ok(regex) if regex.is_match(&value) => Ok(Value::Bool(true)),
+                Ok(_) => Err(format!("env var {name} does not match `{pattern}`")),
+                Err(err) => Err(format!("invalid regex `{pattern}` for env var {name}, {err}")),
+            },
+            _ => Ok(Value::Bool(true)),
+        }
+    }
+
+    /// Checks that a path exists, if expected is `file` or `dir` the path must be that kind
+    fn check_exists(path: &str, expected: &Value) -> Result<Value, String> {
+        let path = Path::new(path);
+        let kind = if path.is_dir() {
+            "dir"
+        } else if path.is_file() {
+            "file"
+        } else {
+            return Err(format!("{:?} does not exist", path));
+        };
+
+        match expected {
+            Value::TextBuffer(expected) if expected != kind => Err(format!("expected {:?} to be a {expected}, found a {kind}", path)),
+            _ => Ok(Value::TextBuffer(kind.to_string())),
+        }
+    }
+
+    /// Checks that the disk w/ path has at least min_mb megabytes available
+    fn check_disk(path: &str, min_mb: i32) -> Result<Value, String> {
+        let available = fs2::available_space(path).map_err(|err| format!("could not read disk space for {path}, {err}"))?;
+        let available_mb = (available / (1024 * 1024)) as i32;
+
+        if available_mb >= min_mb {
+            Ok(Value::Int(available_mb))
+        } else {
+            Err(format!("{available_mb} MB available for {path}, expected at least {min_mb} MB"))
+        }
+    }
+
+    /// Checks that a port is free to bind, the port must be within 1-65535
+    fn check_port(port: i32) -> Result<Value, String> {
+        let bind_port = u16::try_from(port)
+            .ok()
+            .filter(|p| *p > 0)
+            .ok_or(format!("port {port} is not within 1-65535"))?;
+
+        TcpListener::bind(("0.0.0.0", bind_port))
+            .map(|_| Value::Int(port))
+            .map_err(|err| format!("port {port} is not free, {err}"))
+    }
+
+    /// Checks that the current OS is expected
+    fn check_os(expected: &str) -> Result<Value, String> {
+        if expected == OS {
+            Ok(Value::TextBuffer(OS.to_string()))
+        } else {
+            Err(format!("expected OS {expected}, found {OS}"))
+        }
+    }
+
+    /// Checks that the kernel release matches a semver requirement
+    fn check_kernel(requirement: &str) -> Result<Value, String> {
+        let requirement = VersionReq::parse(requirement).map_err(|err| format!("invalid version requirement `{requirement}`, {err}"))?;
+        let release = std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .ok()
+            .or_else(|| {
+                std::process::Command::new("uname")
+                    .arg("-r")
+                    .output()
+                    .ok()
+                    .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+            })
+            .map(|r| r.trim().to_string())
+            .ok_or("could not read the kernel release".to_string())?;
+
+        match Self::parse_version(&release) {
+            Some(version) if requirement.matches(&version) => Ok(Value::TextBuffer(release)),
+            _ => Err(format!("kernel release {release} does not match `{requirement}`")),
+        }
+    }
 }
 
 impl Plugin<ThunkContext> for Expect {
@@ -29,37 +216,70 @@ impl Plugin<ThunkContext> for Expect {
         "Check expectations for the current environment."
     }
 
+    fn caveats() -> &'static str {
+        "Failed checks are reported as `{fix_name} missing` errors, which `fix` engines w/ the `missing` plugin can resolve"
+    }
+
     fn call_with_context(context: &mut ThunkContext) -> Option<crate::plugins::AsyncContext> {
         context.clone().task(|_| {
             let mut tc = context.clone();
             async move {
                 let mut project = tc.clone().project.unwrap_or_default();
 
-                // Uses `which` crate to check path for binaries
-                for (name, check) in tc.as_ref().find_symbol_values("which") {
-                    if !Self::should_expect(&name, "which") {
-                        eprintln!("skipping {name}");
-                        continue;
-                    }
+                for check in Self::CHECKS {
+                    for (name, expected) in tc.as_ref().find_symbol_values(check) {
+                        if !Self::should_expect(&name, check) {
+                            eprintln!("skipping {name}");
+                            continue;
+                        }
 
-                    if let Value::TextBuffer(command) = check {
-                        tc.update_status_only(format!("checking {command}")).await;
-                        match which(&command) {
-                            Ok(path) => {
-                                let path = format!("{:?}", path).trim_matches('"').to_string();
-                                tc.update_status_only(format!("found {command} at {path}")).await;
+                        let name = name.trim_end_matches(&format!("::{check}")).to_string();
+                        let key = name.split("::").next().unwrap_or_default().to_string();
+                        tc.update_status_only(format!("checking {key} {check}")).await;
 
-                                // Output results to path symbol for current block
-                                project = project.with_block("env", "path", |g| {
-                                    g.add_text_attr(&command, path);
+                        let (record_name, fix_name, result) = match (check, &expected) {
+                            // Uses `which` crate to check path for binaries
+                            ("which", Value::TextBuffer(command)) => (
+                                command.to_string(),
+                                Self::fix_name(&tc, &key, command),
+                                which(command)
+                                    .map(|path| Value::TextBuffer(format!("{:?}", path).trim_matches('"').to_string()))
+                                    .map_err(|err| format!("`{command}`: {err}")),
+                            ),
+                            ("version", Value::TextBuffer(requirement)) => {
+                                let program = Self::version_program(&tc, &key);
+                                (key.to_string(), Self::fix_name(&tc, &key, &key), Self::check_version(&program, requirement).await)
+                            }
+                            ("env_var", expected) => (key.to_string(), Self::fix_name(&tc, &key, &key), Self::check_env_var(&key, expected)),
+                            ("exists", expected) => (key.to_string(), Self::fix_name(&tc, &key, &key), Self::check_exists(&key, expected)),
+                            ("disk", Value::Int(min_mb)) => (key.to_string(), Self::fix_name(&tc, &key, &key), Self::check_disk(&key, *min_mb)),
+                            ("port", Value::Int(port)) => (key.to_string(), Self::fix_name(&tc, &key, &key), Self::check_port(*port)),
+                            ("os", Value::TextBuffer(os)) => (key.to_string(), Self::fix_name(&tc, &key, &key), Self::check_os(os)),
+                            ("kernel", Value::TextBuffer(requirement)) => {
+                                (key.to_string(), Self::fix_name(&tc, &key, &key), Self::check_kernel(requirement))
+                            }
+                            _ => {
+                                event!(Level::WARN, "`expect` plugin skipping {key} {check}, unexpected value {:?}", expected);
+                                continue;
+                            }
+                        };
+
+                        match result {
+                            Ok(value) => {
+                                tc.update_status_only(format!("ok {key} {check}")).await;
+
+                                // Output results to the env block, w/ a symbol for each check
+                                let symbol = if check == "which" { "path" } else { check };
+                                project = project.with_block("env", symbol, |g| {
+                                    g.with(&record_name, value);
                                 });
                             }
                             Err(err) => {
-                                let log = format!("`expect` plugin error on symbol `which` for `{command}`: {err}");
+                                let log = format!("`expect` plugin error on symbol `{check}` for `{key}`: {err}");
                                 event!(Level::ERROR, "{log}");
                                 tc.update_status_only(format!("{log}")).await;
                                 tc.error(|g| {
-                                    g.add_text_attr(&command, "missing");
+                                    g.add_text_attr(&fix_name, Self::PROBLEM);
                                 });
                             }
                         }
@@ -73,3 +293,50 @@ impl Plugin<ThunkContext> for Expect {
         })
     }
 }
+
+#[test]
+fn test_expect() {
+    use crate::testing::PluginHarness;
+
+    let busy = TcpListener::bind("0.0.0.0:0").expect("can bind");
+    let busy_port = busy.local_addr().expect("bound").port();
+
+    let run = PluginHarness::new(format!(
+        r#"
+    define rustc  which     .text rustc
+    define rustc  version   .text >=1.0
+    define compiler which   .text rustc
+    define compiler version .text >=1.0
+    define toolchain program .text rustc
+    define toolchain version .text >=1.0
+    define PATH   env_var   .enable
+    define src    exists    .text dir
+    define src    disk      .int 1
+    define {OS}   os        .text {OS}
+    define busy   port      .int {busy_port}
+    define missing_program which .text program_that_does_not_exist
+    define missing_program fix   .text install_program
+    "#
+    ))
+    .run::<Expect>();
+
+    run.assert_completed()
+        .assert_error("busy")
+        .assert_error("install_program")
+        .assert_block_text("env", "exists", "src", "dir")
+        .assert_block_text("env", "os", OS, OS)
+        .assert_block_value("env", "env_var", "PATH", Value::Bool(true));
+
+    assert_eq!(run.errors.iter().flat_map(|e| e.errors()).count(), 2);
+    assert!(run.block("env", "path").and_then(|b| b.find_text("rustc")).is_some());
+    assert!(run.block("env", "version").and_then(|b| b.find_text("rustc")).is_some());
+    assert!(run.block("env", "version").and_then(|b| b.find_text("compiler")).is_some());
+    assert!(run.block("env", "version").and_then(|b| b.find_text("toolchain")).is_some());
+
+    assert_eq!(Expect::check_port(0), Err("port 0 is not within 1-65535".to_string()));
+    assert_eq!(Expect::check_port(65536 + 80), Err("port 65616 is not within 1-65535".to_string()));
+    assert!(Expect::check_port(-1).is_err());
+
+    assert_eq!(Expect::parse_version("cargo 1.65.0 (4bc8f24d3 2022-10-20)"), Some(Version::new(1, 65, 0)));
+    assert_eq!(Expect::parse_version("5.4"), Some(Version::new(5, 4, 0)));
+}
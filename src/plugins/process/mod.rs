@@ -1,8 +1,8 @@
-use std::{env::consts::OS, process::Output};
+use std::{env::consts::OS, process::{ExitStatus, Output}};
 
-use super::{thunks::CancelToken, Plugin, ThunkContext};
+use super::{thunks::CancelToken, Plugin, Secure, ThunkContext};
 use atlier::system::Value;
-use chrono::{Local, Utc, DateTime};
+use chrono::{Utc, DateTime};
 use specs::{Component, HashMapStorage};
 use tokio::{select, task::JoinHandle, process::Command};
 
@@ -12,6 +12,9 @@ pub use remote::Remote;
 mod expect;
 pub use expect::Expect;
 
+mod assert;
+pub use assert::Assert;
+
 mod missing;
 pub use missing::Missing;
 
@@ -35,7 +38,7 @@ impl Process {
         }
     }
 
-    async fn resolve_args(tc: &ThunkContext, command: &mut Command) {
+    async fn resolve_args(tc: &mut ThunkContext, command: &mut Command) {
         for (arg, value) in tc.clone().as_ref().find_symbol_values("arg") {
             let arg = arg.trim_end_matches("::arg");
             match value {
@@ -51,6 +54,7 @@ impl Process {
                 },
                 Value::Symbol(reference) => {
                     if let Some(value) = tc.as_ref().find_text(&reference) {
+                        tc.as_mut().derive_sensitive(&reference, arg);
                         tc.update_status_only(format!("define {arg} arg .symbol {reference}")).await;
                         if arg.len() == 1 {
                             let arg = format!("-{arg}");
@@ -66,11 +70,46 @@ impl Process {
         }
     }
 
-    async fn resolve_env(tc: &ThunkContext, command: &mut Command) {
-        for (env, value) in tc.as_ref().find_symbol_values("env") {
+    /// Returns the output of a stubbed process, read from `stub_stdout` and `stub_stderr`
+    fn stub_output(tc: &ThunkContext) -> Output {
+        Output {
+            status: ExitStatus::default(),
+            stdout: tc.as_ref().find_text("stub_stdout").unwrap_or_default().into_bytes(),
+            stderr: tc.as_ref().find_text("stub_stderr").unwrap_or_default().into_bytes(),
+        }
+    }
+
+    async fn resolve_env(tc: &mut ThunkContext, command: &mut Command) {
+        for (env, value) in tc.clone().as_ref().find_symbol_values("env") {
             let env = env.trim_end_matches("::env");
             match value {
+                Value::Symbol(reference) if reference.starts_with("secret::") => {
+                    let entry = reference.trim_start_matches("secret::");
+                    tc.update_status_only(format!("define {env} env .secret {entry}")).await;
+
+                    // If the secure plugin wasn't called before this plugin, resolve the secret from the vault now
+                    if tc.as_ref().find_secret(env).is_none() {
+                        match Secure::open_vault(tc.as_ref()) {
+                            Ok(vault) => {
+                                Secure::resolve(&vault, tc.as_mut());
+                            }
+                            Err(err) => {
+                                tc.update_status_only(format!("# could not open vault, {:?}", err)).await;
+                            }
+                        }
+                    }
+
+                    if let Some(value) = tc.as_ref().find_secret(env) {
+                        command.env(env, value);
+                    } else {
+                        tc.update_status_only(format!("# could not resolve secret {entry} for env {env}")).await;
+                        tc.error(|g| {
+                            g.add_text_attr(env, format!("could not resolve secret {entry}"));
+                        });
+                    }
+                },
                 Value::Symbol(reference) => {
+                    tc.as_mut().derive_sensitive(&reference, env);
                     tc.update_status_only(format!("define {env} env .symbol {reference}")).await;
                     if let Some(value) = tc.as_ref().find_text(reference) {
                         command.env(env, value);
@@ -90,19 +129,24 @@ impl Process {
             .unwrap_or_default();
 
         let command = command.as_ref().to_string();
+        let redactor = tc.as_ref().redactor();
+        let stdout = redactor.redact_bytes(output.stdout);
+        let stderr = redactor.redact_bytes(output.stderr);
 
-        let timestamp_utc = Some(Utc::now().to_string());
-        let timestamp_local = Some(Local::now().to_string());
+        let clock = tc.clock();
+        let now = clock.now();
+        let timestamp_utc = Some(now.to_string());
+        let timestamp_local = Some(clock.to_local(now).to_string());
         let elapsed = start_time
-            .and_then(|s| Some(Utc::now() - s))
+            .and_then(|s| Some(now - s))
             .and_then(|d| Some(format!("{} ms", d.num_milliseconds())));
 
         if let Some(project) = tc.project.as_mut() {
             *project = project.with_block(program, "process", |c| {
                 c.with_int("code", output.status.code().unwrap_or_default())
                     .with_text("command", &command)
-                    .with_binary("stdout", output.stdout)
-                    .with_binary("stderr", output.stderr)
+                    .with_binary("stdout", stdout)
+                    .with_binary("stderr", stderr)
                     .with_text("timestamp_local", timestamp_local.unwrap_or_default())
                     .with_text("timestamp_utc", timestamp_utc.unwrap_or_default())
                 .add_text_attr("elapsed", elapsed.unwrap_or_default());
@@ -121,6 +165,10 @@ impl Plugin<ThunkContext> for Process {
         "Executes a new command w/ an OS process."
     }
 
+    fn caveats() -> &'static str {
+        "If `stub` is enabled, the command isn't started, and the output is read from `stub_stdout` and `stub_stderr`"
+    }
+
     fn call_with_context(
         context: &mut super::ThunkContext,
     ) -> Option<(JoinHandle<ThunkContext>, CancelToken)> {
@@ -130,53 +178,74 @@ impl Plugin<ThunkContext> for Process {
                 let command = Self::resolve_command(&tc).unwrap_or("echo missing command".to_string());
                 // Creating a new tokio command
                 let parts: Vec<&str> = command.split(" ").collect();
-                tc.update_progress(format!("``` {} process", tc.block.block_name), 0.10)
-                    .await;
+                let mut log = tc.writer();
+                log.set_progress(0.10);
+                log.writeln(format!("``` {} process", tc.block.block_name)).await.ok();
                 if let Some(program) = parts.get(0) {
-                    tc.update_progress(format!("add command .text {}", program), 0.10)
-                        .await;
+                    log.writeln(format!("add command .text {}", program)).await.ok();
                     let mut command_task = tokio::process::Command::new(&program);
                     for (el, arg) in parts.iter().skip(1).enumerate() {
                         command_task.arg(arg);
-                        tc.update_progress(format!("define arg{}    .text {}", el, arg), 0.10)
-                            .await;
+                        log.writeln(format!("define arg{}    .text {}", el, arg)).await.ok();
                     }
 
-                    Self::resolve_args(&tc, &mut command_task).await;
-                    Self::resolve_env(&tc, &mut command_task).await;
+                    Self::resolve_args(&mut tc, &mut command_task).await;
+                    Self::resolve_env(&mut tc, &mut command_task).await;
 
+                    // Values resolved above can be sensitive, so the writer needs the current context to redact them
+                    let mut log = tc.writer();
+                    log.set_progress(0.20);
                     if let Some(current_dir) = tc.as_ref().find_text("current_dir") {
-                        tc.update_progress(format!("add current_dir .text {current_dir}"), 0.20).await;
+                        log.writeln(format!("add current_dir .text {current_dir}")).await.ok();
                         command_task.current_dir(current_dir);
                     }
 
-                    tc.update_progress("```", 0.20).await;
-                    tc.update_progress("# Running", 0.20).await;
-                    let start_time = Some(Utc::now());
+                    log.writeln("```").await.ok();
+                    let stub = if tc.as_ref().is_enabled("stub").unwrap_or_default() {
+                        log.writeln("# Stubbed").await.ok();
+                        Some(Self::stub_output(&tc))
+                    } else {
+                        log.writeln("# Running").await.ok();
+                        None
+                    };
+                    let start_time = Some(tc.clock().now());
 
                     command_task.kill_on_drop(true);
+                    let output = async move {
+                        match stub {
+                            Some(stub) => Ok(stub),
+                            None => command_task.output().await,
+                        }
+                    };
 
                     select! {
-                       output = command_task.output() => {
+                       output = output => {
                             match output {
                                 Ok(output) => {
-                                    for b in output.stdout.clone() {
+                                    for b in log.redactor().redact_bytes(output.stdout.clone()) {
                                         tc.send_char(b).await;
                                     }
-                                    for b in output.stderr.clone() {
+                                    for b in log.redactor().redact_bytes(output.stderr.clone()) {
                                         tc.send_char(b).await;
                                     }
+
+                                    log.write_lines(&output.stdout).await.ok();
+                                    log.write_lines(&output.stderr).await.ok();
+
                                     // Completed process, publish result
-                                    tc.update_progress("# Finished, recording output", 0.30).await;
+                                    log.set_progress(0.30);
+                                    log.writeln("# Finished, recording output").await.ok();
                                     Self::resolve_output(&mut tc, command, start_time, output);
                                 }
                                 Err(err) => {
-                                    tc.update_progress(format!("# error {}", err), 0.0).await;
+                                    log.set_progress(0.0);
+                                    log.writeln(format!("[error] # error {}", err)).await.ok();
                                 }
                             }
                        }
                        _ = cancel_source => {
-                            tc.update_progress(format!("# cancelling"), 0.0).await;
+                            log.set_progress(0.0);
+                            log.writeln("[warn] # cancelling").await.ok();
                        }
                     }
                 }
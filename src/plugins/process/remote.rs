@@ -1,6 +1,5 @@
 use crate::plugins::thunks::CancelToken;
 use crate::plugins::{Plugin, ThunkContext};
-use chrono::Utc;
 use specs::storage::DenseVecStorage;
 use specs::Component;
 use tokio::sync::oneshot;
@@ -25,6 +24,10 @@ impl Plugin<ThunkContext> for Remote {
         "Starts a process and pipes stdin and stdout to the current console. Useful for ssh, etc."
     }
 
+    fn caveats() -> &'static str {
+        "If `stub` is enabled, the command isn't started, and the output is read from `stub_stdout` and `stub_stderr`"
+    }
+
     fn call_with_context(
         context: &mut ThunkContext,
     ) -> Option<(tokio::task::JoinHandle<ThunkContext>, CancelToken)> {
@@ -35,17 +38,17 @@ impl Plugin<ThunkContext> for Remote {
             async move {
                 let cmd = Process::resolve_command(&tc).unwrap_or("echo missing command".to_string());
                 let parts: Vec<&str> = cmd.split(" ").collect();
-                tc.update_progress(format!("``` {} process", tc.block.block_name), 0.10)
-                            .await;
+                let mut writer = tc.writer();
+                writer.set_progress(0.10);
+                writer.writeln(format!("``` {} process", tc.block.block_name)).await.ok();
                 if let Some(command) = parts.get(0) {
-                    tc.update_progress(format!("add command .text {}", command), 0.10).await;
+                    writer.writeln(format!("add command .text {}", command)).await.ok();
                     let mut command_task = Command::new(&command);
                     for (el, arg) in parts.iter().skip(1).enumerate() {
                             command_task.arg(arg);
-                            tc.update_progress(format!("add arg{}    .text {}", el, arg), 0.10)
-                                .await;
+                            writer.writeln(format!("add arg{}    .text {}", el, arg)).await.ok();
                     }
-                    tc.update_progress("```", 0.10).await;
+                    writer.writeln("```").await.ok();
                     
                     let enable_stdin_shell = tc.as_ref().is_enabled("enable_listener").unwrap_or_default();
                     if enable_stdin_shell {
@@ -58,12 +61,28 @@ impl Plugin<ThunkContext> for Remote {
                     command_task.stderr(Stdio::piped());
 
                     if let Some(current_dir) = tc.as_ref().find_text("current_dir") {
-                        tc.update_progress(format!("add current_dir .text {current_dir}"), 0.20).await;
+                        writer.set_progress(0.20);
+                        writer.writeln(format!("add current_dir .text {current_dir}")).await.ok();
                         command_task.current_dir(current_dir);
                     }
 
-                    Process::resolve_args(&tc, &mut command_task).await;
-                    Process::resolve_env(&tc, &mut command_task).await;
+                    Process::resolve_args(&mut tc, &mut command_task).await;
+                    Process::resolve_env(&mut tc, &mut command_task).await;
+
+                    if tc.as_ref().is_enabled("stub").unwrap_or_default() {
+                        let mut writer = tc.writer();
+                        writer.writeln("# Stubbed").await.ok();
+                        let start_time = Some(tc.clock().now());
+                        let output = Process::stub_output(&tc);
+                        writer.write_lines(&output.stdout).await.ok();
+                        writer.write_lines(&output.stderr).await.ok();
+                        Process::resolve_output(&mut tc, cmd, start_time, output);
+                        return Some(tc);
+                    }
+
+                    // Values resolved above can be sensitive, so the writers need the current context to redact them
+                    let mut stdout_log = tc.writer();
+                    let mut stderr_log = tc.writer();
 
                     if let Some(mut child) = command_task.spawn().ok() {
                         if let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) {
@@ -122,44 +141,47 @@ impl Plugin<ThunkContext> for Remote {
 
                             if let Some(handle) = child_handle {
                                 let _child_task = handle.clone().spawn(async move {
-                                tc.update_progress("# child process started, stdout/stdin are being piped to console", 0.50).await;
-                                let start_time = Some(Utc::now());
+                                let mut writer = tc.writer();
+                                writer.set_progress(0.50);
+                                writer.writeln("# child process started, stdout/stdin are being piped to console").await.ok();
+                                let start_time = Some(tc.clock().now());
 
                                 select! {
                                     output = child.wait_with_output() => {
                                          match output {
                                              Ok(output) => {
                                                 // Completed process, publish result
-                                                tc.update_progress("# Finished, recording output", 0.30).await;
+                                                writer.set_progress(0.30);
+                                                writer.writeln("# Finished, recording output").await.ok();
                                                 Process::resolve_output(&mut tc, cmd, start_time, output);
                                              }
                                              Err(err) => {
-                                                 tc.update_progress(format!("# error {}", err), 0.0).await;
+                                                 writer.set_progress(0.0);
+                                                 writer.writeln(format!("[error] # error {}", err)).await.ok();
                                              }
                                          }
                                     }
                                     _ = child_cancel_rx => {
-                                         tc.update_progress(format!("# child cancel received"), 0.0).await;
+                                         writer.set_progress(0.0);
+                                         writer.writeln("[warn] # child cancel received").await.ok();
                                     }
                                  }
 
                                 tc
                             });
 
-                            let log = log.clone();
-                            let log_stderr = log.clone();
-
                             // Reads child's stdout, so that stdin can continue to work
                             let reader_task = log.handle().unwrap().spawn(async move {
                                 event!(Level::DEBUG, "starting to listen to stdout");
                                 while let Ok(line) = reader.next_line().await {
                                     match line {
                                         Some(line) => {
+                                            let line = stdout_log.redactor().redact(line);
                                             for byte in line.as_bytes() {
-                                                log.send_char(*byte).await;
+                                                stdout_log.context().send_char(*byte).await;
                                             }
-                                            log.send_char(b'\r').await;
-                                            log.update_status_only(line).await;
+                                            stdout_log.context().send_char(b'\r').await;
+                                            stdout_log.writeln(line).await.ok();
                                         },
                                         None => {
                                             break;
@@ -167,18 +189,19 @@ impl Plugin<ThunkContext> for Remote {
                                     }
                                 }
                             });
-                            let stderr_reader_task = log_stderr.handle().unwrap().spawn(async move {
+                            let stderr_reader_task = log.handle().unwrap().spawn(async move {
                                 event!(Level::DEBUG, "starting to listen to stderr");
                                 while let Ok(line) = stderr_reader.next_line().await {
                                     match line {
                                         Some(line) => {      
+                                            let line = stderr_log.redactor().redact(line);
                                             for byte in line.as_bytes() {
-                                                log_stderr.send_char(*byte).await;
+                                                stderr_log.context().send_char(*byte).await;
                                             }
-                                            log_stderr.send_char(b'\r').await;
+                                            stderr_log.context().send_char(b'\r').await;
                                         
                                             eprintln!("{}", line);
-                                            log_stderr.update_status_only(line).await;
+                                            stderr_log.writeln(line).await.ok();
                                         },
                                         None => {
                                             event!(Level::WARN, "Didn't read anything from stderr");
@@ -222,7 +245,7 @@ impl Plugin<ThunkContext> for Remote {
                     }
                 }
 
-                log.update_status_only("Could not spawn child process").await;
+                log.writer().writeln("[error] Could not spawn child process").await.ok();
                 None
             } else {
                 None
@@ -0,0 +1,534 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
+use tokio::select;
+use tracing::{event, Level};
+
+use crate::plugins::{AsyncContext, Control, Plugin, ThunkContext};
+use crate::AttributeGraph;
+
+/// Plugin that calls a sequence on a cron expression, or a fixed interval, i.e.
+///
+/// ````runmd
+/// ``` nightly call
+/// define a_schedule schedule .symbol every_five
+/// ```
+///
+/// ``` every_five schedule
+/// add cron .text */5 * * * *
+/// add body .text build
+/// add overlap .text skip
+/// add missed .text once
+/// ```
+///
+/// ``` build call
+/// define a_build process .symbol build
+/// ```
+/// ````
+///
+/// Cron expressions have 5 fields, `minute hour day-of-month month day-of-week`, evaluated in UTC. Each field is either `*`, a value,
+/// a range `a-b`, a step `*/n` or `a-b/n`, or a comma separated list of these. Instead of `cron`, `interval` (seconds) or `interval_ms`
+/// fires on a fixed interval.
+///
+/// The schedule is a control flow event, see `Control`, when it fires it calls the `body` sequence, and is called again after the
+/// sequence completes to wait for the next fire time. The next fire time is reported as the status of the event.
+///
+/// `overlap` decides what happens when a fire time passes while `body` is still running, see `Overlap`, and `missed` decides what happens
+/// to fire times that passed while the process was paused, see `Missed`.
+///
+/// Time is read from the context's clock, see `ThunkContext::clock`, so a virtual clock can be used to test a schedule w/o sleeping.
+///
+#[derive(Default)]
+pub struct Schedule;
+
+/// When a schedule fires
+#[derive(Debug, Clone, PartialEq)]
+pub enum Trigger {
+    /// Fires when the time matches a cron expression
+    Cron(Cron),
+    /// Fires every interval, aligned to the unix epoch
+    Interval(Duration),
+}
+
+/// What happens when a fire time passes while the sequence from the last fire time is still running
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overlap {
+    /// The fire time is skipped, default
+    Skip,
+    /// The fire time is queued, and the sequence is called again as soon as it completes
+    Queue,
+    /// The running sequence is cancelled at the fire time, and the sequence is called again
+    Cancel,
+}
+
+/// What happens to fire times that passed while the process was paused
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Missed {
+    /// Missed fire times are skipped, default
+    Skip,
+    /// The sequence is called once for all missed fire times
+    Once,
+    /// The sequence is called for each missed fire time, up to `max_queue`
+    All,
+}
+
+/// Result of polling a schedule, see `Schedule::poll`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Next {
+    /// The schedule fires now
+    Fire,
+    /// The schedule should wait until this time, and then poll again
+    WaitUntil(DateTime<Utc>),
+}
+
+/// Cron expression w/ 5 fields, each field is stored as a bit set of the values it matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// True if day-of-month is not `*`, when both day fields are restricted a day matches if either field matches
+    days_restricted: bool,
+    /// True if day-of-week is not `*`
+    weekdays_restricted: bool,
+}
+
+impl FromStr for Cron {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 5 {
+            return Err(format!("expected 5 fields, found {}", fields.len()));
+        }
+
+        let mut weekdays = Self::field(fields[4], 0, 7)?;
+        // Both 0 and 7 are sunday
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+
+        Ok(Self {
+            minutes: Self::field(fields[0], 0, 59)?,
+            hours: Self::field(fields[1], 0, 23)?,
+            days: Self::field(fields[2], 1, 31)?,
+            months: Self::field(fields[3], 1, 12)?,
+            weekdays,
+            days_restricted: fields[2] != "*",
+            weekdays_restricted: fields[4] != "*",
+        })
+    }
+}
+
+impl Cron {
+    /// Returns true if the minute of time matches this expression
+    pub fn matches(&self, time: DateTime<Utc>) -> bool {
+        Self::has(self.months, time.month())
+            && self.matches_day(time)
+            && Self::has(self.hours, time.hour())
+            && Self::has(self.minutes, time.minute())
+    }
+
+    /// Returns the first time after `after` that matches this expression, returns None if there isn't one in the next 5 years
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let limit = after + Duration::days(366 * 5);
+        let mut next = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+
+        while next <= limit {
+            if !Self::has(self.months, next.month()) {
+                // Skip to the first day of the next month
+                let (year, month) = if next.month() == 12 {
+                    (next.year() + 1, 1)
+                } else {
+                    (next.year(), next.month() + 1)
+                };
+                next = next.with_day(1)?.with_hour(0)?.with_minute(0)?.with_year(year)?.with_month(month)?;
+            } else if !self.matches_day(next) {
+                next = next.with_hour(0)?.with_minute(0)? + Duration::days(1);
+            } else if !Self::has(self.hours, next.hour()) {
+                next = next.with_minute(0)? + Duration::hours(1);
+            } else if !Self::has(self.minutes, next.minute()) {
+                next = next + Duration::minutes(1);
+            } else {
+                return Some(next);
+            }
+        }
+
+        None
+    }
+
+    fn matches_day(&self, time: DateTime<Utc>) -> bool {
+        let day = Self::has(self.days, time.day());
+        let weekday = Self::has(self.weekdays, time.weekday().num_days_from_sunday());
+
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    fn has(set: u64, value: u32) -> bool {
+        set & (1 << value) != 0
+    }
+
+    /// Parses a field into a bit set
+    fn field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+        let mut set = 0;
+
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (
+                    range,
+                    step.parse::<u32>()
+                        .ok()
+                        .filter(|s| *s > 0)
+                        .ok_or(format!("invalid step `{part}`"))?,
+                ),
+                None => (part, 1),
+            };
+
+            let (start, end) = match range {
+                "*" => (min, max),
+                _ => match range.split_once('-') {
+                    Some((start, end)) => (Self::value(start, part)?, Self::value(end, part)?),
+                    None => {
+                        let value = Self::value(range, part)?;
+                        // `a/n` is the same as `a-max/n`
+                        (value, if part.contains('/') { max } else { value })
+                    }
+                },
+            };
+
+            if start < min || end > max || start > end {
+                return Err(format!("`{part}` is not within {min}-{max}"));
+            }
+
+            for value in (start..=end).step_by(step as usize) {
+                set |= 1 << value;
+            }
+        }
+
+        Ok(set)
+    }
+
+    fn value(value: &str, part: &str) -> Result<u32, String> {
+        value.parse::<u32>().map_err(|_| format!("invalid value `{part}`"))
+    }
+}
+
+impl Trigger {
+    /// Returns the first fire time after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Trigger::Cron(cron) => cron.next_after(after),
+            Trigger::Interval(interval) => {
+                let interval = interval.num_milliseconds().max(1);
+                let elapsed = after.timestamp_millis();
+                let next = (elapsed.div_euclid(interval) + 1) * interval;
+                Some(after + Duration::milliseconds(next - elapsed))
+            }
+        }
+    }
+
+    /// Returns the number of fire times from `from` to `to`, inclusive, up to max
+    pub fn count(&self, from: DateTime<Utc>, to: DateTime<Utc>, max: usize) -> usize {
+        if from > to {
+            return 0;
+        }
+
+        // Intervals fire on multiples of the interval, so these can be counted w/o stepping through each fire time
+        if let Trigger::Interval(interval) = self {
+            let interval = interval.num_milliseconds().max(1);
+            let count = 1 + to.timestamp_millis().div_euclid(interval) - from.timestamp_millis().div_euclid(interval);
+            return (count as usize).min(max);
+        }
+
+        let mut count = 0;
+        let mut next = Some(from);
+        while let Some(fire) = next.filter(|n| *n <= to) {
+            count += 1;
+            if count >= max {
+                break;
+            }
+            next = self.next_after(fire);
+        }
+        count
+    }
+}
+
+impl Schedule {
+    /// Default limit for queued fire times
+    pub const DEFAULT_MAX_QUEUE: i32 = 10;
+
+    /// Default time after a fire time before the fire time is considered missed
+    pub const DEFAULT_MISFIRE_MS: i32 = 1000;
+
+    /// Returns the trigger configured by `cron`, `interval`, or `interval_ms`
+    pub fn trigger(graph: &AttributeGraph) -> Result<Trigger, String> {
+        if let Some(cron) = graph.find_text("cron") {
+            return cron.parse().map(Trigger::Cron).map_err(|err| format!("invalid cron `{cron}`, {err}"));
+        }
+
+        let interval = graph.find_int("interval").unwrap_or_default() as i64 * 1000
+            + graph.find_int("interval_ms").unwrap_or_default() as i64;
+        if interval > 0 {
+            Ok(Trigger::Interval(Duration::milliseconds(interval)))
+        } else {
+            Err("schedule requires `cron`, `interval` or `interval_ms`".to_string())
+        }
+    }
+
+    /// Returns the overlap policy, from `overlap`
+    pub fn overlap(graph: &AttributeGraph) -> Overlap {
+        match graph.find_text("overlap").as_deref() {
+            Some("queue") => Overlap::Queue,
+            Some("cancel") | Some("cancel_previous") => Overlap::Cancel,
+            _ => Overlap::Skip,
+        }
+    }
+
+    /// Returns the missed run policy, from `missed`
+    pub fn missed(graph: &AttributeGraph) -> Missed {
+        match graph.find_text("missed").as_deref() {
+            Some("once") => Missed::Once,
+            Some("all") => Missed::All,
+            _ => Missed::Skip,
+        }
+    }
+
+    /// Returns the next fire time recorded in graph
+    pub fn next_fire(graph: &AttributeGraph) -> Option<DateTime<Utc>> {
+        Self::find_time(graph, "next_fire")
+    }
+
+    /// Returns the last fire time recorded in graph
+    pub fn last_fire(graph: &AttributeGraph) -> Option<DateTime<Utc>> {
+        Self::find_time(graph, "last_fire")
+    }
+
+    /// Polls the schedule at now, the state of the schedule is stored in graph,
+    ///
+    /// `running` is enabled when the schedule fires, so that the next poll knows that any fire times that have passed, passed while the
+    /// sequence was running.
+    ///
+    pub fn poll(graph: &mut AttributeGraph, trigger: &Trigger, now: DateTime<Utc>) -> Next {
+        let max_queue = graph.find_int("max_queue").unwrap_or(Self::DEFAULT_MAX_QUEUE).max(0) as usize;
+        let misfire = Duration::milliseconds(graph.find_int("misfire_ms").unwrap_or(Self::DEFAULT_MISFIRE_MS).max(0) as i64);
+
+        let queued = graph.find_int("queued").unwrap_or_default();
+        if queued > 0 {
+            graph.with_int("queued", queued - 1);
+            return Self::fire(graph, trigger, now);
+        }
+
+        let next = match Self::next_fire(graph).or_else(|| trigger.next_after(now)) {
+            Some(next) => next,
+            None => {
+                event!(Level::WARN, "schedule doesn't fire in the next 5 years");
+                return Next::WaitUntil(now + Duration::days(1));
+            }
+        };
+        Self::set_time(graph, "next_fire", next);
+
+        let running = graph.is_enabled("running").unwrap_or_default();
+        graph.with_bool("running", false);
+        if next > now {
+            return Next::WaitUntil(next);
+        }
+
+        // Every fire time that passed is counted, max_queue only limits how many are queued
+        let due = trigger.count(next, now, usize::MAX);
+        if running {
+            match Self::overlap(graph) {
+                Overlap::Skip => Self::skip(graph, trigger, now, due),
+                Overlap::Queue => {
+                    graph.with_int("queued", due.saturating_sub(1).min(max_queue) as i32);
+                    Self::fire(graph, trigger, now)
+                }
+                // The running sequence was cancelled at the fire time
+                Overlap::Cancel => Self::fire(graph, trigger, now),
+            }
+        } else if now - next > misfire {
+            match Self::missed(graph) {
+                Missed::Skip => Self::skip(graph, trigger, now, due),
+                Missed::Once => Self::fire(graph, trigger, now),
+                Missed::All => {
+                    graph.with_int("queued", due.saturating_sub(1).min(max_queue) as i32);
+                    Self::fire(graph, trigger, now)
+                }
+            }
+        } else {
+            Self::fire(graph, trigger, now)
+        }
+    }
+
+    fn fire(graph: &mut AttributeGraph, trigger: &Trigger, now: DateTime<Utc>) -> Next {
+        let fired = graph.find_int("fired").unwrap_or_default();
+        graph.with_int("fired", fired + 1).with_bool("running", true);
+        Self::set_time(graph, "last_fire", now);
+        if let Some(next) = trigger.next_after(now) {
+            Self::set_time(graph, "next_fire", next);
+        }
+        Next::Fire
+    }
+
+    fn skip(graph: &mut AttributeGraph, trigger: &Trigger, now: DateTime<Utc>, skipped: usize) -> Next {
+        event!(Level::DEBUG, "skipping {skipped} fire times");
+        let total = graph.find_int("skipped").unwrap_or_default();
+        graph.with_int("skipped", total.saturating_add(skipped.min(i32::MAX as usize) as i32));
+
+        match trigger.next_after(now) {
+            Some(next) => {
+                Self::set_time(graph, "next_fire", next);
+                Next::WaitUntil(next)
+            }
+            None => Next::WaitUntil(now + Duration::days(1)),
+        }
+    }
+
+    fn find_time(graph: &AttributeGraph, name: &str) -> Option<DateTime<Utc>> {
+        graph
+            .find_text(name)
+            .and_then(|time| DateTime::parse_from_rfc3339(&time).ok())
+            .map(|time| time.with_timezone(&Utc))
+    }
+
+    fn set_time(graph: &mut AttributeGraph, name: &str, time: DateTime<Utc>) {
+        graph.with_text(name, time.to_rfc3339());
+    }
+}
+
+impl Plugin<ThunkContext> for Schedule {
+    fn symbol() -> &'static str {
+        "schedule"
+    }
+
+    fn description() -> &'static str {
+        "Calls the `body` sequence on a `cron` expression, or every `interval` seconds"
+    }
+
+    fn caveats() -> &'static str {
+        "Cron expressions are evaluated in UTC"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<AsyncContext> {
+        context.clone().task(|mut cancel_source| {
+            let mut tc = context.clone();
+            async move {
+                tc.as_mut().apply("previous");
+                let block_name = tc.block.block_name.to_string();
+
+                let trigger = match Schedule::trigger(tc.as_ref()) {
+                    Ok(trigger) => trigger,
+                    Err(err) => {
+                        event!(Level::ERROR, "{block_name}, {err}");
+                        tc.error(|g| {
+                            g.with_text(&block_name, Schedule::symbol());
+                        });
+                        Control::jump(tc.as_mut(), None, false);
+                        return Some(tc);
+                    }
+                };
+
+                let clock = tc.clock();
+                loop {
+                    let now = clock.now();
+                    match Schedule::poll(tc.as_mut(), &trigger, now) {
+                        Next::Fire => break,
+                        Next::WaitUntil(next) => {
+                            let progress = Schedule::last_fire(tc.as_ref())
+                                .filter(|last| *last < next)
+                                .map(|last| {
+                                    (now - last).num_milliseconds() as f32
+                                        / (next - last).num_milliseconds().max(1) as f32
+                                })
+                                .unwrap_or_default();
+                            tc.update_progress(format!("next fire at {}", next.to_rfc3339()), progress)
+                                .await;
+
+                            // Wake up at least every second to report progress
+                            let wake = next.min(now + Duration::seconds(1));
+                            select! {
+                                _ = clock.sleep_until(wake) => {}
+                                _ = &mut cancel_source => {
+                                    return None;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                let body = tc.as_ref().find_text("body");
+                Control::jump(tc.as_mut(), body.as_deref(), true);
+
+                match (Schedule::overlap(tc.as_ref()), Schedule::next_fire(tc.as_ref())) {
+                    (Overlap::Cancel, Some(next)) => Control::set_deadline(tc.as_mut(), Some(next)),
+                    _ => Control::set_deadline(tc.as_mut(), None),
+                }
+
+                tc.update_progress(format!("fired at {}", clock.now().to_rfc3339()), 1.0)
+                    .await;
+                Some(tc)
+            }
+        })
+    }
+}
+
+#[test]
+fn test_schedule() {
+    use crate::Clock;
+
+    let at = |secs| Clock::from_timestamp(secs).expect("in range");
+
+    let cron = "*/15 9-17 * * 1-5".parse::<Cron>().expect("valid");
+    // Friday, 1970-01-02 09:07
+    let time = at(86400 + 9 * 3600 + 7 * 60);
+    assert_eq!(cron.next_after(time), Some(at(86400 + 9 * 3600 + 15 * 60)));
+    // Friday 17:45 -> Monday 09:00
+    let time = at(86400 + 17 * 3600 + 45 * 60);
+    assert_eq!(cron.next_after(time), Some(at(4 * 86400 + 9 * 3600)));
+    assert!("* * *".parse::<Cron>().is_err());
+    assert!("61 * * * *".parse::<Cron>().is_err());
+
+    let mut graph = AttributeGraph::from(0);
+    graph.with_text("cron", "*/5 * * * *").with_text("overlap", "queue");
+    let trigger = Schedule::trigger(&graph).expect("valid");
+
+    // Fire times are counted the same for cron and intervals
+    let (from, to) = (at(1200), at(4501));
+    assert_eq!(trigger.count(from, to, usize::MAX), 12);
+    assert_eq!(Trigger::Interval(Duration::minutes(5)).count(from, to, usize::MAX), 12);
+    assert_eq!(Trigger::Interval(Duration::minutes(5)).count(from, to, 10), 10);
+
+    let clock = Clock::virtual_at(at(0));
+    assert_eq!(
+        Schedule::poll(&mut graph, &trigger, clock.now()),
+        Next::WaitUntil(at(300))
+    );
+
+    clock.advance(std::time::Duration::from_secs(300));
+    assert_eq!(Schedule::poll(&mut graph, &trigger, clock.now()), Next::Fire);
+
+    // The body ran past the next 2 fire times, which are queued
+    clock.advance(std::time::Duration::from_secs(601));
+    assert_eq!(Schedule::poll(&mut graph, &trigger, clock.now()), Next::Fire);
+    assert_eq!(graph.find_int("queued"), Some(1));
+    assert_eq!(Schedule::poll(&mut graph, &trigger, clock.now()), Next::Fire);
+    assert_eq!(
+        Schedule::poll(&mut graph, &trigger, clock.now()),
+        Next::WaitUntil(at(1200))
+    );
+
+    // Missed fire times while paused are skipped by default
+    graph.with_bool("running", false);
+    clock.advance(std::time::Duration::from_secs(3600));
+    assert_eq!(
+        Schedule::poll(&mut graph, &trigger, clock.now()),
+        Next::WaitUntil(at(4800))
+    );
+    // 1200 through 4500
+    assert_eq!(graph.find_int("skipped"), Some(12));
+    assert_eq!(graph.find_int("fired"), Some(3));
+}
@@ -1,23 +1,208 @@
+use atlier::system::Value;
+use tracing::{event, Level};
 
-/// Component for dealing with secure data, 
-/// 
+use crate::plugins::{Plugin, ThunkContext};
+use crate::AttributeGraph;
+
+mod vault;
+pub use vault::Vault;
+pub use vault::VaultErrors;
+
+/// Component for dealing with secure data,
+///
 /// This component is mainly best effort, and is not trying to reinvent any security protocols.
-/// Underneath the hood it will use the securestore-rs library to enable this feature.
-/// 
+/// Underneath the hood it uses a local vault in the style of securestore, see `Vault`.
+///
 /// The focus is for dealing with runtime secrets, so any type of critical secrets need to be stored at rest
-/// on actual vault services (such as .key files) such as Azure Key Vault. 
-/// 
+/// on actual vault services (such as .key files) such as Azure Key Vault.
+///
 /// This component will focus on transient secrets, like access tokens, shared access tokens, etc.
-/// 
-/// # About securestore-rs 
-/// 
-/// securestore-rs' main data artifact is the secrets.json file which is an opaque archive of the secret store, designed to be checked-in
-/// and saved in plain text, and transferred between machines. 
-/// 
-/// The secret artifact is a .key file that must be protected and stored in a vault service. 
 ///
+/// # About the vault
+///
+/// The vault's main data artifact is the secrets.json file which is an opaque archive of the secret store, designed to be checked-in
+/// and saved in plain text, and transferred between machines.
+///
+/// The secret artifact is a .key file that must be protected and stored in a vault service.
+///
+/// # Usage
+///
+/// Secrets are referenced in runmd w/ the `.secret` value type, which only stores the name of the vault entry,
+///
+/// ````runmd
+/// ``` deploy secure
+/// add vault_src .text secrets.json
+/// add key_src   .text secrets.key
+/// add token     .secret github_token
+/// ```
+/// ````
+///
+/// When called, this plugin decrypts each referenced entry into a transient attribute `{name}::secret`. These transients
+/// are never written by `transpile()` or `RuntimeState::save`.
+///
+#[derive(Default)]
 pub struct Secure;
 
+impl Secure {
+    /// Prefix of symbol values that reference a vault entry
+    pub const PREFIX: &'static str = "secret::";
+
+    /// If the value is a reference to a vault entry, returns the name of the entry
+    pub fn entry_name(value: &Value) -> Option<&str> {
+        match value {
+            Value::Symbol(symbol) => symbol
+                .strip_prefix(Self::PREFIX)
+                .filter(|entry| !entry.is_empty()),
+            _ => None,
+        }
+    }
+
+    /// Returns a vec of (attribute name, entry name) for each secret referenced by the graph
+    pub fn find_references(graph: &AttributeGraph) -> Vec<(String, String)> {
+        let mut references = vec![];
+        for attr in graph
+            .iter_attributes()
+            .filter(|a| a.id() == graph.entity())
+        {
+            if let Some(entry) = Self::entry_name(attr.value()) {
+                references.push((attr.name().to_string(), entry.to_string()));
+            }
+
+            if let Some((name, value)) = attr.transient() {
+                if let Some(entry) = Self::entry_name(value) {
+                    references.push((name.to_string(), entry.to_string()));
+                }
+            }
+        }
+        references
+    }
+
+    /// Opens the vault configured by `vault_src` and `key_src`, defaults to secrets.json and secrets.key
+    pub fn open_vault(graph: &AttributeGraph) -> Result<Vault, VaultErrors> {
+        let vault_src = graph
+            .find_text("vault_src")
+            .unwrap_or("secrets.json".to_string());
+        let key_src = graph
+            .find_text("key_src")
+            .unwrap_or("secrets.key".to_string());
+
+        Vault::open(vault_src, key_src)
+    }
+
+    /// Decrypts each referenced secret into a `{name}::secret` transient, returns the names of
+    /// entries that could not be resolved
+    pub fn resolve(vault: &Vault, graph: &mut AttributeGraph) -> Vec<String> {
+        let mut missing = vec![];
+        for (name, entry) in Self::find_references(graph) {
+            match vault.get_text(&entry) {
+                Ok(secret) => {
+                    graph
+                        .define(&name, "secret")
+                        .edit_as(Value::TextBuffer(secret));
+                }
+                Err(err) => {
+                    event!(Level::ERROR, "could not resolve secret {entry}, {:?}", err);
+                    missing.push(entry);
+                }
+            }
+        }
+        missing
+    }
+}
+
+/// Trait for types that can provide a token by name
 pub trait TokenProvider {
-    fn get_token(&self) -> Secure; 
+    fn get_token(&self, name: impl AsRef<str>) -> Option<String>;
+}
+
+impl TokenProvider for Vault {
+    fn get_token(&self, name: impl AsRef<str>) -> Option<String> {
+        self.get_text(name).ok()
+    }
+}
+
+impl Plugin<ThunkContext> for Secure {
+    fn symbol() -> &'static str {
+        "secure"
+    }
+
+    fn description() -> &'static str {
+        "Decrypts secrets referenced w/ `.secret` from a local vault into transient attributes."
+    }
+
+    fn caveats() -> &'static str {
+        "Decrypted secrets are only available at runtime, the vault key must be provided separately from the vault."
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<crate::plugins::AsyncContext> {
+        context.clone().task(|_| {
+            let mut tc = context.clone();
+            async move {
+                match Self::open_vault(tc.as_ref()) {
+                    Ok(vault) => {
+                        tc.update_status_only("opened vault").await;
+                        for entry in Self::resolve(&vault, tc.as_mut()) {
+                            tc.update_status_only(format!("missing secret {entry}")).await;
+                            tc.error(|g| {
+                                g.add_text_attr(&entry, "missing");
+                            });
+                        }
+                    }
+                    Err(err) => {
+                        let log = format!("`secure` plugin could not open vault, {:?}", err);
+                        event!(Level::ERROR, "{log}");
+                        tc.update_status_only(log).await;
+                        let vault_src = tc
+                            .as_ref()
+                            .find_text("vault_src")
+                            .unwrap_or("secrets.json".to_string());
+                        tc.error(|g| {
+                            g.add_text_attr("vault_src", &vault_src);
+                        });
+                    }
+                }
+
+                Some(tc)
+            }
+        })
+    }
+}
+
+#[test]
+fn test_secure() {
+    use crate::plugins::BlockContext;
+    use crate::RuntimeDispatcher;
+
+    let dir = crate::testing::TempDir::new("secure");
+    let mut vault = Vault::create(dir.join("secrets.json"), dir.join("secrets.key")).expect("created");
+    vault.set("github_token", "hunter2").expect("set");
+
+    let mut graph = AttributeGraph::from(0);
+    assert!(graph
+        .batch_mut(
+            r#"
+``` deploy secure
+add token .secret github_token
+define GITHUB_TOKEN env .secret github_token
+```
+"#,
+        )
+        .is_ok());
+
+    let mut deploy = BlockContext::root_context(&graph, "deploy");
+    assert!(deploy.update_block("secure", |g| {
+        assert!(Secure::resolve(&vault, g).is_empty());
+    }));
+
+    let secure = deploy.get_block("secure").expect("exists");
+    assert_eq!(secure.find_secret("token"), Some("hunter2".to_string()));
+    assert_eq!(secure.find_secret("GITHUB_TOKEN"), Some("hunter2".to_string()));
+
+    let transpiled = deploy.transpile().expect("transpiles");
+    assert!(transpiled.contains("add token .secret github_token"));
+    assert!(transpiled.contains("define GITHUB_TOKEN env .secret github_token"));
+    assert!(!transpiled.contains("hunter2"));
+
+    use crate::RuntimeState;
+    assert!(!secure.save().unwrap_or_default().contains("hunter2"));
 }
@@ -0,0 +1,389 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, NewAead};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+/// Current version of the secrets.json format
+const VAULT_VERSION: u32 = 1;
+
+/// Known plaintext encrypted w/ the vault key, used to check that a .key file belongs to a vault
+const SENTINEL: &[u8] = b"lifec.secure.sentinel";
+
+/// Errors returned by vault operations
+#[derive(Debug)]
+pub enum VaultErrors {
+    /// Reading or writing the vault or key file failed
+    Io(std::io::Error),
+    /// The vault file could not be parsed
+    Format(String),
+    /// The key file is not a valid 256-bit key
+    InvalidKey,
+    /// The key does not match the key the vault was created with
+    KeyMismatch,
+    /// A secret could not be encrypted or decrypted
+    Crypto,
+    /// The vault does not contain a secret w/ the name
+    NotFound(String),
+    /// Creating a vault would overwrite an existing vault or key file
+    AlreadyExists(PathBuf),
+}
+
+impl From<std::io::Error> for VaultErrors {
+    fn from(err: std::io::Error) -> Self {
+        VaultErrors::Io(err)
+    }
+}
+
+/// An encrypted value, each value is encrypted w/ it's own nonce
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sealed {
+    /// base64 encoded 96-bit nonce
+    iv: String,
+    /// base64 encoded ciphertext
+    payload: String,
+}
+
+/// On-disk format of the secrets.json file
+///
+/// This file is opaque w/o the key, so it is safe to check-in and transfer between machines.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    sentinel: Sealed,
+    secrets: BTreeMap<String, Sealed>,
+}
+
+/// Local encrypted secret store, in the style of securestore,
+///
+/// The vault is made up of two artifacts,
+///     1) secrets.json - the encrypted store, which can be checked-in
+///     2) secrets.key - the 256-bit key, which must be protected and stored in a vault service
+///
+/// Each secret is sealed w/ AES-256-GCM and a random nonce.
+///
+pub struct Vault {
+    /// Path to the secrets.json file
+    vault_path: PathBuf,
+    /// Vault key
+    key: [u8; 32],
+    /// Decoded vault file
+    file: VaultFile,
+}
+
+impl Vault {
+    /// Creates a new vault w/ a freshly generated key, writes the key to key_path and the empty
+    /// vault to vault_path
+    ///
+    /// Returns `AlreadyExists` if either file exists, so that an existing vault is never overwritten
+    ///
+    pub fn create(
+        vault_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> Result<Self, VaultErrors> {
+        let key = Self::generate_key();
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            sentinel: Self::seal_with(&key, SENTINEL)?,
+            secrets: BTreeMap::default(),
+        };
+        let content = Self::encode(&file)?;
+
+        let mut vault_file = Self::create_new(&vault_path, false)?;
+        let mut key_file = match Self::create_new(&key_path, true) {
+            Ok(key_file) => key_file,
+            Err(err) => {
+                // The vault file was just created above, so it's removed so that create can be retried
+                fs::remove_file(&vault_path).ok();
+                return Err(err);
+            }
+        };
+        vault_file.write_all(content.as_bytes())?;
+        key_file.write_all(&key)?;
+
+        Ok(Self {
+            vault_path: vault_path.as_ref().to_path_buf(),
+            key,
+            file,
+        })
+    }
+
+    /// Opens an existing vault w/ the key stored at key_path
+    ///
+    pub fn open(
+        vault_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> Result<Self, VaultErrors> {
+        let key = Self::read_key(key_path)?;
+        let content = fs::read_to_string(&vault_path)?;
+        let file = serde_json::from_str::<VaultFile>(&content)
+            .map_err(|e| VaultErrors::Format(format!("{e}")))?;
+
+        if file.version != VAULT_VERSION {
+            return Err(VaultErrors::Format(format!(
+                "unsupported vault version {}",
+                file.version
+            )));
+        }
+
+        match Self::unseal_with(&key, &file.sentinel) {
+            Ok(sentinel) if sentinel == SENTINEL => Ok(Self {
+                vault_path: vault_path.as_ref().to_path_buf(),
+                key,
+                file,
+            }),
+            _ => Err(VaultErrors::KeyMismatch),
+        }
+    }
+
+    /// Writes the vault to the path it was opened/created with
+    ///
+    /// The vault is written to a temp file first and then renamed into place, so the vault on disk is never
+    /// partially written.
+    ///
+    pub fn save(&self) -> Result<(), VaultErrors> {
+        let temp = Self::write_temp(&self.vault_path, Self::encode(&self.file)?, false)?;
+        fs::rename(temp, &self.vault_path)?;
+        Ok(())
+    }
+
+    /// Encrypts and stores a secret, overwrites any existing secret w/ the same name
+    ///
+    pub fn set(&mut self, name: impl AsRef<str>, secret: impl AsRef<[u8]>) -> Result<(), VaultErrors> {
+        let sealed = Self::seal_with(&self.key, secret.as_ref())?;
+        self.file.secrets.insert(name.as_ref().to_string(), sealed);
+        Ok(())
+    }
+
+    /// Decrypts a secret by name
+    ///
+    pub fn get(&self, name: impl AsRef<str>) -> Result<Vec<u8>, VaultErrors> {
+        match self.file.secrets.get(name.as_ref()) {
+            Some(sealed) => Self::unseal_with(&self.key, sealed),
+            None => Err(VaultErrors::NotFound(name.as_ref().to_string())),
+        }
+    }
+
+    /// Decrypts a secret by name as utf8 text
+    ///
+    pub fn get_text(&self, name: impl AsRef<str>) -> Result<String, VaultErrors> {
+        String::from_utf8(self.get(name)?).map_err(|_| VaultErrors::Crypto)
+    }
+
+    /// Removes a secret, returns true if the secret existed
+    ///
+    pub fn remove(&mut self, name: impl AsRef<str>) -> bool {
+        self.file.secrets.remove(name.as_ref()).is_some()
+    }
+
+    /// Returns an iterator over the names of secrets in the vault
+    ///
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.file.secrets.keys()
+    }
+
+    /// Generates a new key, re-encrypts every secret w/ the new key, and saves the re-encrypted vault and
+    /// the new key to key_path
+    ///
+    /// Both files are written to temp files before either is replaced, the vault is renamed into place first and
+    /// then the key. If rotating fails, the vault keeps using the old key. If the process exits between the two
+    /// renames, the new key can be recovered from `{key_path}.tmp`.
+    ///
+    pub fn rotate(&mut self, key_path: impl AsRef<Path>) -> Result<(), VaultErrors> {
+        let next_key = Self::generate_key();
+
+        let mut secrets = BTreeMap::default();
+        for (name, sealed) in self.file.secrets.iter() {
+            let plaintext = Self::unseal_with(&self.key, sealed)?;
+            secrets.insert(name.to_string(), Self::seal_with(&next_key, plaintext)?);
+        }
+
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            sentinel: Self::seal_with(&next_key, SENTINEL)?,
+            secrets,
+        };
+
+        let vault_temp = Self::write_temp(&self.vault_path, Self::encode(&file)?, false)?;
+        let key_temp = match Self::write_temp(&key_path, next_key, true) {
+            Ok(key_temp) => key_temp,
+            Err(err) => {
+                fs::remove_file(vault_temp).ok();
+                return Err(err);
+            }
+        };
+
+        if let Err(err) = fs::rename(&vault_temp, &self.vault_path) {
+            fs::remove_file(vault_temp).ok();
+            fs::remove_file(key_temp).ok();
+            return Err(err.into());
+        }
+        fs::rename(&key_temp, key_path)?;
+
+        self.file = file;
+        self.key = next_key;
+        event!(Level::DEBUG, "rotated vault key for {:?}", self.vault_path);
+        Ok(())
+    }
+
+    fn generate_key() -> [u8; 32] {
+        let mut key = [0; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        key
+    }
+
+    fn read_key(key_path: impl AsRef<Path>) -> Result<[u8; 32], VaultErrors> {
+        let bytes = fs::read(key_path)?;
+
+        if bytes.len() != 32 {
+            return Err(VaultErrors::InvalidKey);
+        }
+
+        let mut key = [0; 32];
+        key.copy_from_slice(&bytes);
+        Ok(key)
+    }
+
+    fn encode(file: &VaultFile) -> Result<String, VaultErrors> {
+        serde_json::to_string_pretty(file).map_err(|e| VaultErrors::Format(format!("{e}")))
+    }
+
+    /// Creates a new file for writing, if private is set the file is only readable by the owner
+    ///
+    fn create_new(path: impl AsRef<Path>, private: bool) -> Result<fs::File, VaultErrors> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            if private {
+                options.mode(0o600);
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = private;
+
+        options.open(path).map_err(|err| match err.kind() {
+            std::io::ErrorKind::AlreadyExists => VaultErrors::AlreadyExists(path.to_path_buf()),
+            _ => VaultErrors::Io(err),
+        })
+    }
+
+    /// Writes content to `{path}.tmp`, returns the path to the temp file so that it can be renamed into place
+    ///
+    fn write_temp(
+        path: impl AsRef<Path>,
+        content: impl AsRef<[u8]>,
+        private: bool,
+    ) -> Result<PathBuf, VaultErrors> {
+        let mut temp = path.as_ref().as_os_str().to_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        // A temp file left from a write that didn't complete
+        fs::remove_file(&temp).ok();
+
+        let mut file = Self::create_new(&temp, private)?;
+        file.write_all(content.as_ref())?;
+        file.sync_all()?;
+        Ok(temp)
+    }
+
+    fn seal_with(key: &[u8; 32], plaintext: impl AsRef<[u8]>) -> Result<Sealed, VaultErrors> {
+        let cipher = Aes256Gcm::new(Key::from_slice(key));
+
+        let mut iv = [0; 12];
+        rand::thread_rng().fill_bytes(&mut iv);
+
+        let payload = cipher
+            .encrypt(Nonce::from_slice(&iv), plaintext.as_ref())
+            .map_err(|_| VaultErrors::Crypto)?;
+
+        Ok(Sealed {
+            iv: base64::encode(iv),
+            payload: base64::encode(payload),
+        })
+    }
+
+    fn unseal_with(key: &[u8; 32], sealed: &Sealed) -> Result<Vec<u8>, VaultErrors> {
+        let cipher = Aes256Gcm::new(Key::from_slice(key));
+
+        let iv = base64::decode(&sealed.iv).map_err(|e| VaultErrors::Format(format!("{e}")))?;
+        let payload =
+            base64::decode(&sealed.payload).map_err(|e| VaultErrors::Format(format!("{e}")))?;
+
+        if iv.len() != 12 {
+            return Err(VaultErrors::Format("invalid iv length".to_string()));
+        }
+
+        cipher
+            .decrypt(Nonce::from_slice(&iv), payload.as_ref())
+            .map_err(|_| VaultErrors::Crypto)
+    }
+}
+
+#[test]
+fn test_vault() {
+    let dir = crate::testing::TempDir::new("vault");
+    let vault_path = dir.join("secrets.json");
+    let key_path = dir.join("secrets.key");
+
+    let mut vault = Vault::create(&vault_path, &key_path).expect("created");
+    vault.set("github_token", "hunter2").expect("set");
+    vault.save().expect("saved");
+
+    // Creating a vault again must not overwrite the existing vault or key
+    let key = fs::read(&key_path).expect("exists");
+    assert!(matches!(
+        Vault::create(&vault_path, &key_path),
+        Err(VaultErrors::AlreadyExists(path)) if path == vault_path
+    ));
+    assert!(matches!(
+        Vault::create(dir.join("other.json"), &key_path),
+        Err(VaultErrors::AlreadyExists(path)) if path == key_path
+    ));
+    assert!(!dir.join("other.json").exists());
+    assert_eq!(fs::read(&key_path).expect("exists"), key);
+
+    let content = fs::read_to_string(&vault_path).expect("exists");
+    assert!(!content.contains("hunter2"), "secrets.json must not contain plaintext");
+
+    let mut vault = Vault::open(&vault_path, &key_path).expect("opened");
+    assert_eq!(vault.get_text("github_token").ok(), Some("hunter2".to_string()));
+
+    // Rotating should re-encrypt w/ a new key, and the old key should no longer work
+    let old_key = fs::read(&key_path).expect("exists");
+    vault.rotate(&key_path).expect("rotated");
+    assert!(!dir.join("secrets.key.tmp").exists());
+    assert_ne!(old_key, fs::read(&key_path).expect("exists"));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&key_path).expect("exists").permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    let vault = Vault::open(&vault_path, &key_path).expect("opened");
+    assert_eq!(vault.get_text("github_token").ok(), Some("hunter2".to_string()));
+
+    let old_key_path = dir.join("old.key");
+    fs::write(&old_key_path, old_key).expect("written");
+    assert!(matches!(
+        Vault::open(&vault_path, &old_key_path),
+        Err(VaultErrors::KeyMismatch)
+    ));
+}
@@ -0,0 +1,436 @@
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use specs::storage::DenseVecStorage;
+use specs::Component;
+
+use crate::plugins::*;
+
+use super::{CancelToken, ThunkContext};
+
+/// Format of an archive
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// Returns the format from the extension of a file name, i.e. `bundle.tar.gz`
+    pub fn from_name(name: impl AsRef<str>) -> Option<Self> {
+        let name = name.as_ref().to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+
+    /// Packs entries of (path, content) into an archive
+    ///
+    /// Entries don't have timestamps, so packing the same entries returns the same bytes.
+    ///
+    pub fn pack(&self, entries: &[(String, Vec<u8>)]) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Tar => Self::pack_tar(Vec::new(), entries),
+            Self::TarGz => Self::pack_tar(GzEncoder::new(Vec::new(), Compression::default()), entries)?.finish(),
+            Self::Zip => {
+                let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+                let options = zip::write::FileOptions::default()
+                    .compression_method(zip::CompressionMethod::Deflated);
+
+                for (path, content) in entries {
+                    zip.start_file(path, options).map_err(Self::zip_error)?;
+                    zip.write_all(content)?;
+                }
+
+                Ok(zip.finish().map_err(Self::zip_error)?.into_inner())
+            }
+        }
+    }
+
+    /// Unpacks the file entries of an archive into (path, content),
+    ///
+    /// Caveat: Entry paths are returned as is, use `safe_path` before writing an entry to disk.
+    ///
+    pub fn unpack(&self, archive: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+        match self {
+            Self::Tar => Self::unpack_tar(archive),
+            Self::TarGz => Self::unpack_tar(GzDecoder::new(archive)),
+            Self::Zip => {
+                let mut zip = zip::ZipArchive::new(Cursor::new(archive)).map_err(Self::zip_error)?;
+                let mut entries = vec![];
+                for index in 0..zip.len() {
+                    let mut file = zip.by_index(index).map_err(Self::zip_error)?;
+                    if file.is_dir() {
+                        continue;
+                    }
+
+                    let mut content = vec![];
+                    file.read_to_end(&mut content)?;
+                    entries.push((file.name().to_string(), content));
+                }
+                Ok(entries)
+            }
+        }
+    }
+
+    fn pack_tar<W: Write>(writer: W, entries: &[(String, Vec<u8>)]) -> io::Result<W> {
+        let mut tar = tar::Builder::new(writer);
+        for (path, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, path, content.as_slice())?;
+        }
+        tar.into_inner()
+    }
+
+    fn unpack_tar(reader: impl Read) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut tar = tar::Archive::new(reader);
+        let mut entries = vec![];
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+
+            // Links and other special entries are skipped, only file content is unpacked
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let path = entry.path()?.to_string_lossy().to_string();
+            let mut content = vec![];
+            entry.read_to_end(&mut content)?;
+            entries.push((path, content));
+        }
+        Ok(entries)
+    }
+
+    fn zip_error(err: zip::result::ZipError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "tar" => Ok(Self::Tar),
+            "tar.gz" | "tgz" | "gzip" => Ok(Self::TarGz),
+            "zip" => Ok(Self::Zip),
+            other => Err(format!("unknown archive format `{other}`")),
+        }
+    }
+}
+
+/// Returns the path of an archive entry within work_dir, None if the entry would be written outside of work_dir,
+///
+/// i.e. entries that are absolute or that have a `..` component.
+///
+pub fn safe_path(work_dir: impl AsRef<Path>, entry: impl AsRef<str>) -> Option<PathBuf> {
+    let mut path = work_dir.as_ref().to_path_buf();
+    for component in Path::new(entry.as_ref()).components() {
+        match component {
+            std::path::Component::Normal(part) => path.push(part),
+            std::path::Component::CurDir => continue,
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+/// Reads each file in a directory recursively, paths are relative to the directory, sorted and separated by `/`
+fn read_dir_entries(dir: &Path, prefix: &str, entries: &mut Vec<(String, Vec<u8>)>) -> io::Result<()> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let name = if prefix.is_empty() { name } else { format!("{prefix}/{name}") };
+        if path.is_dir() {
+            read_dir_entries(&path, &name, entries)?;
+        } else if path.is_file() {
+            entries.push((name, std::fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the extension of a file name
+fn file_ext(file_name: &str) -> String {
+    Path::new(file_name)
+        .extension()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Packs a directory or the file blocks from previous into an archive
+#[derive(Component, Default)]
+#[storage(DenseVecStorage)]
+pub struct ArchiveFiles;
+
+impl Plugin<ThunkContext> for ArchiveFiles {
+    fn symbol() -> &'static str {
+        "archive"
+    }
+
+    fn description() -> &'static str {
+        "Packs the files in file_dir, or file blocks, into a tar, tar.gz or zip file block named archive_name."
+    }
+
+    fn caveats() -> &'static str {
+        "The format is read from `format`, or the extension of `archive_name`"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<(JoinHandle<ThunkContext>, CancelToken)> {
+        context.clone().task(|_| {
+            let mut tc = context.clone();
+            async move {
+                tc.as_mut().apply("previous");
+
+                let archive_name = tc.as_ref().find_text("archive_name").unwrap_or("archive.tar.gz".to_string());
+                let format = match tc.as_ref().find_text("format") {
+                    Some(format) => format.parse::<ArchiveFormat>().ok(),
+                    None => ArchiveFormat::from_name(&archive_name),
+                };
+
+                let format = match format {
+                    Some(format) => format,
+                    None => {
+                        tc.error(|a| {
+                            a.add_text_attr("error", format!("unknown archive format for {archive_name}"));
+                        });
+                        return Some(tc);
+                    }
+                };
+
+                let mut entries = vec![];
+                if let Some(file_dir) = tc.as_ref().find_text("file_dir") {
+                    if let Err(err) = read_dir_entries(Path::new(&file_dir), "", &mut entries) {
+                        tc.update_status_only(format!("# error reading {file_dir}, {err}")).await;
+                        tc.error(|a| {
+                            a.add_text_attr("error", format!("{}", err));
+                        });
+                        return Some(tc);
+                    }
+                }
+
+                for file_block in tc.as_ref().find_blocks("file") {
+                    if let (Some(file_name), Some(content)) = (file_block.find_text("file_name"), file_block.find_binary("content")) {
+                        entries.push((file_name, content));
+                    }
+                }
+
+                match format.pack(&entries) {
+                    Ok(archive) => {
+                        tc.update_status_only(format!("# packed {} entries into {archive_name}", entries.len())).await;
+                        if let Some(project) = tc.project.as_mut() {
+                            *project = project.with_block(&archive_name, "file", |c| {
+                                c.with_text("file_name", &archive_name)
+                                    .with_text("file_ext", file_ext(&archive_name))
+                                    .add_binary_attr("content", archive);
+                            });
+                        }
+                        tc.as_mut().add_int_attr("entries", entries.len() as i32);
+                    }
+                    Err(err) => {
+                        tc.update_status_only(format!("# error packing {archive_name}, {err}")).await;
+                        tc.error(|a| {
+                            a.add_text_attr("error", format!("{}", err));
+                        });
+                    }
+                }
+
+                Some(tc)
+            }
+        })
+    }
+}
+
+/// Unpacks archive_src, or archive file blocks from previous, into file blocks, and writes them to work_dir
+#[derive(Component, Default)]
+#[storage(DenseVecStorage)]
+pub struct ExtractFiles;
+
+impl Plugin<ThunkContext> for ExtractFiles {
+    fn symbol() -> &'static str {
+        "extract"
+    }
+
+    fn description() -> &'static str {
+        "Unpacks a tar, tar.gz or zip archive into a file block per entry, and writes each entry to work_dir."
+    }
+
+    fn caveats() -> &'static str {
+        "Entries w/ an absolute path or a `..` component are rejected, so that entries can't be written outside of work_dir"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<(JoinHandle<ThunkContext>, CancelToken)> {
+        context.clone().task(|_| {
+            let mut tc = context.clone();
+            async move {
+                tc.as_mut().apply("previous");
+
+                let mut archives = vec![];
+                if let Some(archive_src) = tc.as_ref().find_text("archive_src") {
+                    match tokio::fs::read(&archive_src).await {
+                        Ok(content) => archives.push((archive_src, content)),
+                        Err(err) => {
+                            tc.update_status_only(format!("# error reading {archive_src}, {err}")).await;
+                            tc.error(|a| {
+                                a.add_text_attr("error", format!("{}", err));
+                            });
+                        }
+                    }
+                }
+
+                for file_block in tc.as_ref().find_blocks("file") {
+                    if let (Some(file_name), Some(content)) = (file_block.find_text("file_name"), file_block.find_binary("content")) {
+                        if ArchiveFormat::from_name(&file_name).is_some() {
+                            archives.push((file_name, content));
+                        }
+                    }
+                }
+
+                let format = tc.as_ref().find_text("format").and_then(|f| f.parse::<ArchiveFormat>().ok());
+                let work_dir = tc.as_ref().find_text("work_dir");
+                let mut extracted = 0;
+                for (archive_name, archive) in archives {
+                    let entries = format
+                        .or_else(|| ArchiveFormat::from_name(&archive_name))
+                        .ok_or(format!("unknown archive format for {archive_name}"))
+                        .and_then(|format| format.unpack(&archive).map_err(|err| format!("{err}")));
+
+                    let entries = match entries {
+                        Ok(entries) => entries,
+                        Err(err) => {
+                            tc.update_status_only(format!("# error unpacking {archive_name}, {err}")).await;
+                            tc.error(|a| {
+                                a.add_text_attr("error", &err);
+                            });
+                            continue;
+                        }
+                    };
+
+                    for (entry, content) in entries {
+                        // Entries are checked even w/o a work_dir, since the file blocks can be written by write_file
+                        let path = match safe_path(work_dir.as_deref().unwrap_or_default(), &entry) {
+                            Some(path) => path,
+                            None => {
+                                tc.update_status_only(format!("# rejected entry {entry}, path is outside of work_dir")).await;
+                                tc.error(|a| {
+                                    a.add_text_attr("error", format!("rejected entry {entry}, path is outside of work_dir"));
+                                });
+                                continue;
+                            }
+                        };
+
+                        let mut file_src = None;
+                        if work_dir.is_some() {
+                            if let Some(parent) = path.parent() {
+                                tokio::fs::create_dir_all(parent).await.ok();
+                            }
+
+                            match tokio::fs::write(&path, &content).await {
+                                Ok(_) => {
+                                    tc.update_status_only(format!("# wrote file to {:?}", path)).await;
+                                    file_src = Some(format!("{:?}", path).trim_matches('"').to_string());
+                                }
+                                Err(err) => {
+                                    tc.update_status_only(format!("# error writing file {}", err)).await;
+                                    tc.error(|a| {
+                                        a.add_text_attr("error", format!("{}", err));
+                                    });
+                                    continue;
+                                }
+                            }
+                        }
+
+                        if let Some(project) = tc.project.as_mut() {
+                            *project = project.with_block(&entry, "file", |c| {
+                                c.with_text("file_name", &entry).with_text("file_ext", file_ext(&entry));
+                                if let Some(file_src) = file_src {
+                                    c.add_text_attr("file_src", file_src);
+                                }
+                                c.add_binary_attr("content", content);
+                            });
+                        }
+                        extracted += 1;
+                    }
+                }
+
+                tc.as_mut().add_int_attr("entries", extracted);
+                Some(tc)
+            }
+        })
+    }
+}
+
+#[test]
+fn test_archive() {
+    use crate::testing::PluginHarness;
+
+    let entries = vec![
+        ("a.txt".to_string(), b"hello".to_vec()),
+        ("dir/b.txt".to_string(), b"world".to_vec()),
+    ];
+    for format in [ArchiveFormat::Tar, ArchiveFormat::TarGz, ArchiveFormat::Zip] {
+        let archive = format.pack(&entries).expect("packed");
+        assert_eq!(format.pack(&entries).expect("packed"), archive);
+        assert_eq!(format.unpack(&archive).expect("unpacked"), entries);
+    }
+
+    assert_eq!(ArchiveFormat::from_name("bundle.tar.gz"), Some(ArchiveFormat::TarGz));
+    assert_eq!("zip".parse::<ArchiveFormat>(), Ok(ArchiveFormat::Zip));
+    assert_eq!(safe_path("work", "dir/./b.txt"), Some(PathBuf::from("work/dir/b.txt")));
+    assert_eq!(safe_path("work", "../evil.txt"), None);
+    assert_eq!(safe_path("work", "/etc/passwd"), None);
+
+    // tar::Builder rejects `..` paths, so the name of the evil entry is written to the header directly
+    let mut tar = tar::Builder::new(Vec::new());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(4);
+    header.set_mode(0o644);
+    header.as_old_mut().name[..11].copy_from_slice(b"../evil.txt");
+    header.set_cksum();
+    tar.append(&header, b"evil".as_slice()).expect("appended");
+
+    let mut header = tar::Header::new_gnu();
+    header.set_size(5);
+    header.set_mode(0o644);
+    tar.append_data(&mut header, "dir/b.txt", b"world".as_slice()).expect("appended");
+    let archive = tar.into_inner().expect("packed");
+
+    let dir = crate::testing::TempDir::new("extract");
+    let work_dir = dir.join("work");
+    let archive_src = dir.join("work.tar");
+    std::fs::write(&archive_src, archive).expect("can write archive");
+
+    let run = PluginHarness::new(format!(
+        r#"
+    add archive_src .text {}
+    add work_dir    .text {}
+    "#,
+        archive_src.to_str().expect("utf8 path"),
+        work_dir.to_str().expect("utf8 path"),
+    ))
+    .run::<ExtractFiles>();
+
+    run.assert_completed().assert_error("error").assert_int("entries", 1);
+    assert_eq!(run.assert_block("dir/b.txt", "file").find_binary("content"), Some(b"world".to_vec()));
+    assert_eq!(std::fs::read(work_dir.join("dir/b.txt")).expect("extracted"), b"world");
+    assert!(!dir.join("evil.txt").exists());
+}
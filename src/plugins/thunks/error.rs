@@ -52,7 +52,7 @@ impl ErrorContext {
     }
 
     pub fn set_previous_attempt(&mut self, previous: AttributeGraph) {
-        event!(Level::INFO, "previous {:#?}", previous);
+        event!(Level::INFO, "previous {:#?}", previous.redacted());
 
         self.0.add_block("previous_attempt", |c| {
             c.copy(&previous);
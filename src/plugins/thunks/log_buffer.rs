@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::path::Path;
+
+use specs::storage::HashMapStorage;
+use specs::{Component, Join, World, WorldExt};
+use tracing::Level;
+
+use super::ContextWriter;
+
+/// Bounded log of status updates for an entity,
+///
+/// When the buffer is at capacity, the oldest line is dropped to make room for the next line. The event runtime
+/// records each status update to the entity's buffer, so the log is available w/ or w/o an editor.
+///
+#[derive(Component, Clone, Debug)]
+#[storage(HashMapStorage)]
+pub struct LogBuffer {
+    /// Maximum number of lines kept
+    capacity: usize,
+    /// Number of lines dropped since the buffer was created
+    dropped: usize,
+    /// Number of lines pushed since the buffer was created
+    pushed: usize,
+    /// Progress from the last status update
+    progress: f32,
+    /// Lines w/ the level parsed from the line's tag
+    lines: VecDeque<(Level, String)>,
+}
+
+impl Default for LogBuffer {
+    fn default() -> Self {
+        Self::new(1000)
+    }
+}
+
+impl LogBuffer {
+    /// Returns a new log buffer that keeps at most capacity lines
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            dropped: 0,
+            pushed: 0,
+            progress: 0.0,
+            lines: VecDeque::with_capacity(capacity.min(1000)),
+        }
+    }
+
+    /// Pushes a line to the buffer, parses the optional level tag at the start of the line
+    ///
+    /// A buffer w/ 0 capacity doesn't keep any lines.
+    ///
+    pub fn push(&mut self, line: impl AsRef<str>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let (level, message) = ContextWriter::parse_level(line.as_ref());
+        if self.lines.len() >= self.capacity && self.lines.pop_front().is_some() {
+            self.dropped += 1;
+        }
+
+        self.lines.push_back((level, message.to_string()));
+        self.pushed += 1;
+    }
+
+    /// Records a status update, empty statuses only update the progress
+    pub fn update(&mut self, progress: f32, status: impl AsRef<str>) {
+        self.progress = progress;
+        if !status.as_ref().is_empty() {
+            self.push(status);
+        }
+    }
+
+    /// Returns the progress from the last status update
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+
+    /// Returns the last line in the buffer
+    pub fn last(&self) -> Option<&(Level, String)> {
+        self.lines.back()
+    }
+
+    /// Returns the number of lines pushed since the buffer was created, including dropped lines
+    pub fn pushed(&self) -> usize {
+        self.pushed
+    }
+
+    /// Returns an iterator over the lines in the buffer, oldest first
+    pub fn lines(&self) -> impl Iterator<Item = &(Level, String)> {
+        self.lines.iter()
+    }
+
+    /// Returns an iterator over lines at or above the level, i.e. WARN returns WARN and ERROR lines
+    pub fn lines_at(&self, level: Level) -> impl Iterator<Item = &(Level, String)> {
+        self.lines.iter().filter(move |(l, _)| *l <= level)
+    }
+
+    /// Returns the number of lines in the buffer
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Returns true if the buffer has no lines
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Returns the number of lines that were dropped because the buffer was at capacity
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Saves the buffer to a file
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Saves the log buffer of each entity in the world to `{dir}/{entity_id}.log`,
+    /// for runners w/o an editor
+    pub fn save_all(world: &World, dir: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::create_dir_all(&dir)?;
+
+        let log_buffers = world.read_component::<LogBuffer>();
+        for (entity, log_buffer) in (&world.entities(), &log_buffers).join() {
+            log_buffer.save(dir.as_ref().join(format!("{}.log", entity.id())))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for LogBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.dropped > 0 {
+            writeln!(f, "[{}] ... {} lines dropped", Level::WARN, self.dropped)?;
+        }
+
+        for (level, line) in self.lines.iter() {
+            writeln!(f, "[{level}] {line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_log_buffer() {
+    let mut log_buffer = LogBuffer::new(2);
+    log_buffer.update(0.5, "[warn] first");
+    log_buffer.update(0.6, "");
+    log_buffer.push("second");
+    log_buffer.push("[error] third");
+
+    assert_eq!(log_buffer.len(), 2);
+    assert_eq!(log_buffer.dropped(), 1);
+    assert_eq!(log_buffer.pushed(), 3);
+    assert_eq!(log_buffer.progress(), 0.6);
+    assert_eq!(log_buffer.last(), Some(&(Level::ERROR, "third".to_string())));
+    assert_eq!(log_buffer.lines_at(Level::WARN).count(), 1);
+
+    let dir = crate::testing::TempDir::new("log_buffer");
+    let path = dir.join("log_buffer.log");
+    log_buffer.save(&path).expect("can save");
+    assert_eq!(
+        std::fs::read_to_string(&path).expect("saved"),
+        "[WARN] ... 1 lines dropped\n[INFO] second\n[ERROR] third\n"
+    );
+
+    // A buffer w/o capacity keeps nothing, and doesn't count lines as dropped
+    let mut log_buffer = LogBuffer::new(0);
+    log_buffer.push("ignored");
+    assert!(log_buffer.is_empty());
+    assert_eq!(log_buffer.dropped(), 0);
+}
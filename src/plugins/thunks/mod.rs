@@ -3,13 +3,16 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 use crate::AttributeGraph;
+use crate::Clock;
+use crate::Random;
 use crate::RuntimeDispatcher;
 use crate::state::AttributeIndex;
+use crate::state::Redactor;
 use atlier::system::Value;
 use hyper::client::HttpConnector;
 use imgui::Ui;
 use specs::Component;
-use specs::{storage::DenseVecStorage, Entity};
+use specs::{storage::DenseVecStorage, storage::FlaggedStorage, Entity};
 
 mod open_file;
 pub use open_file::OpenFile;
@@ -26,6 +29,7 @@ use tokio::net::UdpSocket;
 use tokio::select;
 use tokio::sync;
 use tokio::sync::oneshot;
+use tracing::Instrument;
 use tracing::Level;
 use tracing::event;
 pub use write_file::WriteFile;
@@ -33,6 +37,17 @@ pub use write_file::WriteFile;
 mod timer;
 pub use timer::Timer;
 
+mod watch;
+pub use watch::Watch;
+pub use watch::FileWatcher;
+pub use watch::FileWatchers;
+
+mod archive;
+pub use archive::ArchiveFiles;
+pub use archive::ExtractFiles;
+pub use archive::ArchiveFormat;
+pub use archive::safe_path;
+
 mod println;
 pub use println::Println;
 
@@ -42,7 +57,14 @@ pub use error::ErrorContext;
 mod dispatch;
 pub use dispatch::Dispatch;
 
+mod writer;
+pub use writer::ContextWriter;
+
+mod log_buffer;
+pub use log_buffer::LogBuffer;
+
 use super::block::BlockAddress;
+use super::network::ProxiedMessage;
 use super::{BlockContext, Plugin, Project};
 use tokio::{runtime::Handle, sync::mpsc::Sender, sync::oneshot::channel, task::JoinHandle};
 
@@ -137,7 +159,7 @@ impl From<CancelToken> for CancelThunk {
 /// to the context once, on subsequent calls of the plugin, the context will remain the same.
 /// 
 #[derive(Component, Default, Clone)]
-#[storage(DenseVecStorage)]
+#[storage(FlaggedStorage)]
 pub struct ThunkContext {
     /// Underlying block context for this thunk
     pub block: BlockContext,
@@ -166,6 +188,39 @@ pub struct ThunkContext {
     ///     2) wait for the connection to close, 
     ///     3) and cannot be stored in the context,
     udp_socket: Option<Arc<UdpSocket>>,
+    /// Clock plugins read the current time from, set by the event runtime from the world's Clock resource
+    clock: Clock,
+    /// Randomness plugins read from, set by the event runtime from the world's Random resource
+    random: Random,
+    /// File watchers kept alive between calls of the watch plugin, set by the event runtime from the world's FileWatchers resource
+    file_watchers: FileWatchers,
+    /// Redactor for the sensitive values of the block, cleared when the block is borrowed mutably
+    redactor: CachedRedactor,
+}
+
+/// Redactor cached by a context, so that status updates don't scan the graph for sensitive values on every update
+///
+#[derive(Default)]
+struct CachedRedactor(std::sync::Mutex<Option<Arc<Redactor>>>);
+
+impl CachedRedactor {
+    /// Returns the cached redactor, or caches a new redactor for graph
+    fn get_or_init(&self, graph: &AttributeGraph) -> Arc<Redactor> {
+        let mut cached = self.0.lock().expect("not poisoned");
+        cached.get_or_insert_with(|| Arc::new(graph.redactor())).clone()
+    }
+
+    /// Clears the cached redactor
+    fn clear(&mut self) {
+        *self.0.get_mut().expect("not poisoned") = None;
+    }
+}
+
+impl Clone for CachedRedactor {
+    fn clone(&self) -> Self {
+        // The clone has the same graph, so the cached redactor is still valid
+        Self(std::sync::Mutex::new(self.0.lock().expect("not poisoned").clone()))
+    }
 }
 
 impl AttributeIndex for ThunkContext {
@@ -228,6 +283,42 @@ impl ThunkContext {
         async_enabled
     }
 
+    /// Sets the clock plugins read the current time from
+    /// 
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    /// Returns the clock for reading the current time, by default this is the system clock
+    /// 
+    pub fn clock(&self) -> Clock {
+        self.clock.clone()
+    }
+
+    /// Sets the source of randomness plugins read from
+    /// 
+    pub fn set_random(&mut self, random: Random) {
+        self.random = random;
+    }
+
+    /// Returns the source of randomness, by default this is the thread rng
+    /// 
+    pub fn random(&self) -> Random {
+        self.random.clone()
+    }
+
+    /// Sets the file watchers the watch plugin keeps its watcher in
+    /// 
+    pub fn set_file_watchers(&mut self, file_watchers: FileWatchers) {
+        self.file_watchers = file_watchers;
+    }
+
+    /// Returns the file watchers the watch plugin keeps its watcher in, by default each context has its own
+    /// 
+    pub fn file_watchers(&self) -> FileWatchers {
+        self.file_watchers.clone()
+    }
+
     /// Enables output to a char_device, a plugin can use to output bytes to. 
     /// 
     /// The implementation of the char_device, can choose how to handle this output, 
@@ -390,12 +481,16 @@ impl ThunkContext {
 
             let task = (task)(cancel);
             Some((
-                handle.spawn(async {
-                    match task.await {
-                        Some(next) => next,
-                        None => default_return,
+                // The task is instrumented w/ the current span, so that it's nested under the span of the event that called it
+                handle.spawn(
+                    async {
+                        match task.await {
+                            Some(next) => next,
+                            None => default_return,
+                        }
                     }
-                }),
+                    .instrument(tracing::Span::current()),
+                ),
                 tx,
             ))
         } else {
@@ -405,6 +500,8 @@ impl ThunkContext {
 
     /// Sends an update for the status and progress
     /// 
+    /// Sensitive values are masked before the status is sent.
+    /// 
     pub async fn update_progress(&self, status: impl AsRef<str>, progress: f32) {
         if let ThunkContext {
             status_updates: Some(status_updates),
@@ -413,7 +510,7 @@ impl ThunkContext {
         } = self
         {
             match status_updates
-                .send((*entity, progress, status.as_ref().to_string()))
+                .send((*entity, progress, self.redactor().redact(status)))
                 .await
             {
                 Ok(_) => {}
@@ -422,6 +519,20 @@ impl ThunkContext {
         }
     }
 
+    /// Returns the redactor for the sensitive values of this context,
+    ///
+    /// The redactor is cached until the context's graph is borrowed mutably w/ `as_mut()`.
+    ///
+    pub fn redactor(&self) -> Arc<Redactor> {
+        self.redactor.get_or_init(self.as_ref())
+    }
+
+    /// Returns a writer for this context, each line written becomes a status update
+    /// 
+    pub fn writer(&self) -> ContextWriter {
+        ContextWriter::new(self.clone())
+    }
+
     /// Updates status of thunk execution
     ///
     /// TODO: The ergonomics of this api and the one above need some improvement,
@@ -431,7 +542,7 @@ impl ThunkContext {
 
         if self.as_ref().is_enabled("debug").unwrap_or_default() {
             let block_name = &self.block.block_name;
-            let status = status.as_ref();
+            let status = self.redactor().redact(status);
             event!(Level::DEBUG, "{block_name}\t{status}"); 
         }
     }
@@ -486,6 +597,15 @@ impl ThunkContext {
             None
         }
     }
+
+    /// Receives the next message a proxy sent to this context's socket, decoded w/ `ProxiedMessage::decode`
+    ///
+    /// Returns None if the socket isn't enabled, or if the message isn't a proxied message
+    ///
+    pub async fn receive_proxied(&self, buffer: &mut [u8]) -> Option<ProxiedMessage> {
+        let (read, _) = self.socket()?.recv_from(buffer).await.ok()?;
+        ProxiedMessage::decode(&buffer[..read])
+    }
 }
 
 /// Some utility methods
@@ -493,6 +613,7 @@ impl ThunkContext {
 impl ThunkContext {
     /// Updates error block
     pub fn error(&mut self, record: impl Fn(&mut AttributeGraph)) {
+        self.redactor.clear();
         if !self.block.update_block("error", &record) {
             self.block.add_block("error", record);
         }
@@ -520,6 +641,10 @@ impl From<AttributeGraph> for ThunkContext {
             dispatcher: None,
             char_device: None,
             udp_socket: None,
+            clock: Clock::default(),
+            random: Random::default(),
+            file_watchers: FileWatchers::default(),
+            redactor: CachedRedactor::default(),
         }
     }
 }
@@ -532,6 +657,7 @@ impl AsRef<AttributeGraph> for ThunkContext {
 
 impl AsMut<AttributeGraph> for ThunkContext {
     fn as_mut(&mut self) -> &mut AttributeGraph {
+        self.redactor.clear();
         self.block.as_mut()
     }
 }
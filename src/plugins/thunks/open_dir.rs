@@ -28,8 +28,9 @@ impl Plugin<ThunkContext> for OpenDir {
             let mut tc = context.clone();
 
             async move {
+                let mut log = tc.writer();
                 if let Some(file_dir) = tc.as_ref().find_text("file_dir") {
-                    tc.update_status_only("file directory found").await;
+                    log.writeln("file directory found").await.ok();
 
                     tc.block.block_name = file_dir.to_string();
 
@@ -42,15 +43,15 @@ impl Plugin<ThunkContext> for OpenDir {
                             match read_dir.next_entry().await {
                                 Ok(dir_entry) => {
                                     progress += 0.01;
-                                    tc.update_progress("got next entry", progress).await;
+                                    log.set_progress(progress);
+                                    log.writeln("got next entry").await.ok();
                                     match dir_entry {
                                         Some(entry) => {
                                             progress += 0.01;
-                                            tc.update_progress(
-                                                format!("found entry {:?}", entry),
-                                                progress,
-                                            )
-                                            .await;
+                                            log.set_progress(progress);
+                                            log.writeln(format!("found entry {:?}", entry))
+                                                .await
+                                                .ok();
                                             let path_buf = entry.path();
                                             let file_src = path_buf.to_str().unwrap_or_default();
                                             let mut work_file = tc.clone();
@@ -59,17 +60,17 @@ impl Plugin<ThunkContext> for OpenDir {
                                                 OpenFile::call_with_context(&mut work_file)
                                             {
                                                 progress += 0.01;
-                                                tc.update_progress("open file task started", progress).await;
+                                                log.set_progress(progress);
+                                                log.writeln("open file task started").await.ok();
                                                 if let Some(result) = handle.await.ok() {
                                                     progress += 0.01;
-                                                    tc.update_progress(
-                                                        format!(
-                                                            "open file task completing, merging, {}",
-                                                            result.as_ref().entity()
-                                                        ),
-                                                        progress,
-                                                    )
-                                                    .await;
+                                                    log.set_progress(progress);
+                                                    log.writeln(format!(
+                                                        "open file task completing, merging, {}",
+                                                        result.as_ref().entity()
+                                                    ))
+                                                    .await
+                                                    .ok();
 
                                                     let file_block =
                                                         result.as_ref().find_imported_graph(
@@ -93,15 +94,14 @@ impl Plugin<ThunkContext> for OpenDir {
                                                             block_context.transpile().ok()
                                                         {
                                                             progress += 0.01;
-                                                            tc.update_progress(
-                                                                format!(
-                                                                    "Transpiled {} \n{}",
-                                                                    block_context.block_name,
-                                                                    transpiled
-                                                                ),
-                                                                progress,
-                                                            )
-                                                            .await;
+                                                            log.set_progress(progress);
+                                                            log.writeln(format!(
+                                                                "Transpiled {} \n{}",
+                                                                block_context.block_name,
+                                                                transpiled
+                                                            ))
+                                                            .await
+                                                            .ok();
 
                                                             // transpiles the content into a message
                                                             tc.as_mut().add_message(
@@ -1,4 +1,4 @@
-use std::{path::PathBuf, time::Instant};
+use std::path::PathBuf;
 
 use specs::Component;
 use tokio::fs;
@@ -33,7 +33,8 @@ impl Plugin<ThunkContext> for OpenFile {
             let mut tc = context.clone();
 
             async {            
-                let start = Instant::now();
+                let clock = tc.clock();
+                let start = clock.now();
                 if let Some(file_src) = tc.as_ref().find_text("file_src") {
                     tc.update_status_only("file source found").await;
 
@@ -71,7 +72,8 @@ impl Plugin<ThunkContext> for OpenFile {
                         }
                     } 
                 }
-                tc.as_mut().add_text_attr("elapsed", format!("{:?}", start.elapsed()));
+                let elapsed = (clock.now() - start).to_std().unwrap_or_default();
+                tc.as_mut().add_text_attr("elapsed", format!("{:?}", elapsed));
                 Some(tc)
             }
         })
@@ -17,7 +17,7 @@ impl Plugin<ThunkContext> for Println {
                 tc.as_mut().apply("previous");
 
                 if tc.as_ref().is_enabled("debug").unwrap_or_default() {
-                    eprintln!("{:#?}", tc.as_ref());
+                    eprintln!("{:#?}", tc.as_ref().redacted());
                 }
 
                 Some(tc) 
@@ -1,5 +1,3 @@
-use std::time::Instant;
-
 use crate::plugins::*;
 use specs::storage::DenseVecStorage;
 use tokio::task::JoinHandle;
@@ -10,6 +8,11 @@ use super::CancelToken;
 #[storage(DenseVecStorage)]
 pub struct Timer;
 
+impl Timer {
+    /// Interval progress is reported at
+    pub const TICK_MS: i64 = 16;
+}
+
 impl Plugin<ThunkContext> for Timer {
     fn symbol() -> &'static str {
         "timer"
@@ -36,27 +39,32 @@ impl Plugin<ThunkContext> for Timer {
                     duration += d_ms / 1000.0;
                 }
 
-                let start = Instant::now();
+                let clock = tc.clock();
+                let start = clock.now();
+                let end = start + chrono::Duration::milliseconds((duration * 1000.0) as i64);
 
                 loop {
-                    let elapsed = start.elapsed();
-                    let progress = elapsed.as_secs_f32() / duration;
+                    let elapsed = clock.now() - start;
+                    let progress = elapsed.num_milliseconds() as f32 / (duration * 1000.0);
                     if progress < 1.0 {
                         
                         if tc.as_ref().is_enabled("quiet").unwrap_or_default() {
                             tc.update_progress("", progress).await;
                         } else {
-                            tc.update_progress(format!("elapsed {} ms", elapsed.as_millis()), progress).await;
+                            tc.update_progress(format!("elapsed {} ms", elapsed.num_milliseconds()), progress).await;
                         }
                     } else {
                         tc.as_mut()
-                            .add_text_attr("elapsed", format!("{:?}", elapsed));
+                            .add_text_attr("elapsed", format!("{:?}", elapsed.to_std().unwrap_or_default()));
                         break;
                     }
 
                     if ThunkContext::is_cancelled(&mut cancel_source) {
                         break;
                     }
+
+                    // Time is read from the context's clock, so that a virtual clock can drive the timer
+                    clock.sleep_until(end.min(clock.now() + chrono::Duration::milliseconds(Timer::TICK_MS))).await;
                 }
 
                 Some(tc)
@@ -0,0 +1,475 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use atlier::system::Value;
+use notify::{Config, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use specs::storage::DenseVecStorage;
+use specs::world::EntitiesRes;
+use specs::{Component, Entity};
+use tokio::select;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::Mutex as AsyncMutex;
+use tracing::{event, Level};
+
+use crate::plugins::{AsyncContext, Plugin, ThunkContext};
+use crate::{AttributeGraph, Predicate};
+
+/// Plugin that waits for files to change, so that the rest of the sequence is called each time a file changes, i.e. in runmd,
+///
+/// ````runmd
+/// ``` build call
+/// define a watch .symbol src
+/// define b process .symbol build
+/// add repeat .enable
+/// ```
+///
+/// ``` src watch
+/// add watch_path .text src
+/// define rust include .text *.rs
+/// define target exclude .text target/*
+/// add debounce_ms .int 200
+/// ```
+/// ````
+///
+/// When files change, each changed path is added to the context as a `changed` symbol, see `Watch::changed`, and to the
+/// `{block_name} watch` project block that is handed off to the next event.
+///
+/// Paths are watched w/ inotify on linux, if inotify can't be used or `poll` is enabled, paths are polled every `poll_interval_ms` instead.
+///
+/// The watcher is kept alive between calls for the same entity, so that files changed while the rest of the sequence runs complete
+/// the next call right away, see `FileWatchers`.
+///
+#[derive(Component, Default)]
+#[storage(DenseVecStorage)]
+pub struct Watch;
+
+impl Watch {
+    /// Default time to wait for more changes, before the plugin completes
+    pub const DEFAULT_DEBOUNCE_MS: i32 = 200;
+
+    /// Default interval to poll paths at, if inotify can't be used
+    pub const DEFAULT_POLL_INTERVAL_MS: i32 = 1000;
+
+    /// Returns the changed paths added to a context by this plugin
+    pub fn changed(graph: &AttributeGraph) -> Vec<String> {
+        graph
+            .find_symbol_values("changed")
+            .into_iter()
+            .filter_map(|(_, value)| match value {
+                Value::TextBuffer(path) => Some(path),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns a file watcher configured from the attributes of graph
+    pub fn watcher(graph: &AttributeGraph) -> notify::Result<FileWatcher> {
+        let poll_interval = graph
+            .find_int("poll_interval_ms")
+            .unwrap_or(Self::DEFAULT_POLL_INTERVAL_MS)
+            .max(1);
+        let mut watcher = FileWatcher::new(
+            Duration::from_millis(poll_interval as u64),
+            graph.is_enabled("poll").unwrap_or_default(),
+        )?;
+
+        for include in Self::find_all(graph, "include") {
+            watcher.include(include);
+        }
+
+        for exclude in Self::find_all(graph, "exclude") {
+            watcher.exclude(exclude);
+        }
+
+        let mut paths = Self::find_all(graph, "watch_path");
+        if paths.is_empty() {
+            paths.push(".".to_string());
+        }
+
+        let recursive = graph.is_enabled("recursive").unwrap_or(true);
+        for path in paths {
+            watcher.watch(path, recursive)?;
+        }
+
+        Ok(watcher)
+    }
+
+    /// Returns the watcher for the context's entity from the context's `FileWatchers`, which is created on the first call and kept
+    /// alive between calls
+    ///
+    /// Contexts w/o an entity get a new watcher.
+    ///
+    pub fn shared_watcher(tc: &ThunkContext) -> notify::Result<Arc<AsyncMutex<FileWatcher>>> {
+        match tc.entity {
+            Some(entity) => tc.file_watchers().get_or_create(entity, tc.as_ref()),
+            None => Ok(Arc::new(AsyncMutex::new(Self::watcher(tc.as_ref())?))),
+        }
+    }
+
+    /// Returns the attributes a watcher is configured from, see `Watch::watcher`
+    fn watcher_key(graph: &AttributeGraph) -> String {
+        format!(
+            "{:?} {:?} {:?} {:?} {:?} {:?}",
+            Self::find_all(graph, "watch_path"),
+            Self::find_all(graph, "include"),
+            Self::find_all(graph, "exclude"),
+            graph.find_int("poll_interval_ms"),
+            graph.is_enabled("poll"),
+            graph.is_enabled("recursive"),
+        )
+    }
+
+    /// Returns the text values of name, declared either w/ `add` or `define`
+    fn find_all(graph: &AttributeGraph, name: &str) -> Vec<String> {
+        graph
+            .find_text(name)
+            .into_iter()
+            .chain(
+                graph
+                    .find_symbol_values(name)
+                    .into_iter()
+                    .filter_map(|(_, value)| match value {
+                        Value::TextBuffer(text) => Some(text),
+                        _ => None,
+                    }),
+            )
+            .collect()
+    }
+}
+
+impl Plugin<ThunkContext> for Watch {
+    fn symbol() -> &'static str {
+        "watch"
+    }
+
+    fn description() -> &'static str {
+        "Waits for files under `watch_path` to change, w/ `include` and `exclude` globs"
+    }
+
+    fn caveats() -> &'static str {
+        "Changes are debounced for `debounce_ms`, so that a burst of changes completes the plugin once"
+    }
+
+    fn call_with_context(context: &mut ThunkContext) -> Option<AsyncContext> {
+        context.clone().task(|mut cancel_source| {
+            let mut tc = context.clone();
+            async move {
+                let block_name = tc.block.block_name.to_string();
+                let watcher = match Watch::shared_watcher(&tc) {
+                    Ok(watcher) => watcher,
+                    Err(err) => {
+                        event!(Level::ERROR, "could not watch paths for {block_name}, {err}");
+                        tc.error(|g| {
+                            g.with_text(&block_name, Watch::symbol());
+                        });
+                        return Some(tc);
+                    }
+                };
+
+                let debounce = tc
+                    .as_ref()
+                    .find_int("debounce_ms")
+                    .unwrap_or(Watch::DEFAULT_DEBOUNCE_MS)
+                    .max(0);
+                let mut watcher = watcher.lock().await;
+                tc.update_status_only(format!("watching, polling: {}", watcher.is_polling()))
+                    .await;
+
+                let changed = select! {
+                    changed = watcher.changed(Duration::from_millis(debounce as u64)) => changed,
+                    _ = &mut cancel_source => {
+                        return None;
+                    }
+                };
+
+                let changed = changed
+                    .iter()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .collect::<Vec<_>>();
+
+                for (index, path) in changed.iter().enumerate() {
+                    tc.as_mut()
+                        .define(format!("change_{index}"), "changed")
+                        .edit_as(Value::TextBuffer(path.to_string()));
+                }
+
+                if let Some(project) = tc.project.as_mut() {
+                    *project = project.with_block(&block_name, "watch", |b| {
+                        b.with_int("count", changed.len() as i32);
+                        for (index, path) in changed.iter().enumerate() {
+                            b.with_text(format!("changed_{index}"), path);
+                        }
+                    });
+                }
+
+                tc.update_progress(format!("{} files changed", changed.len()), 1.0)
+                    .await;
+                Some(tc)
+            }
+        })
+    }
+}
+
+/// Resource that keeps the file watcher of each entity alive between calls of the `watch` plugin,
+///
+/// The event runtime sets the world's watchers on each context it starts, and drops the watchers of entities that were deleted.
+/// Clones share the same watchers.
+///
+#[derive(Clone, Default)]
+pub struct FileWatchers(Arc<Mutex<HashMap<Entity, (String, Arc<AsyncMutex<FileWatcher>>)>>>);
+
+impl FileWatchers {
+    /// Returns the watcher for entity, if the watch attributes of graph changed since the watcher was created, a new watcher is created
+    pub fn get_or_create(
+        &self,
+        entity: Entity,
+        graph: &AttributeGraph,
+    ) -> notify::Result<Arc<AsyncMutex<FileWatcher>>> {
+        let mut watchers = self.lock();
+
+        let key = Watch::watcher_key(graph);
+        match watchers.get(&entity) {
+            Some((existing, watcher)) if *existing == key => Ok(watcher.clone()),
+            _ => {
+                let watcher = Arc::new(AsyncMutex::new(Watch::watcher(graph)?));
+                watchers.insert(entity, (key, watcher.clone()));
+                Ok(watcher)
+            }
+        }
+    }
+
+    /// Drops the watchers of entities that are no longer alive
+    pub fn maintain(&self, entities: &EntitiesRes) {
+        self.lock().retain(|entity, _| entities.is_alive(*entity));
+    }
+
+    /// Returns the number of watchers being kept alive
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns true if no watchers are being kept alive
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn lock(&self) -> MutexGuard<HashMap<Entity, (String, Arc<AsyncMutex<FileWatcher>>)>> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Watches paths for changes w/ include and exclude globs, w/ inotify on linux, falling back to polling
+///
+/// Globs are matched against paths relative to the current directory, see `Predicate::Matches`.
+///
+pub struct FileWatcher {
+    watcher: Box<dyn Watcher + Send>,
+    polling: bool,
+    poll_interval: Duration,
+    watched: Vec<(PathBuf, RecursiveMode)>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    sender: UnboundedSender<PathBuf>,
+    changes: UnboundedReceiver<PathBuf>,
+}
+
+impl FileWatcher {
+    /// Returns a new file watcher, if poll is true, or if inotify can't be used, paths are polled every poll_interval
+    pub fn new(poll_interval: Duration, poll: bool) -> notify::Result<Self> {
+        let (sender, changes) = unbounded_channel();
+
+        let (watcher, polling) = if poll {
+            (Self::poll_watcher(sender.clone(), poll_interval)?, true)
+        } else {
+            match RecommendedWatcher::new(Self::handler(sender.clone()), Config::default()) {
+                Ok(watcher) => (Box::new(watcher) as Box<dyn Watcher + Send>, false),
+                Err(err) => {
+                    event!(Level::WARN, "could not create watcher, falling back to polling, {err}");
+                    (Self::poll_watcher(sender.clone(), poll_interval)?, true)
+                }
+            }
+        };
+
+        Ok(Self {
+            watcher,
+            polling,
+            poll_interval,
+            watched: vec![],
+            include: vec![],
+            exclude: vec![],
+            sender,
+            changes,
+        })
+    }
+
+    /// Returns true if paths are being polled
+    pub fn is_polling(&self) -> bool {
+        self.polling
+    }
+
+    /// Adds a glob, if any globs are included, only paths that match one are reported
+    pub fn include(&mut self, glob: impl AsRef<str>) {
+        self.include.push(glob.as_ref().to_string());
+    }
+
+    /// Adds a glob, paths that match are never reported
+    pub fn exclude(&mut self, glob: impl AsRef<str>) {
+        self.exclude.push(glob.as_ref().to_string());
+    }
+
+    /// Watches a path, if inotify fails to watch the path, i.e. the watch limit is reached, all paths are polled instead
+    pub fn watch(&mut self, path: impl AsRef<Path>, recursive: bool) -> notify::Result<()> {
+        let path = path.as_ref().to_path_buf();
+        let mode = if recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        };
+
+        match self.watcher.watch(&path, mode) {
+            Ok(_) => {}
+            Err(err) if !self.polling && path.exists() => {
+                event!(Level::WARN, "could not watch {:?}, falling back to polling, {err}", path);
+                self.watcher = Self::poll_watcher(self.sender.clone(), self.poll_interval)?;
+                self.polling = true;
+                for (path, mode) in self.watched.iter() {
+                    self.watcher.watch(path, *mode)?;
+                }
+                self.watcher.watch(&path, mode)?;
+            }
+            Err(err) => return Err(err),
+        }
+
+        self.watched.push((path, mode));
+        Ok(())
+    }
+
+    /// Returns true if the path matches the include and exclude globs
+    pub fn is_match(&self, path: impl AsRef<Path>) -> bool {
+        let path = path.as_ref();
+        let relative = std::env::current_dir()
+            .ok()
+            .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+            .unwrap_or_else(|| path.to_path_buf());
+        let relative = Value::TextBuffer(relative.to_string_lossy().to_string());
+
+        let matches = |glob: &String| Predicate::Matches(glob.to_string()).test(&relative);
+
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+
+    /// Returns the matching paths that have changed since the last call, w/o waiting
+    pub fn try_changed(&mut self) -> BTreeSet<PathBuf> {
+        let mut changed = BTreeSet::default();
+        while let Ok(path) = self.changes.try_recv() {
+            if self.is_match(&path) {
+                changed.insert(path);
+            }
+        }
+        changed
+    }
+
+    /// Waits for a matching path to change, then waits until no paths have changed for debounce, returns all changed paths
+    pub async fn changed(&mut self, debounce: Duration) -> BTreeSet<PathBuf> {
+        let mut changed = BTreeSet::default();
+
+        while changed.is_empty() {
+            match self.changes.recv().await {
+                Some(path) if self.is_match(&path) => {
+                    changed.insert(path);
+                }
+                Some(_) => continue,
+                // The sender is owned by self, so the channel doesn't close
+                None => return changed,
+            }
+        }
+
+        while let Ok(Some(path)) = tokio::time::timeout(debounce, self.changes.recv()).await {
+            if self.is_match(&path) {
+                changed.insert(path);
+            }
+        }
+
+        changed
+    }
+
+    fn poll_watcher(sender: UnboundedSender<PathBuf>, poll_interval: Duration) -> notify::Result<Box<dyn Watcher + Send>> {
+        let watcher = PollWatcher::new(
+            Self::handler(sender),
+            Config::default().with_poll_interval(poll_interval),
+        )?;
+        Ok(Box::new(watcher))
+    }
+
+    fn handler(sender: UnboundedSender<PathBuf>) -> impl Fn(notify::Result<notify::Event>) + Send + 'static {
+        move |result: notify::Result<notify::Event>| match result {
+            Ok(notify::Event { kind, paths, .. }) if !kind.is_access() => {
+                for path in paths {
+                    sender.send(path).ok();
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                event!(Level::WARN, "error watching files, {err}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_watch() {
+    let dir = crate::testing::TempDir::new("watch");
+
+    let mut graph = AttributeGraph::from(0);
+    graph
+        .with_text("watch_path", dir.path().to_str().expect("path"))
+        .with_text("include", "*.rs")
+        .with_bool("poll", true)
+        .with_int("poll_interval_ms", 10);
+
+    let mut watcher = Watch::watcher(&graph).expect("watching");
+    assert!(watcher.is_polling());
+    assert!(watcher.is_match("src/lib.rs"));
+    assert!(!watcher.is_match("README.md"));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let changed = runtime.block_on(async {
+        // The first poll records the initial state of the dir
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        std::fs::write(dir.join("lib.rs"), "fn main() {}").expect("written");
+        std::fs::write(dir.join("notes.md"), "# notes").expect("written");
+
+        tokio::time::timeout(Duration::from_secs(5), watcher.changed(Duration::from_millis(50))).await
+    });
+
+    let changed = changed.expect("changed before timeout");
+    assert_eq!(changed.len(), 1);
+    assert!(changed.iter().all(|p| p.ends_with("lib.rs")));
+
+    // The watcher for an entity is kept until the watch attributes change, or until the entity is deleted
+    use specs::{Builder, WorldExt};
+    let mut world = specs::World::new();
+    let watchers = FileWatchers::default();
+    let mut tc = ThunkContext::from(graph);
+    tc.set_file_watchers(watchers.clone());
+    tc.entity = Some(world.create_entity().build());
+    let watcher = Watch::shared_watcher(&tc).expect("watching");
+    assert!(Arc::ptr_eq(&watcher, &Watch::shared_watcher(&tc).expect("watching")));
+    tc.as_mut().with_int("poll_interval_ms", 20);
+    assert!(!Arc::ptr_eq(&watcher, &Watch::shared_watcher(&tc).expect("watching")));
+
+    // Watchers are kept per world, so an entity w/ the same id in another world gets its own watcher
+    let mut other = tc.clone();
+    other.set_file_watchers(FileWatchers::default());
+    let watcher = Watch::shared_watcher(&tc).expect("watching");
+    assert!(!Arc::ptr_eq(&watcher, &Watch::shared_watcher(&other).expect("watching")));
+
+    assert_eq!(watchers.len(), 1);
+    world.delete_entity(tc.entity.expect("entity")).expect("deleted");
+    world.maintain();
+    watchers.maintain(&world.entities());
+    assert!(watchers.is_empty());
+}
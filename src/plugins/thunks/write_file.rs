@@ -1,10 +1,8 @@
-use std::path::PathBuf;
-
 use crate::plugins::*;
 use specs::storage::DenseVecStorage;
 use specs::Component;
 
-use super::{CancelToken, ThunkContext};
+use super::{safe_path, CancelToken, ThunkContext};
 
 #[derive(Component, Default)]
 #[storage(DenseVecStorage)]
@@ -30,10 +28,26 @@ impl Plugin<ThunkContext> for WriteFile {
                     if let Some(work_dir) = tc.as_ref().find_text("work_dir") {
                         if let Some(file_name) = file_block.find_text("file_name") {
                             if let Some(content) = file_block.find_binary("content") {
-                                let path = PathBuf::from(&work_dir);
+                                // file_name can come from an archive entry, so it can't resolve outside of work_dir
+                                let path = match safe_path(&work_dir, &file_name) {
+                                    Some(path) => path,
+                                    None => {
+                                        tc.update_status_only(format!(
+                                            "# refusing to write {file_name} outside of work_dir"
+                                        ))
+                                        .await;
+                                        tc.error(|a| {
+                                            a.add_text_attr("error", format!("{file_name} is outside of work_dir"));
+                                        });
+                                        continue;
+                                    }
+                                };
                                 tokio::fs::create_dir_all(&work_dir).await.ok();
 
-                                let path = path.join(file_name);
+                                // file_name can be a nested path, i.e. entries from `extract`
+                                if let Some(parent) = path.parent() {
+                                    tokio::fs::create_dir_all(parent).await.ok();
+                                }
 
                                 match tokio::fs::write(&path, content).await {
                                     Ok(_) => {
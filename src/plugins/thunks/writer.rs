@@ -0,0 +1,257 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::{event, Level};
+
+use crate::state::Redactor;
+
+use super::ThunkContext;
+
+/// Context writer implements AsyncWrite for a thunk context,
+///
+/// Each line written becomes a status update for the context's entity, w/ the progress last set by `set_progress`. A line can start w/ an
+/// optional level tag, i.e. `[warn] disk is almost full`, which also sets the level of the tracing event
+/// that is emitted for the line. Lines w/o a tag are logged at INFO.
+///
+/// Lines are redacted w/ the context's sensitive values before they leave the writer.
+///
+/// ```ignore
+/// let mut log = tc.writer();
+/// log.writeln("# event received").await.ok();
+/// log.write_all(b"[warn] partial line,").await.ok();
+/// log.write_all(b" finished\n").await.ok();
+/// log.flush().await.ok();
+/// ```
+///
+/// Caveat: Lines are sent as the writer is polled, call `flush` or `shutdown` to make sure all lines have been
+/// sent. If the writer is dropped, any unsent lines are sent w/o waiting for capacity on the channel.
+///
+pub struct ContextWriter {
+    /// Context that owns this writer
+    context: ThunkContext,
+    /// Redacts the sensitive values of the context, computed once when the writer is created
+    redactor: Redactor,
+    /// Current line being written
+    line: Vec<u8>,
+    /// Progress that is sent w/ each line
+    progress: f32,
+    /// Lines that are waiting to be sent, w/ the progress at the time the line was written
+    pending: VecDeque<(f32, String)>,
+    /// Line that is currently being sent
+    sending: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+}
+
+impl ContextWriter {
+    /// Returns a new writer for the context
+    pub fn new(context: ThunkContext) -> Self {
+        Self {
+            redactor: (*context.redactor()).clone(),
+            context,
+            line: vec![],
+            progress: 0.0,
+            pending: VecDeque::default(),
+            sending: None,
+        }
+    }
+
+    /// Returns the context this writer was created from
+    pub fn context(&self) -> &ThunkContext {
+        &self.context
+    }
+
+    /// Returns the redactor for the sensitive values of the context this writer was created from
+    pub fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+
+    /// Writes a line, and waits for it to be sent
+    pub async fn writeln(&mut self, line: impl AsRef<str>) -> io::Result<()> {
+        self.write_all(format!("{}\n", line.as_ref()).as_bytes())
+            .await?;
+        self.flush().await
+    }
+
+    /// Writes bytes as lines, ending the last line if it is incomplete, and waits for the lines to be sent
+    pub async fn write_lines(&mut self, bytes: impl AsRef<[u8]>) -> io::Result<()> {
+        let bytes = bytes.as_ref();
+        self.write_all(bytes).await?;
+
+        if !bytes.is_empty() && !bytes.ends_with(b"\n") {
+            self.write_all(b"\n").await?;
+        }
+        self.flush().await
+    }
+
+    /// Sets the progress that is sent w/ lines written after this call
+    pub fn set_progress(&mut self, progress: f32) {
+        self.progress = progress;
+    }
+
+    /// Parses an optional level tag from the start of a line,
+    /// returns the level and the remaining message
+    pub fn parse_level(line: &str) -> (Level, &str) {
+        let trimmed = line.trim_start();
+        if let Some((tag, message)) = trimmed
+            .strip_prefix('[')
+            .and_then(|t| t.split_once(']'))
+        {
+            let level = match tag.to_lowercase().as_str() {
+                "trace" => Some(Level::TRACE),
+                "debug" => Some(Level::DEBUG),
+                "info" => Some(Level::INFO),
+                "warn" | "warning" => Some(Level::WARN),
+                "error" => Some(Level::ERROR),
+                _ => None,
+            };
+
+            if let Some(level) = level {
+                return (level, message.trim_start());
+            }
+        }
+
+        (Level::INFO, line)
+    }
+
+    /// Takes the current line, and queues it to be sent
+    fn queue_line(&mut self) {
+        // Redacted before the line is decoded, so that sensitive values are also masked in non-utf8 output
+        let line = self.redactor.redact_bytes(std::mem::take(&mut self.line));
+        let line = String::from_utf8_lossy(&line)
+            .trim_end_matches('\r')
+            .to_string();
+        let block_name = &self.context.block.block_name;
+        match Self::parse_level(&line) {
+            (Level::TRACE, message) => event!(Level::TRACE, "{block_name}\t{message}"),
+            (Level::DEBUG, message) => event!(Level::DEBUG, "{block_name}\t{message}"),
+            (Level::INFO, message) => event!(Level::INFO, "{block_name}\t{message}"),
+            (Level::WARN, message) => event!(Level::WARN, "{block_name}\t{message}"),
+            (_, message) => event!(Level::ERROR, "{block_name}\t{message}"),
+        }
+
+        self.pending.push_back((self.progress, line));
+    }
+
+    /// Polls the line currently being sent, and starts sending the next pending line until
+    /// there are no more pending lines
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if let Some(sending) = self.sending.as_mut() {
+                match sending.as_mut().poll(cx) {
+                    Poll::Ready(_) => {
+                        self.sending = None;
+                    }
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            match self.pending.pop_front() {
+                Some((progress, line)) => {
+                    if let ThunkContext {
+                        status_updates: Some(status_updates),
+                        entity: Some(entity),
+                        ..
+                    } = &self.context
+                    {
+                        let status_updates = status_updates.clone();
+                        let entity = *entity;
+                        self.sending = Some(Box::pin(async move {
+                            status_updates.send((entity, progress, line)).await.ok();
+                        }));
+                    }
+                }
+                None => return Poll::Ready(()),
+            }
+        }
+    }
+}
+
+impl AsyncWrite for ContextWriter {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let writer = self.get_mut();
+
+        // Wait for previous lines to be sent before accepting more
+        if writer.poll_send(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        for byte in buf {
+            if *byte == b'\n' {
+                writer.queue_line();
+            } else {
+                writer.line.push(*byte);
+            }
+        }
+
+        // Start sending any lines that were just completed
+        let _ = writer.poll_send(cx);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        self.get_mut().poll_send(cx).map(Ok)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let writer = self.get_mut();
+        if !writer.line.is_empty() {
+            writer.queue_line();
+        }
+
+        writer.poll_send(cx).map(Ok)
+    }
+}
+
+impl Drop for ContextWriter {
+    fn drop(&mut self) {
+        if !self.line.is_empty() {
+            self.queue_line();
+        }
+
+        if let ThunkContext {
+            status_updates: Some(status_updates),
+            entity: Some(entity),
+            ..
+        } = &self.context
+        {
+            for (progress, line) in self.pending.drain(..) {
+                status_updates.try_send((*entity, progress, line)).ok();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_context_writer() {
+    use specs::{World, WorldExt, Builder};
+
+    let world = World::new();
+    let entity = world.create_entity().build();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+
+    let mut tc = ThunkContext::default();
+    tc.entity = Some(entity);
+    tc.status_updates = Some(tx);
+
+    runtime.block_on(async {
+        let mut writer = tc.writer();
+        writer.writeln("hello").await.expect("written");
+        writer.write_all(b"[warn] world").await.expect("written");
+        writer.shutdown().await.expect("shutdown");
+    });
+
+    assert_eq!(rx.try_recv().ok(), Some((entity, 0.0, "hello".to_string())));
+    assert_eq!(rx.try_recv().ok(), Some((entity, 0.0, "[warn] world".to_string())));
+    assert_eq!(
+        ContextWriter::parse_level("[warn] world"),
+        (Level::WARN, "world")
+    );
+}
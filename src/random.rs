@@ -0,0 +1,60 @@
+use std::sync::{Arc, Mutex};
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+/// Random resource that plugins read randomness from,
+///
+/// By default randomness comes from the thread rng. A seeded source returns the same sequence of values for the same seed, so that
+/// runs that use randomness, i.e. `unique_title`, are reproducible,
+///
+/// ```
+/// use lifec::Random;
+///
+/// let a = Random::seeded(7);
+/// let b = Random::seeded(7);
+/// assert_eq!(a.unique_title("timer"), b.unique_title("timer"));
+/// ```
+///
+/// Caveat: Keys and nonces for secrets, i.e. in the `Vault`, never come from this resource.
+///
+#[derive(Clone, Default)]
+pub struct Random(Option<Arc<Mutex<StdRng>>>);
+
+impl Random {
+    /// Returns randomness from the thread rng
+    pub fn system() -> Self {
+        Self(None)
+    }
+
+    /// Returns a seeded source of randomness
+    pub fn seeded(seed: u64) -> Self {
+        Self(Some(Arc::new(Mutex::new(StdRng::seed_from_u64(seed)))))
+    }
+
+    /// Returns true if this source is seeded
+    pub fn is_seeded(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Returns the next random u64
+    pub fn next_u64(&self) -> u64 {
+        match &self.0 {
+            Some(rng) => rng.lock().expect("not poisoned").next_u64(),
+            None => rand::thread_rng().next_u64(),
+        }
+    }
+
+    /// Fills bytes w/ random data
+    pub fn fill_bytes(&self, bytes: &mut [u8]) {
+        match &self.0 {
+            Some(rng) => rng.lock().expect("not poisoned").fill_bytes(bytes),
+            None => rand::thread_rng().fill_bytes(bytes),
+        }
+    }
+
+    /// Returns title w/ a random suffix
+    pub fn unique_title(&self, title: impl AsRef<str>) -> String {
+        format!("{}_{:#04x}", title.as_ref(), self.next_u64() as u16)
+    }
+}
@@ -17,6 +17,13 @@ where
     
     let mut dispatcher = dipatch_builder.build();
     dispatcher.setup(&mut world);
+
+    // Report blocks that fail schema validation, events created from these blocks record the errors instead of calling the plugin
+    for (block_name, errors) in extension.as_ref().project().validate() {
+        for error in errors {
+            event!(Level::ERROR, "{block_name} {}: {error}", error.name());
+        }
+    }
     
     for sequence_name in call_sequence {
         if let Some(start) = extension.as_ref().create_engine::<Call>(&world, sequence_name.to_string()) {
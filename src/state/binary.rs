@@ -0,0 +1,537 @@
+use std::collections::{BTreeMap, HashMap};
+use std::str::from_utf8;
+
+use atlier::system::{Attribute, Value};
+use tracing::{event, Level};
+
+use super::AttributeGraph;
+use crate::RuntimeState;
+
+/// Header that starts every binary encoded graph
+pub const BINARY_MAGIC: &[u8; 4] = b"LFCG";
+
+/// Current version of the binary format
+///
+/// When the format changes, bump this version and add a decoder for the previous version to `AttributeGraph::from_bytes`,
+/// so that graphs saved w/ an older version can still be loaded.
+///
+pub const BINARY_VERSION: u16 = 1;
+
+/// Errors returned when decoding a binary encoded graph
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryErrors {
+    /// The bytes ended before the graph was decoded
+    UnexpectedEnd,
+    /// The bytes are not a binary encoded graph, or a legacy text encoded graph
+    InvalidHeader,
+    /// The graph was encoded w/ a newer version of the format
+    UnsupportedVersion(u16),
+    /// A value has an unknown type tag
+    InvalidTag(u8),
+    /// A string is not valid utf8
+    InvalidUtf8,
+    /// An attribute refers to a name that isn't in the name table
+    InvalidName(usize),
+}
+
+/// Encoding used when a graph leaves the current process, or is handed off between events
+///
+/// Set w/ the `encoding` symbol of a graph, i.e. in runmd,
+///
+/// ```runmd
+/// add encoding .symbol binary
+/// ```
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// Text encoding, runmd for the `previous` hand-off, ron for persistence, and json for network messages
+    #[default]
+    Text,
+    /// Versioned binary encoding, see `AttributeGraph::to_bytes`
+    Binary,
+}
+
+impl Encoding {
+    /// Returns the encoding selected by a graph, text if the graph doesn't select an encoding
+    pub fn find(graph: &AttributeGraph) -> Self {
+        match graph.find_symbol("encoding").as_deref() {
+            Some("binary") => Encoding::Binary,
+            Some("text") | None => Encoding::Text,
+            Some(unknown) => {
+                event!(Level::WARN, "unknown encoding {unknown}, using text");
+                Encoding::Text
+            }
+        }
+    }
+}
+
+impl AttributeGraph {
+    /// Returns the graph encoded in the versioned binary format,
+    ///
+    /// The format is,
+    ///
+    /// ```text
+    /// magic "LFCG" | version u16 | entity u32 | names | attributes
+    /// ```
+    ///
+    /// Attribute names are interned in a table of length-prefixed strings, and each attribute refers to it's name by index.
+    /// Lengths are LEB128 varints, numbers are little-endian, and binary values are written as raw length-prefixed bytes.
+    ///
+    /// Caveat: Sensitive values are encoded as is, use `redacted()` first if the bytes leave the process.
+    ///
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut names = Vec::<&str>::default();
+        let mut name_index = HashMap::<&str, usize>::default();
+        for attr in self.index.values() {
+            for name in std::iter::once(attr.name()).chain(attr.transient().map(|(n, _)| n.as_str())) {
+                name_index.entry(name).or_insert_with(|| {
+                    names.push(name);
+                    names.len() - 1
+                });
+            }
+        }
+
+        let mut attributes = Writer::default();
+        attributes.varint(self.index.len() as u64);
+        for (key, attr) in self.index.iter() {
+            attributes.varint(attr.id() as u64);
+            attributes.varint(name_index[attr.name()] as u64);
+
+            // Most keys are the attribute's string form, only keys that differ are written
+            if *key == attr.to_string() {
+                attributes.u8(0);
+            } else {
+                attributes.u8(1);
+                attributes.str(key);
+            }
+
+            attributes.value(attr.value());
+
+            match attr.transient() {
+                Some((name, value)) => {
+                    attributes.u8(1);
+                    attributes.varint(name_index[name.as_str()] as u64);
+                    attributes.value(value);
+                }
+                None => attributes.u8(0),
+            }
+        }
+
+        let mut bytes = Writer::default();
+        bytes.0.extend_from_slice(BINARY_MAGIC);
+        bytes.0.extend_from_slice(&BINARY_VERSION.to_le_bytes());
+        bytes.0.extend_from_slice(&self.entity.to_le_bytes());
+        bytes.varint(names.len() as u64);
+        for name in names {
+            bytes.str(name);
+        }
+        bytes.0.extend(attributes.0);
+        bytes.0
+    }
+
+    /// Decodes a graph from bytes,
+    ///
+    /// Bytes w/o the binary header are decoded as the legacy text formats written by `save`, so that existing files can be migrated
+    /// by loading them w/ this function and saving them w/ `to_bytes`.
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BinaryErrors> {
+        if !Self::is_binary(bytes) {
+            return Self::from_legacy_text(bytes);
+        }
+
+        let mut reader = Reader {
+            bytes,
+            position: BINARY_MAGIC.len(),
+        };
+
+        match reader.u16()? {
+            1 => Self::decode_v1(&mut reader),
+            version => Err(BinaryErrors::UnsupportedVersion(version)),
+        }
+    }
+
+    /// Returns true if bytes start w/ the binary format header
+    pub fn is_binary(bytes: &[u8]) -> bool {
+        bytes.starts_with(BINARY_MAGIC)
+    }
+
+    /// Returns the graph encoded for persistence, sensitive values are not saved
+    pub fn save_with(&self, encoding: Encoding) -> Option<Vec<u8>> {
+        match encoding {
+            Encoding::Text => self.save_ron().map(|s| s.into_bytes()),
+            Encoding::Binary => Some(self.redacted().to_bytes()),
+        }
+    }
+
+    /// Returns the binary encoding of the graph as base64, used by `RuntimeState::save` when a graph selects the binary encoding
+    pub fn save_base64(&self) -> String {
+        base64::encode(self.redacted().to_bytes())
+    }
+
+    /// Decodes a graph saved w/ `save_base64`, returns None if the text isn't a base64 encoded binary graph
+    pub fn load_base64(text: impl AsRef<str>) -> Option<Self> {
+        base64::decode(text.as_ref().trim())
+            .ok()
+            .filter(|bytes| Self::is_binary(bytes))
+            .and_then(|bytes| Self::from_bytes(&bytes).ok())
+    }
+
+    /// Adds the blocks of other to this graph, as if other's transpiled blocks were dispatched w/ `batch_mut`
+    ///
+    pub fn import_blocks(&mut self, other: &AttributeGraph) {
+        let mut blocks = BTreeMap::<u32, Vec<&Attribute>>::default();
+        for attr in other.iter_attributes().filter(|a| a.id() != other.entity()) {
+            blocks.entry(attr.id()).or_default().push(attr);
+        }
+
+        for (_, attrs) in blocks {
+            let find_text = |name: &str| {
+                attrs.iter().find(|a| a.name() == name).and_then(|a| match a.value() {
+                    Value::TextBuffer(text) => Some(text.to_string()),
+                    _ => None,
+                })
+            };
+
+            let (block_name, block_symbol) = match (find_text("block_name"), find_text("block_symbol")) {
+                (Some(block_name), Some(block_symbol)) => (block_name, block_symbol),
+                _ => continue,
+            };
+
+            self.start_block_mode(&block_name, &block_symbol);
+            for attr in attrs {
+                if attr.name().starts_with("block_") || attr.name().ends_with("::secret") {
+                    continue;
+                }
+
+                match (attr.value(), attr.transient()) {
+                    (Value::Symbol(symbol), _) if symbol.ends_with("::block") => continue,
+                    (_, None) => {
+                        self.with(attr.name(), attr.value().clone());
+                    }
+                    (Value::Symbol(symbol), Some((_, value))) => {
+                        let symbol = symbol.trim_end_matches("::");
+                        let name = attr.name().trim_end_matches(&format!("::{symbol}"));
+                        self.define(name, symbol).edit_as(value.clone());
+                    }
+                    // i.e. an attribute being edited, the transient is kept as is
+                    (_, Some(_)) => {
+                        self.copy_attribute(attr);
+                    }
+                }
+            }
+            self.end_block_mode();
+        }
+    }
+
+    fn decode_v1(reader: &mut Reader) -> Result<Self, BinaryErrors> {
+        let entity = reader.u32()?;
+
+        let name_count = reader.varint()? as usize;
+        let mut names = Vec::with_capacity(name_count.min(reader.remaining()));
+        for _ in 0..name_count {
+            names.push(reader.str()?);
+        }
+        let name = |index: usize| {
+            names
+                .get(index)
+                .cloned()
+                .ok_or(BinaryErrors::InvalidName(index))
+        };
+
+        let mut graph = AttributeGraph::from(entity);
+        let attr_count = reader.varint()? as usize;
+        for _ in 0..attr_count {
+            let id = reader.varint()? as u32;
+            let attr_name = name(reader.varint()? as usize)?;
+            let key = match reader.u8()? {
+                0 => None,
+                _ => Some(reader.str()?),
+            };
+
+            let mut attr = Attribute::new(id, attr_name, reader.value()?);
+            if reader.u8()? != 0 {
+                let transient_name = name(reader.varint()? as usize)?;
+                attr.edit((transient_name, reader.value()?));
+            }
+
+            graph
+                .index
+                .insert(key.unwrap_or_else(|| attr.to_string()), attr);
+        }
+
+        Ok(graph)
+    }
+
+    fn from_legacy_text(bytes: &[u8]) -> Result<Self, BinaryErrors> {
+        let text = from_utf8(bytes).map_err(|_| BinaryErrors::InvalidUtf8)?;
+
+        ron::from_str::<AttributeGraph>(text)
+            .ok()
+            .or_else(|| serde_json::from_str::<AttributeGraph>(text).ok())
+            .ok_or(BinaryErrors::InvalidHeader)
+    }
+}
+
+/// Writer for the binary format, also used for the canonical encoding hashed by `AttributeGraph::content_hash`
+#[derive(Default)]
+pub(super) struct Writer(pub(super) Vec<u8>);
+
+impl Writer {
+    pub(super) fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub(super) fn varint(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.0.push(byte);
+                break;
+            }
+            self.0.push(byte | 0x80);
+        }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    pub(super) fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub(super) fn value(&mut self, value: &Value) {
+        match value {
+            Value::Empty => self.u8(0),
+            Value::Bool(b) => {
+                self.u8(1);
+                self.u8(*b as u8);
+            }
+            Value::TextBuffer(text) => {
+                self.u8(2);
+                self.str(text);
+            }
+            Value::Int(i) => {
+                self.u8(3);
+                self.i32(*i);
+            }
+            Value::IntPair(a, b) => {
+                self.u8(4);
+                self.i32(*a);
+                self.i32(*b);
+            }
+            Value::IntRange(a, b, c) => {
+                self.u8(5);
+                self.i32(*a);
+                self.i32(*b);
+                self.i32(*c);
+            }
+            Value::Float(f) => {
+                self.u8(6);
+                self.f32(*f);
+            }
+            Value::FloatPair(a, b) => {
+                self.u8(7);
+                self.f32(*a);
+                self.f32(*b);
+            }
+            Value::FloatRange(a, b, c) => {
+                self.u8(8);
+                self.f32(*a);
+                self.f32(*b);
+                self.f32(*c);
+            }
+            Value::BinaryVector(bin) => {
+                self.u8(9);
+                self.bytes(bin);
+            }
+            Value::Reference(r) => {
+                self.u8(10);
+                self.0.extend_from_slice(&r.to_le_bytes());
+            }
+            Value::Symbol(symbol) => {
+                self.u8(11);
+                self.str(symbol);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BinaryErrors> {
+        if len > self.remaining() {
+            return Err(BinaryErrors::UnexpectedEnd);
+        }
+
+        let taken = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], BinaryErrors> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn u8(&mut self) -> Result<u8, BinaryErrors> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, BinaryErrors> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, BinaryErrors> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, BinaryErrors> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Result<f32, BinaryErrors> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    fn varint(&mut self) -> Result<u64, BinaryErrors> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(BinaryErrors::InvalidHeader)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], BinaryErrors> {
+        let len = self.varint()? as usize;
+        self.take(len)
+    }
+
+    fn str(&mut self) -> Result<String, BinaryErrors> {
+        from_utf8(self.bytes()?)
+            .map(|s| s.to_string())
+            .map_err(|_| BinaryErrors::InvalidUtf8)
+    }
+
+    fn value(&mut self) -> Result<Value, BinaryErrors> {
+        let value = match self.u8()? {
+            0 => Value::Empty,
+            1 => Value::Bool(self.u8()? != 0),
+            2 => Value::TextBuffer(self.str()?),
+            3 => Value::Int(self.i32()?),
+            4 => Value::IntPair(self.i32()?, self.i32()?),
+            5 => Value::IntRange(self.i32()?, self.i32()?, self.i32()?),
+            6 => Value::Float(self.f32()?),
+            7 => Value::FloatPair(self.f32()?, self.f32()?),
+            8 => Value::FloatRange(self.f32()?, self.f32()?, self.f32()?),
+            9 => Value::BinaryVector(self.bytes()?.to_vec()),
+            10 => Value::Reference(u64::from_le_bytes(self.array()?)),
+            11 => Value::Symbol(self.str()?),
+            tag => return Err(BinaryErrors::InvalidTag(tag)),
+        };
+        Ok(value)
+    }
+}
+
+#[test]
+fn test_binary_format() {
+    use crate::RuntimeDispatcher;
+
+    let mut graph = AttributeGraph::from(0);
+    graph
+        .batch_mut(
+            r#"
+add name .text lifec
+add encoding .symbol binary
+``` test process
+add command .text echo hello
+add code .int 0
+add size .int2 1, 2
+add scale .float3 1.0, 2.0, 3.0
+add stdout .bin aGVsbG8=
+define node_title test .text hello
+```
+"#,
+        )
+        .expect("valid");
+
+    let bytes = graph.to_bytes();
+    assert!(AttributeGraph::is_binary(&bytes));
+    assert_eq!(AttributeGraph::from_bytes(&bytes), Ok(graph.clone()));
+    assert_eq!(Encoding::find(&graph), Encoding::Binary);
+
+    // Truncated or newer versions are errors
+    assert_eq!(
+        AttributeGraph::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(BinaryErrors::UnexpectedEnd)
+    );
+    let mut newer = bytes.clone();
+    newer[4..6].copy_from_slice(&(BINARY_VERSION + 1).to_le_bytes());
+    assert_eq!(
+        AttributeGraph::from_bytes(&newer),
+        Err(BinaryErrors::UnsupportedVersion(BINARY_VERSION + 1))
+    );
+
+    // Legacy text is migrated
+    let saved = graph.save_with(Encoding::Text).expect("saved");
+    assert_eq!(AttributeGraph::from_bytes(&saved), Ok(graph.clone()));
+
+    // Since the graph selects binary, save writes the binary encoding
+    let saved = graph.save().expect("saved");
+    assert!(ron::from_str::<AttributeGraph>(&saved).is_err());
+    assert_eq!(AttributeGraph::default().load(saved), graph.clone());
+
+    // Blocks can be handed off in binary, like transpiled runmd
+    let mut next = AttributeGraph::from(0);
+    next.import_blocks(&graph);
+    let mut expected = AttributeGraph::from(0);
+    expected
+        .batch_mut(crate::plugins::Project::from(graph.clone()).transpile_blocks().expect("transpiles"))
+        .expect("valid");
+    assert_eq!(next.find_blocks("process").len(), 1);
+    assert_eq!(
+        next.find_blocks("process")[0].find_text("command"),
+        expected.find_blocks("process")[0].find_text("command")
+    );
+
+    // Transients of other values are kept
+    let mut editing = graph.clone();
+    editing.start_block_mode("test", "process");
+    editing
+        .find_attr_mut("code")
+        .expect("exists")
+        .edit(("code".to_string(), Value::Int(1)));
+    editing.end_block_mode();
+    let mut next = AttributeGraph::from(0);
+    next.import_blocks(&editing);
+    assert_eq!(
+        next.find_blocks("process")[0]
+            .find_attr("code")
+            .and_then(|a| a.transient())
+            .map(|(_, v)| v.clone()),
+        Some(Value::Int(1))
+    );
+}
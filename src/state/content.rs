@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use atlier::system::Value;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::binary::Writer;
+use super::AttributeGraph;
+
+/// SHA-256 hash over the canonical encoding of a graph
+///
+/// Unlike `AttributeGraph::hash_code`, this hash is stable across processes and rust versions, so it can be used as an identity,
+/// i.e. for caching results or addressing a block from another process. Display and FromStr use lowercase hex.
+///
+#[derive(Debug, Default, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+    /// Returns the bytes of the hash
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Returns the first 8 bytes of the hash as a u64, for places that only have room for a u64
+    pub fn to_u64(&self) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&self.0[..8]);
+        u64::from_le_bytes(bytes)
+    }
+}
+
+impl From<[u8; 32]> for ContentHash {
+    fn from(hash: [u8; 32]) -> Self {
+        Self(hash)
+    }
+}
+
+impl Display for ContentHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for byte in self.0.iter() {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ContentHash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(format!("expected 64 hex characters, found {s}"));
+        }
+
+        let mut hash = [0; 32];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|e| format!("{e}"))?;
+        }
+        Ok(Self(hash))
+    }
+}
+
+impl AttributeGraph {
+    /// Returns a SHA-256 hash of the canonical encoding of this graph, including the entity id of each attribute
+    ///
+    /// The canonical encoding does not depend on the order attributes were added, or on the keys used to index them.
+    ///
+    pub fn content_hash(&self) -> ContentHash {
+        self.canonical_hash(true)
+    }
+
+    /// Returns a SHA-256 hash of the canonical encoding of this graph, w/o entity ids
+    ///
+    /// Attributes are identified by the block they belong to instead of the entity id, so the same runmd loaded in different
+    /// processes, or at different entity ids, has the same hash. Bookkeeping attributes that store entity ids, i.e. block definitions,
+    /// are not included.
+    ///
+    pub fn content_hash_without_ids(&self) -> ContentHash {
+        self.canonical_hash(false)
+    }
+
+    fn canonical_hash(&self, with_ids: bool) -> ContentHash {
+        let identities = if with_ids {
+            BTreeMap::default()
+        } else {
+            self.block_identities()
+        };
+
+        let mut encoded = self
+            .iter_attributes()
+            .filter(|a| with_ids || !Self::is_id_bookkeeping(a.name(), a.value()))
+            .map(|attr| {
+                let mut writer = Writer::default();
+                match identities.get(&attr.id()) {
+                    Some(identity) => writer.str(identity),
+                    None => writer.varint(attr.id() as u64),
+                }
+                writer.str(attr.name());
+                writer.value(attr.value());
+                match attr.transient() {
+                    Some((name, value)) => {
+                        writer.u8(1);
+                        writer.str(name);
+                        writer.value(value);
+                    }
+                    None => writer.u8(0),
+                }
+                writer.0
+            })
+            .collect::<Vec<_>>();
+        encoded.sort();
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"lifec.content.v1");
+        if with_ids {
+            hasher.update(self.entity.to_le_bytes());
+        }
+        for attr in encoded {
+            hasher.update((attr.len() as u64).to_le_bytes());
+            hasher.update(attr);
+        }
+
+        let mut hash = [0; 32];
+        hash.copy_from_slice(&hasher.finalize());
+        ContentHash(hash)
+    }
+
+    /// Returns an identity for each entity id in this graph that doesn't depend on the id,
+    /// blocks are identified by name and symbol, and other entities by the order of their ids
+    fn block_identities(&self) -> BTreeMap<u32, String> {
+        let mut blocks = BTreeMap::<u32, (Option<&str>, Option<&str>)>::default();
+        for attr in self.iter_attributes() {
+            let (block_name, block_symbol) = blocks.entry(attr.id()).or_default();
+            match (attr.name(), attr.value()) {
+                ("block_name", Value::TextBuffer(name)) => *block_name = Some(name.as_str()),
+                ("block_symbol", Value::TextBuffer(symbol)) => *block_symbol = Some(symbol.as_str()),
+                _ => {}
+            }
+        }
+
+        let mut unnamed = 0;
+        blocks
+            .into_iter()
+            .map(|(id, block)| {
+                let identity = match block {
+                    _ if id == self.entity => "root".to_string(),
+                    (Some(name), Some(symbol)) => format!("block {name} {symbol}"),
+                    _ => {
+                        unnamed += 1;
+                        format!("entity {unnamed}")
+                    }
+                };
+                (id, identity)
+            })
+            .collect()
+    }
+
+    /// Returns true if the attribute only exists to store an entity id
+    fn is_id_bookkeeping(name: &str, value: &Value) -> bool {
+        name == "parent::block"
+            || name == "last::block"
+            || matches!(value, Value::Symbol(symbol) if symbol.ends_with("::block"))
+    }
+}
+
+#[test]
+fn test_content_hash() {
+    use crate::RuntimeDispatcher;
+
+    let runmd = r#"
+add name .text lifec
+``` test process
+add command .text echo hello
+```
+"#;
+
+    let mut a = AttributeGraph::from(0);
+    a.batch_mut(runmd).expect("valid");
+    let mut b = AttributeGraph::from(10);
+    b.batch_mut(runmd).expect("valid");
+
+    assert_ne!(a.content_hash(), b.content_hash());
+    assert_eq!(a.content_hash_without_ids(), b.content_hash_without_ids());
+    assert_eq!(a.content_hash(), a.clone().content_hash());
+
+    let mut c = a.clone();
+    c.batch_mut("add name .text lifec2").expect("valid");
+    assert_ne!(a.content_hash_without_ids(), c.content_hash_without_ids());
+
+    let hash = a.content_hash();
+    assert_eq!(hash.to_string().len(), 64);
+    assert_eq!(hash.to_string().parse::<ContentHash>(), Ok(hash));
+}
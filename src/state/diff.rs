@@ -0,0 +1,476 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Write};
+
+use atlier::system::{Attribute, Value};
+use tracing::{event, Level};
+
+use super::{AttributeGraph, REDACTED};
+
+/// A change to a single attribute between two graphs
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeChange {
+    /// The attribute only exists in the next graph
+    Added(Attribute),
+    /// The attribute only exists in the previous graph
+    Removed(Attribute),
+    /// The attribute exists in both graphs, w/ a different stable or transient value
+    Changed {
+        previous: Attribute,
+        next: Attribute,
+    },
+}
+
+impl AttributeChange {
+    /// Returns the attribute as it was before the change, if it existed
+    pub fn previous(&self) -> Option<&Attribute> {
+        match self {
+            AttributeChange::Added(_) => None,
+            AttributeChange::Removed(previous) | AttributeChange::Changed { previous, .. } => {
+                Some(previous)
+            }
+        }
+    }
+
+    /// Returns the attribute as it is after the change, if it still exists
+    pub fn next(&self) -> Option<&Attribute> {
+        match self {
+            AttributeChange::Removed(_) => None,
+            AttributeChange::Added(next) | AttributeChange::Changed { next, .. } => Some(next),
+        }
+    }
+
+    /// Returns the attribute that changed, after the change if it still exists
+    pub fn attribute(&self) -> &Attribute {
+        self.next()
+            .or(self.previous())
+            .expect("a change has at least one attribute")
+    }
+
+    /// Returns the entity id of the attribute
+    pub fn id(&self) -> u32 {
+        self.attribute().id()
+    }
+
+    /// Returns the name of the attribute
+    pub fn name(&self) -> &str {
+        self.attribute().name()
+    }
+
+    /// Returns true if the stable value changed
+    pub fn is_stable_change(&self) -> bool {
+        self.previous().map(|a| a.value()) != self.next().map(|a| a.value())
+    }
+
+    /// Returns true if the transient value changed
+    pub fn is_transient_change(&self) -> bool {
+        self.previous().and_then(|a| a.transient()) != self.next().and_then(|a| a.transient())
+    }
+}
+
+/// Errors returned when a diff can't be written as a patch
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchErrors {
+    /// A text value has more than one line, runmd text values are a single line
+    MultilineText(String),
+}
+
+/// Diff between two attribute graphs, returned by `AttributeGraph::diff`
+///
+/// Changes are grouped by the entity id that owns the attribute, which for graphs built from runmd is the id of the block
+/// the attribute was added in.
+///
+/// A diff can be written as a patch of runmd messages, that when applied to the previous graph w/ `batch_mut`,
+/// results in the next graph,
+///
+/// ```ignore
+/// let diff = before.diff(&after);
+/// before.batch_mut(diff.to_patch()?)?;
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GraphDiff {
+    /// Entity id of the graph's root
+    root: u32,
+    /// Changes, ordered by entity id and then attribute name
+    changes: Vec<AttributeChange>,
+    /// (block_name, block_symbol) for each block entity w/ changes
+    blocks: BTreeMap<u32, (String, String)>,
+    /// Names of attributes that are sensitive
+    sensitive: BTreeSet<String>,
+}
+
+impl GraphDiff {
+    /// Returns the diff from previous to next
+    pub fn new(previous: &AttributeGraph, next: &AttributeGraph) -> Self {
+        let mut changes = vec![];
+
+        for (key, previous_attr) in previous.index.iter() {
+            match next.index.get(key) {
+                Some(next_attr) => {
+                    if previous_attr.value() != next_attr.value()
+                        || previous_attr.transient() != next_attr.transient()
+                    {
+                        changes.push(AttributeChange::Changed {
+                            previous: previous_attr.clone(),
+                            next: next_attr.clone(),
+                        });
+                    }
+                }
+                None => changes.push(AttributeChange::Removed(previous_attr.clone())),
+            }
+        }
+
+        for (key, next_attr) in next.index.iter() {
+            if !previous.index.contains_key(key) {
+                changes.push(AttributeChange::Added(next_attr.clone()));
+            }
+        }
+
+        changes.sort_by(|a, b| (a.id(), a.name()).cmp(&(b.id(), b.name())));
+
+        let mut blocks = BTreeMap::default();
+        let mut sensitive = BTreeSet::default();
+        for change in changes.iter() {
+            if next.is_sensitive(change.name()) || previous.is_sensitive(change.name()) {
+                sensitive.insert(change.name().to_string());
+            }
+
+            if change.id() != previous.entity && !blocks.contains_key(&change.id()) {
+                if let Some(block) =
+                    Self::find_block(next, change.id()).or_else(|| Self::find_block(previous, change.id()))
+                {
+                    blocks.insert(change.id(), block);
+                }
+            }
+        }
+
+        Self {
+            root: previous.entity,
+            changes,
+            blocks,
+            sensitive,
+        }
+    }
+
+    /// Returns true if there are no changes
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Returns all changes
+    pub fn changes(&self) -> &[AttributeChange] {
+        &self.changes
+    }
+
+    /// Returns an iterator over attributes that were added
+    pub fn added(&self) -> impl Iterator<Item = &Attribute> {
+        self.changes.iter().filter_map(|c| match c {
+            AttributeChange::Added(added) => Some(added),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over attributes that were removed
+    pub fn removed(&self) -> impl Iterator<Item = &Attribute> {
+        self.changes.iter().filter_map(|c| match c {
+            AttributeChange::Removed(removed) => Some(removed),
+            _ => None,
+        })
+    }
+
+    /// Returns an iterator over (previous, next) of attributes that changed
+    pub fn changed(&self) -> impl Iterator<Item = (&Attribute, &Attribute)> {
+        self.changes.iter().filter_map(|c| match c {
+            AttributeChange::Changed { previous, next } => Some((previous, next)),
+            _ => None,
+        })
+    }
+
+    /// Returns changes grouped by the entity id that owns the attribute
+    pub fn by_block(&self) -> BTreeMap<u32, Vec<&AttributeChange>> {
+        let mut groups = BTreeMap::<u32, Vec<&AttributeChange>>::default();
+        for change in self.changes.iter() {
+            groups.entry(change.id()).or_default().push(change);
+        }
+        groups
+    }
+
+    /// Returns the (block_name, block_symbol) of a block entity w/ changes
+    pub fn block(&self, id: u32) -> Option<(&str, &str)> {
+        self.blocks
+            .get(&id)
+            .map(|(name, symbol)| (name.as_str(), symbol.as_str()))
+    }
+
+    /// Returns the diff as runmd messages, that can be applied to the previous graph w/ `batch_mut`
+    ///
+    /// Caveat: Resolved secrets, sensitive values, block bookkeeping attributes, and references are not included in the patch.
+    /// Returns an error if a changed text value has more than one line.
+    ///
+    pub fn to_patch(&self) -> Result<String, PatchErrors> {
+        let mut patch = String::default();
+
+        for (id, changes) in self.by_block() {
+            let block = self.block(id);
+            if id != self.root {
+                match block {
+                    Some((name, symbol)) => {
+                        writeln!(patch, "``` {name} {symbol}").ok();
+                    }
+                    None => {
+                        event!(Level::WARN, "skipping changes to entity {id}, it is not a block in this graph");
+                        continue;
+                    }
+                }
+            }
+
+            for change in changes.into_iter().filter(|c| Self::is_patchable(c.attribute())) {
+                let name = change.name();
+
+                // Values marked sensitive never leave the graph, only the `sensitive` define itself is written
+                if self.sensitive.contains(name) && !name.ends_with("::sensitive") {
+                    event!(Level::DEBUG, "skipping {name}, it is sensitive");
+                    continue;
+                }
+
+                if let Some(next) = change.next() {
+                    let multiline = |value: &Value| matches!(value, Value::TextBuffer(text) if text.contains('\n'));
+                    if multiline(next.value()) || next.transient().map(|(_, t)| multiline(t)).unwrap_or_default() {
+                        return Err(PatchErrors::MultilineText(name.to_string()));
+                    }
+                }
+
+                match change.next() {
+                    Some(next) => {
+                        // Adding an attribute replaces it, and clears it's transient value
+                        if change.is_stable_change() || change.previous().and_then(|p| p.transient()).is_some() {
+                            match Self::format_value(next.value()) {
+                                Some(value) => {
+                                    writeln!(patch, "add {name} {value}").ok();
+                                }
+                                None => {
+                                    event!(Level::WARN, "skipping {name}, value can't be written as runmd");
+                                    continue;
+                                }
+                            }
+                        }
+
+                        if let Some((transient_name, transient)) = next.transient() {
+                            if let Some(transient) = Self::format_value(transient) {
+                                writeln!(patch, "edit {name} {transient_name} {transient}").ok();
+                            }
+                        }
+                    }
+                    None => {
+                        writeln!(patch, "find_remove {name}").ok();
+                    }
+                }
+            }
+
+            if block.is_some() {
+                writeln!(patch, "```").ok();
+            }
+        }
+
+        Ok(patch)
+    }
+
+    /// Returns true if the change to the attribute can be written as part of a patch
+    fn is_patchable(attribute: &Attribute) -> bool {
+        let name = attribute.name();
+        if name.ends_with("::secret") || name == "parent::block" || name == "last::block" {
+            return false;
+        }
+
+        // Block definitions are added by the block delimitter
+        !matches!(attribute.value(), Value::Symbol(symbol) if symbol.ends_with("::block"))
+    }
+
+    /// Formats a value as a value-type token followed by the value
+    fn format_value(value: &Value) -> Option<String> {
+        match value {
+            Value::Empty => Some(".empty".to_string()),
+            Value::Bool(b) => Some(format!(".bool {b}")),
+            Value::TextBuffer(text) => Some(format!(".text {text}")),
+            Value::Int(i) => Some(format!(".int {i}")),
+            Value::IntPair(a, b) => Some(format!(".int2 {a}, {b}")),
+            Value::IntRange(a, b, c) => Some(format!(".int3 {a}, {b}, {c}")),
+            Value::Float(f) => Some(format!(".float {f}")),
+            Value::FloatPair(a, b) => Some(format!(".float2 {a}, {b}")),
+            Value::FloatRange(a, b, c) => Some(format!(".float3 {a}, {b}, {c}")),
+            Value::BinaryVector(bin) => Some(format!(".bin {}", base64::encode(bin))),
+            Value::Symbol(symbol) if symbol.starts_with("secret::") => {
+                Some(format!(".secret {}", symbol.trim_start_matches("secret::")))
+            }
+            Value::Symbol(symbol) => Some(format!(".symbol {symbol}")),
+            _ => None,
+        }
+    }
+
+    fn find_block(graph: &AttributeGraph, id: u32) -> Option<(String, String)> {
+        let find = |name: &str| {
+            graph
+                .iter_attributes()
+                .find(|a| a.id() == id && a.name() == name)
+                .and_then(|a| match a.value() {
+                    Value::TextBuffer(text) => Some(text.to_string()),
+                    _ => None,
+                })
+        };
+
+        match (find("block_name"), find("block_symbol")) {
+            (Some(name), Some(symbol)) => Some((name, symbol)),
+            _ => None,
+        }
+    }
+
+    fn display_value(&self, name: &str, value: &Value) -> String {
+        if self.sensitive.contains(name) {
+            REDACTED.to_string()
+        } else {
+            format!("{value:?}")
+        }
+    }
+}
+
+impl Display for GraphDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (id, changes) in self.by_block() {
+            match self.block(id) {
+                Some((name, symbol)) => writeln!(f, "{name} {symbol}")?,
+                None => writeln!(f, "{id}")?,
+            }
+
+            for change in changes {
+                let name = change.name();
+                match change {
+                    AttributeChange::Added(next) => {
+                        writeln!(f, "  + {name} {}", self.display_value(name, next.value()))?;
+                    }
+                    AttributeChange::Removed(previous) => {
+                        writeln!(f, "  - {name} {}", self.display_value(name, previous.value()))?;
+                    }
+                    AttributeChange::Changed { previous, next } => {
+                        if change.is_stable_change() {
+                            writeln!(
+                                f,
+                                "  ~ {name} {} -> {}",
+                                self.display_value(name, previous.value()),
+                                self.display_value(name, next.value())
+                            )?;
+                        }
+
+                        if change.is_transient_change() {
+                            let transient = |a: &Attribute| {
+                                a.transient()
+                                    .map(|(n, v)| format!("{n} {}", self.display_value(name, v)))
+                                    .unwrap_or_else(|| "none".to_string())
+                            };
+                            writeln!(
+                                f,
+                                "  ~ {name} (transient) {} -> {}",
+                                transient(previous),
+                                transient(next)
+                            )?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AttributeGraph {
+    /// Returns the changes from self to other
+    pub fn diff(&self, other: &AttributeGraph) -> GraphDiff {
+        GraphDiff::new(self, other)
+    }
+}
+
+#[test]
+fn test_graph_diff() {
+    use crate::RuntimeDispatcher;
+
+    let mut before = AttributeGraph::from(0);
+    before
+        .batch_mut(
+            r#"
+add name .text lifec
+add removed .int 1
+``` test process
+add command .text echo hello
+add code .int 0
+```
+"#,
+        )
+        .expect("valid");
+
+    let mut after = before.clone();
+    after
+        .batch_mut(
+            r#"
+add name .text lifec2
+find_remove removed
+``` test process
+add code .int 1
+add stdout .text hello
+edit command command .text echo world
+```
+``` test println
+add message .text done
+```
+"#,
+        )
+        .expect("valid");
+
+    let diff = before.diff(&after);
+    assert_eq!(diff.removed().count(), 1);
+    assert!(diff.changed().any(|(p, n)| p.name() == "code" && *n.value() == Value::Int(1)));
+    assert!(diff
+        .changes()
+        .iter()
+        .any(|c| c.name() == "command" && c.is_transient_change() && !c.is_stable_change()));
+
+    let blocks = diff
+        .by_block()
+        .keys()
+        .filter_map(|id| diff.block(*id))
+        .collect::<Vec<_>>();
+    assert!(blocks.contains(&("test", "process")));
+    assert!(blocks.contains(&("test", "println")));
+
+    // Applying the patch to the previous graph results in the next graph
+    let mut patched = before.clone();
+    patched.batch_mut(diff.to_patch().expect("patch")).expect("valid patch");
+
+    let remaining = patched.diff(&after);
+    assert!(
+        remaining.changes().iter().all(|c| !GraphDiff::is_patchable(c.attribute())),
+        "{}",
+        remaining
+    );
+}
+
+#[test]
+fn test_graph_diff_patch_sensitive() {
+    let mut before = AttributeGraph::from(0);
+    before.with_text("user", "admin");
+
+    let mut after = before.clone();
+    after.with_text("password", "hunter2").with_text("user", "root");
+    after.mark_sensitive("password");
+
+    let patch = before.diff(&after).to_patch().expect("patch");
+    assert!(!patch.contains("hunter2"), "{patch}");
+    assert!(patch.contains("add user .text root"));
+    // The sensitive define is kept, so the patched graph still masks the value
+    assert!(patch.contains("add password::sensitive"), "{patch}");
+
+    after.with_text("notes", "line 1\nline 2");
+    assert_eq!(
+        before.diff(&after).to_patch(),
+        Err(PatchErrors::MultilineText("notes".to_string()))
+    );
+}
@@ -0,0 +1,365 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use imgui::{Key, Ui};
+use tracing::{event, Level};
+
+use crate::{RuntimeDispatcher, RuntimeState};
+
+use super::AttributeGraph;
+
+/// Step in a history, the state before the message was dispatched
+///
+#[derive(Debug, Clone)]
+pub struct Step<S> {
+    message: String,
+    state: S,
+}
+
+impl<S> Step<S> {
+    /// Returns the message that was dispatched
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the state before the message was dispatched
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+}
+
+/// Bounded undo/redo history of a state,
+///
+/// Each step records the dispatched message, and a clone of the state before the message was dispatched, which is the
+/// same clone that `RuntimeDispatcher::dispatch` makes for every message. When the history is full, the oldest step is dropped.
+///
+/// Named checkpoints are kept until they are removed, and are not bounded by the capacity.
+///
+/// ```ignore
+/// let mut history = History::default();
+/// history.dispatch(&mut graph, "add name .text lifec")?;
+/// history.checkpoint("named", &graph);
+/// history.dispatch(&mut graph, "find_remove name")?;
+///
+/// history.undo(&mut graph);       // Some("find_remove name")
+/// history.restore("named", &mut graph);
+/// ```
+///
+#[derive(Debug, Clone)]
+pub struct History<S> {
+    undo: VecDeque<Step<S>>,
+    redo: Vec<Step<S>>,
+    checkpoints: BTreeMap<String, S>,
+    capacity: usize,
+    /// True if the last step was recorded w/ `amend`, and can still be amended
+    amending: bool,
+}
+
+impl<S> Default for History<S> {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl<S> History<S> {
+    /// Default number of steps kept
+    pub const DEFAULT_CAPACITY: usize = 100;
+
+    /// Returns a new history that keeps up to capacity steps
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            undo: VecDeque::default(),
+            redo: vec![],
+            checkpoints: BTreeMap::default(),
+            capacity,
+            amending: false,
+        }
+    }
+
+    /// Returns true if there is a step to undo
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Returns true if there is a step to redo
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Returns an iterator over steps that can be undone, oldest first
+    pub fn steps(&self) -> impl Iterator<Item = &Step<S>> {
+        self.undo.iter()
+    }
+
+    /// Returns an iterator over the messages of steps that can be undone, oldest first
+    pub fn messages(&self) -> impl Iterator<Item = &str> {
+        self.undo.iter().map(|s| s.message())
+    }
+
+    /// Returns an iterator over checkpoint names
+    pub fn checkpoints(&self) -> impl Iterator<Item = &String> {
+        self.checkpoints.keys()
+    }
+
+    /// Removes a checkpoint, returns the checkpoint state if it existed
+    pub fn remove_checkpoint(&mut self, name: impl AsRef<str>) -> Option<S> {
+        self.checkpoints.remove(name.as_ref())
+    }
+
+    /// Clears all steps, checkpoints are kept
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.amending = false;
+    }
+
+    /// Ends the current amended step, so that the next edit is recorded as a new step even if it has the same message
+    pub fn seal(&mut self) {
+        self.amending = false;
+    }
+
+    /// Records a step w/ the state before the message was dispatched, this clears any steps that could be redone
+    pub fn push(&mut self, message: impl AsRef<str>, previous: S) {
+        self.redo.clear();
+        self.amending = false;
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        while self.undo.len() >= self.capacity {
+            self.undo.pop_front();
+        }
+
+        self.undo.push_back(Step {
+            message: message.as_ref().to_string(),
+            state: previous,
+        });
+    }
+
+    /// Records a step, unless the step directly before it was also amended w/ the same message, so that continuous edits,
+    /// i.e. typing into an input, are undone together
+    ///
+    /// Any other step, an undo or redo, or `seal` in between ends the amended step.
+    ///
+    pub fn amend(&mut self, message: impl AsRef<str>, previous: S) {
+        match self.undo.back() {
+            Some(last) if self.amending && last.message == message.as_ref() => {
+                self.redo.clear();
+            }
+            _ => {
+                self.push(message, previous);
+                self.amending = true;
+            }
+        }
+    }
+
+    /// Reverts state to before the last step, returns the message of the step that was undone
+    pub fn undo(&mut self, state: &mut S) -> Option<String> {
+        let Step { message, state: previous } = self.undo.pop_back()?;
+        self.amending = false;
+
+        self.redo.push(Step {
+            message: message.to_string(),
+            state: std::mem::replace(state, previous),
+        });
+
+        event!(Level::TRACE, "undo {message}");
+        Some(message)
+    }
+
+    /// Re-applies the last step that was undone, returns the message of the step
+    pub fn redo(&mut self, state: &mut S) -> Option<String> {
+        let Step { message, state: next } = self.redo.pop()?;
+        self.amending = false;
+
+        self.undo.push_back(Step {
+            message: message.to_string(),
+            state: std::mem::replace(state, next),
+        });
+
+        event!(Level::TRACE, "redo {message}");
+        Some(message)
+    }
+}
+
+impl<S> History<S>
+where
+    S: Clone,
+{
+    /// Calls apply w/ state, if apply returns an error the state is reverted, otherwise the step is recorded w/ message
+    pub fn apply<E>(
+        &mut self,
+        state: &mut S,
+        message: impl AsRef<str>,
+        apply: impl FnOnce(&mut S) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let previous = state.clone();
+        match apply(state) {
+            Ok(_) => {
+                self.push(message, previous);
+                Ok(())
+            }
+            Err(err) => {
+                *state = previous;
+                Err(err)
+            }
+        }
+    }
+
+    /// Saves a named checkpoint of state, replacing any existing checkpoint w/ the same name
+    pub fn checkpoint(&mut self, name: impl AsRef<str>, state: &S) {
+        self.checkpoints
+            .insert(name.as_ref().to_string(), state.clone());
+    }
+
+    /// Restores state to a named checkpoint, restoring is recorded as a step so that it can be undone,
+    /// returns false if the checkpoint doesn't exist
+    pub fn restore(&mut self, name: impl AsRef<str>, state: &mut S) -> bool {
+        match self.checkpoints.get(name.as_ref()) {
+            Some(checkpoint) => {
+                let previous = std::mem::replace(state, checkpoint.clone());
+                self.push(format!("restore {}", name.as_ref()), previous);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<S> History<S>
+where
+    S: RuntimeState,
+{
+    /// Dispatches a message to state's dispatcher, and records the step
+    pub fn dispatch(
+        &mut self,
+        state: &mut S,
+        msg: impl AsRef<str>,
+    ) -> Result<(), <S::Dispatcher as RuntimeDispatcher>::Error> {
+        self.apply(state, msg.as_ref(), |s| s.dispatcher_mut().dispatch_mut(msg.as_ref()))
+    }
+
+    /// Dispatches each line of msgs as a step, stops at the first error
+    pub fn batch(
+        &mut self,
+        state: &mut S,
+        msgs: impl AsRef<str>,
+    ) -> Result<(), <S::Dispatcher as RuntimeDispatcher>::Error> {
+        for message in msgs
+            .as_ref()
+            .trim()
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+        {
+            self.dispatch(state, message)?;
+        }
+        Ok(())
+    }
+}
+
+impl History<AttributeGraph> {
+    /// Records an edit made from the editor, if the graph changed since previous
+    ///
+    /// The step is named after the attributes that changed, and consecutive edits to the same attributes are recorded as a single step.
+    ///
+    pub fn edit(&mut self, previous: AttributeGraph, graph: &AttributeGraph) {
+        let diff = previous.diff(graph);
+        if diff.is_empty() {
+            return;
+        }
+
+        let mut names = diff
+            .changes()
+            .iter()
+            .map(|c| c.name())
+            .collect::<Vec<_>>();
+        names.dedup();
+
+        self.amend(format!("edit {}", names.join(", ")), previous);
+    }
+
+    /// Handles undo/redo shortcuts for a graph, ctrl+z to undo, and ctrl+shift+z or ctrl+y to redo
+    ///
+    /// Returns true if the graph was changed by an undo or redo.
+    ///
+    /// Caveat: The caller decides which graph has focus, i.e. by checking that the graph's widget is hovered.
+    ///
+    pub fn edit_shortcuts(&mut self, graph: &mut AttributeGraph, ui: &Ui) -> bool {
+        let io = ui.io();
+        if !io.key_ctrl {
+            return false;
+        }
+
+        if ui.is_key_pressed_no_repeat(Key::Z) && !io.key_shift {
+            self.undo(graph).is_some()
+        } else if ui.is_key_pressed_no_repeat(Key::Y)
+            || (ui.is_key_pressed_no_repeat(Key::Z) && io.key_shift)
+        {
+            self.redo(graph).is_some()
+        } else {
+            false
+        }
+    }
+}
+
+#[test]
+fn test_history() {
+    let mut graph = AttributeGraph::from(0);
+    let mut history = History::new(2);
+
+    history
+        .dispatch(&mut graph, "add name .text lifec")
+        .expect("valid");
+    history.checkpoint("named", &graph);
+    history.batch(&mut graph, r#"
+    add count .int 1
+    add count .int 2
+    "#).expect("valid");
+
+    // Capacity is 2, so the first step was dropped
+    assert_eq!(
+        history.messages().collect::<Vec<_>>(),
+        vec!["add count .int 1", "add count .int 2"]
+    );
+
+    assert_eq!(history.undo(&mut graph), Some("add count .int 2".to_string()));
+    assert_eq!(graph.find_int("count"), Some(1));
+    assert_eq!(history.redo(&mut graph), Some("add count .int 2".to_string()));
+    assert_eq!(graph.find_int("count"), Some(2));
+
+    // Errors don't change the state or the history
+    assert!(history.dispatch(&mut graph, "not a message").is_err());
+    assert_eq!(history.messages().count(), 2);
+
+    assert!(history.restore("named", &mut graph));
+    assert_eq!(graph.find_int("count"), None);
+    assert_eq!(graph.find_text("name"), Some("lifec".to_string()));
+    assert_eq!(history.undo(&mut graph), Some("restore named".to_string()));
+    assert_eq!(graph.find_int("count"), Some(2));
+
+    // Editor edits to the same attribute are a single step
+    let mut history = History::default();
+    let previous = graph.clone();
+    graph.with_text("name", "l");
+    history.edit(previous, &graph);
+    let previous = graph.clone();
+    graph.with_text("name", "li");
+    history.edit(previous, &graph);
+    assert_eq!(history.messages().collect::<Vec<_>>(), vec!["edit name"]);
+    history.undo(&mut graph);
+    assert_eq!(graph.find_text("name"), Some("lifec".to_string()));
+
+    // Only the step directly before is amended, a sealed or interrupted edit starts a new step
+    let mut history = History::default();
+    history.amend("edit name", graph.clone());
+    history.seal();
+    history.amend("edit name", graph.clone());
+    history.push("edit count", graph.clone());
+    history.amend("edit name", graph.clone());
+    history.amend("edit name", graph.clone());
+    assert_eq!(
+        history.messages().collect::<Vec<_>>(),
+        vec!["edit name", "edit name", "edit count", "edit name"]
+    );
+}
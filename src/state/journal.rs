@@ -0,0 +1,481 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tracing::{event, Level};
+
+use crate::{Clock, RuntimeDispatcher, RuntimeState};
+
+/// Entry in a journal, a message that was dispatched and when
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Position of the entry in the journal, starting at 0
+    pub sequence: u64,
+    /// When the message was dispatched
+    pub timestamp: SystemTime,
+    /// Message that was dispatched
+    pub message: String,
+}
+
+/// Append-only journal of messages dispatched to a runtime state,
+///
+/// Since `RuntimeDispatcher::dispatch_mut` is a reducer, the state at any point can be rebuilt by replaying the journal
+/// from the initial state. To keep replay fast, a snapshot of the state is taken every N messages, and replay starts from
+/// the latest snapshot before the point in time being replayed.
+///
+/// If the journal has a path, each entry is appended to the file as a line of json, snapshots are kept in memory and rebuilt
+/// when the journal is opened. Compacting the journal saves the latest snapshot next to the journal file, and removes the
+/// entries before it, so that opening a long journal doesn't replay every entry.
+///
+/// Entries are timestamped w/ the journal's clock, a virtual clock can be used to control the timestamps.
+///
+/// ```ignore
+/// let mut journal = Journal::new(AttributeGraph::from(0)).with_path(".runmd.journal")?;
+/// journal.dispatch(&mut state, "add name .text lifec")?;
+///
+/// let state = journal.replay()?;
+/// let before = journal.replay_until(an_hour_ago)?;
+/// ```
+///
+/// Caveat: Messages are journaled as dispatched, so secrets included directly in a message are written to the journal file.
+///
+#[derive(Debug, Clone)]
+pub struct Journal<S> {
+    /// State before the first entry
+    initial: S,
+    /// Sequence of the first entry, entries before it were compacted into the initial state
+    offset: u64,
+    /// All entries, in the order they were dispatched
+    entries: Vec<JournalEntry>,
+    /// Snapshots of the state after the entry w/ sequence was dispatched
+    snapshots: BTreeMap<u64, S>,
+    /// Number of entries between snapshots, 0 disables snapshots
+    snapshot_interval: usize,
+    /// Path to the journal file
+    path: Option<PathBuf>,
+    /// Journal file, kept open between appends
+    file: JournalFile,
+    /// Clock entries are timestamped w/
+    clock: Clock,
+}
+
+/// Snapshot saved next to a journal file when the journal is compacted
+///
+#[derive(Serialize, Deserialize)]
+struct JournalSnapshot {
+    /// Sequence of the last entry included in the snapshot
+    sequence: u64,
+    /// State saved w/ `RuntimeState::save`
+    state: String,
+}
+
+/// Handle to a journal file, opened on the first append and kept open,
+///
+/// A clone of a journal opens it's own handle.
+///
+#[derive(Debug, Default)]
+struct JournalFile(Mutex<Option<File>>);
+
+impl Clone for JournalFile {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl JournalFile {
+    /// Appends an entry to the file at path, opening the file if it isn't open
+    fn append(&self, path: &Path, entry: &JournalEntry) -> io::Result<()> {
+        let mut file = self.0.lock().map_err(|_| io::Error::new(io::ErrorKind::Other, "journal file lock poisoned"))?;
+        if file.is_none() {
+            *file = Some(OpenOptions::new().append(true).create(true).open(path)?);
+        }
+
+        match file.as_mut() {
+            Some(file) => write_entry(file, entry),
+            None => Ok(()),
+        }
+    }
+
+    /// Closes the file, so that the next append reopens it, i.e. after the file is replaced
+    fn close(&self) {
+        if let Ok(mut file) = self.0.lock() {
+            *file = None;
+        }
+    }
+}
+
+fn write_entry(file: &mut File, entry: &JournalEntry) -> io::Result<()> {
+    let line = serde_json::to_string(entry)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writeln!(file, "{line}")
+}
+
+/// Returns a path next to path, w/ extension appended
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut sibling = path.as_os_str().to_os_string();
+    sibling.push(format!(".{extension}"));
+    PathBuf::from(sibling)
+}
+
+impl<S> Journal<S>
+where
+    S: RuntimeState,
+{
+    /// Default number of entries between snapshots
+    pub const DEFAULT_SNAPSHOT_INTERVAL: usize = 100;
+
+    /// Returns a new empty journal, that starts from initial
+    pub fn new(initial: S) -> Self {
+        Self {
+            initial,
+            offset: 0,
+            entries: vec![],
+            snapshots: BTreeMap::default(),
+            snapshot_interval: Self::DEFAULT_SNAPSHOT_INTERVAL,
+            path: None,
+            file: JournalFile::default(),
+            clock: Clock::default(),
+        }
+    }
+
+    /// Sets the clock entries are timestamped w/, by default the system clock
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets the number of entries between snapshots, 0 disables snapshots
+    pub fn with_snapshot_interval(mut self, snapshot_interval: usize) -> Self {
+        self.snapshot_interval = snapshot_interval;
+        self
+    }
+
+    /// Sets the path of the journal file, if the file already exists it's entries are loaded into this journal,
+    /// otherwise the file is created w/ the entries of this journal
+    ///
+    /// If the journal was compacted, replay starts from the snapshot saved next to the journal file.
+    ///
+    /// Returns an error if the file already exists and this journal has entries, since they would be lost.
+    ///
+    pub fn with_path(mut self, path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        if path.exists() && (!self.entries.is_empty() || self.offset > 0) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} already exists, and the journal has {} entries that are not in the file", path, self.offset as usize + self.entries.len()),
+            ));
+        } else if path.exists() {
+            if let Some((sequence, initial)) = self.read_snapshot(&path)? {
+                self.initial = initial;
+                self.offset = sequence + 1;
+            }
+
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let entry = serde_json::from_str::<JournalEntry>(&line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+                // Compacting can stop after the snapshot is saved, but before the file is rewritten
+                if entry.sequence >= self.offset {
+                    self.entries.push(entry);
+                }
+            }
+
+            self.rebuild_snapshots();
+        } else {
+            if self.offset > 0 {
+                Self::write_snapshot(&path, self.offset - 1, &self.initial)?;
+            }
+
+            let mut file = File::create(&path)?;
+            for entry in self.entries.iter() {
+                write_entry(&mut file, entry)?;
+            }
+        }
+
+        self.file.close();
+        self.path = Some(path);
+        Ok(self)
+    }
+
+    /// Returns the path of the journal file
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    /// Returns all entries
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Returns the number of snapshots
+    pub fn snapshots(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Dispatches a message to state, and if successful appends the message to the journal w/ the current time of the journal's clock
+    pub fn dispatch(
+        &mut self,
+        state: &mut S,
+        msg: impl AsRef<str>,
+    ) -> Result<(), <S::Dispatcher as RuntimeDispatcher>::Error> {
+        let timestamp = SystemTime::from(self.clock.now());
+        self.dispatch_at(state, msg, timestamp)
+    }
+
+    /// Dispatches a message to state w/ a timestamp, and if successful appends the message to the journal
+    pub fn dispatch_at(
+        &mut self,
+        state: &mut S,
+        msg: impl AsRef<str>,
+        timestamp: SystemTime,
+    ) -> Result<(), <S::Dispatcher as RuntimeDispatcher>::Error> {
+        state.dispatcher_mut().dispatch_mut(msg.as_ref())?;
+
+        let entry = JournalEntry {
+            sequence: self.offset + self.entries.len() as u64,
+            timestamp,
+            message: msg.as_ref().to_string(),
+        };
+
+        if let Some(path) = self.path.as_ref() {
+            if let Err(err) = self.file.append(path, &entry) {
+                event!(Level::ERROR, "could not append to journal {:?}, {err}", path);
+            }
+        }
+
+        let sequence = entry.sequence;
+        self.entries.push(entry);
+
+        if self.snapshot_interval > 0 && (sequence + 1) % self.snapshot_interval as u64 == 0 {
+            self.snapshots.insert(sequence, state.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Dispatches each line of msgs, stops at the first error
+    pub fn batch(
+        &mut self,
+        state: &mut S,
+        msgs: impl AsRef<str>,
+    ) -> Result<(), <S::Dispatcher as RuntimeDispatcher>::Error> {
+        for message in msgs
+            .as_ref()
+            .trim()
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+        {
+            self.dispatch(state, message)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the state after replaying every entry
+    pub fn replay(&self) -> Result<S, <S::Dispatcher as RuntimeDispatcher>::Error> {
+        self.replay_while(|_| true)
+    }
+
+    /// Returns the state after replaying every entry dispatched at or before timestamp
+    pub fn replay_until(
+        &self,
+        timestamp: SystemTime,
+    ) -> Result<S, <S::Dispatcher as RuntimeDispatcher>::Error> {
+        self.replay_while(|e| e.timestamp <= timestamp)
+    }
+
+    /// Returns the state after replaying entries up to and including sequence
+    pub fn replay_to(&self, sequence: u64) -> Result<S, <S::Dispatcher as RuntimeDispatcher>::Error> {
+        self.replay_while(|e| e.sequence <= sequence)
+    }
+
+    /// Compacts the journal, by saving the latest snapshot next to the journal file, and removing the entries the snapshot
+    /// includes from the journal file and from memory, returns the number of entries that were removed
+    ///
+    /// Removed entries can't be replayed, so replaying to a point before the snapshot returns the state of the snapshot.
+    ///
+    /// Caveat: The snapshot is saved w/ `RuntimeState::save`, so sensitive values are not included in the compacted state.
+    ///
+    pub fn compact(&mut self) -> io::Result<usize> {
+        let path = match self.path.as_ref() {
+            Some(path) => path.to_path_buf(),
+            None => return Ok(0),
+        };
+
+        let (sequence, snapshot) = match self.snapshots.iter().next_back() {
+            Some((sequence, snapshot)) => (*sequence, snapshot.clone()),
+            None => return Ok(0),
+        };
+
+        // The snapshot is saved first, entries it includes are skipped when the journal is opened
+        Self::write_snapshot(&path, sequence, &snapshot)?;
+
+        let removed = (sequence + 1 - self.offset) as usize;
+        self.entries.drain(..removed);
+        self.snapshots = self.snapshots.split_off(&(sequence + 1));
+        self.initial = snapshot;
+        self.offset = sequence + 1;
+
+        let tmp = sibling_path(&path, "tmp");
+        let mut file = File::create(&tmp)?;
+        for entry in self.entries.iter() {
+            write_entry(&mut file, entry)?;
+        }
+        fs::rename(tmp, &path)?;
+
+        // The open handle refers to the replaced file
+        self.file.close();
+        Ok(removed)
+    }
+
+    /// Saves a snapshot next to the journal file at path
+    fn write_snapshot(path: &Path, sequence: u64, state: &S) -> io::Result<()> {
+        let state = state.save().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "could not save journal snapshot")
+        })?;
+        let content = serde_json::to_string(&JournalSnapshot { sequence, state })
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let tmp = sibling_path(path, "snapshot.tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(tmp, sibling_path(path, "snapshot"))
+    }
+
+    /// Reads the snapshot next to the journal file at path, if the journal was compacted
+    fn read_snapshot(&self, path: &Path) -> io::Result<Option<(u64, S)>> {
+        let snapshot_path = sibling_path(path, "snapshot");
+        if !snapshot_path.exists() {
+            return Ok(None);
+        }
+
+        let snapshot = serde_json::from_str::<JournalSnapshot>(&fs::read_to_string(snapshot_path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Some((snapshot.sequence, self.initial.load(snapshot.state))))
+    }
+
+    /// Replays entries from the latest snapshot, while the condition is true for the next entry
+    fn replay_while(
+        &self,
+        condition: impl Fn(&JournalEntry) -> bool,
+    ) -> Result<S, <S::Dispatcher as RuntimeDispatcher>::Error> {
+        // Entries are in dispatch order, so the replayable prefix ends at the first entry that doesn't match
+        let end = self
+            .entries
+            .iter()
+            .position(|e| !condition(e))
+            .unwrap_or(self.entries.len());
+
+        let (start, mut state) = match self.snapshots.range(..self.offset + end as u64).next_back() {
+            Some((sequence, snapshot)) => ((*sequence + 1 - self.offset) as usize, snapshot.clone()),
+            None => (0, self.initial.clone()),
+        };
+
+        for entry in self.entries[start..end].iter() {
+            state.dispatcher_mut().dispatch_mut(&entry.message)?;
+        }
+
+        Ok(state)
+    }
+
+    /// Rebuilds snapshots by replaying all entries
+    fn rebuild_snapshots(&mut self) {
+        self.snapshots.clear();
+        if self.snapshot_interval == 0 {
+            return;
+        }
+
+        let mut state = self.initial.clone();
+        for entry in self.entries.iter() {
+            if state.dispatcher_mut().dispatch_mut(&entry.message).is_err() {
+                event!(Level::WARN, "could not replay journal entry {}, snapshots stop here", entry.sequence);
+                return;
+            }
+
+            if (entry.sequence + 1) % self.snapshot_interval as u64 == 0 {
+                self.snapshots.insert(entry.sequence, state.clone());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_journal() {
+    use std::time::Duration;
+
+    use super::AttributeGraph;
+
+    let clock = Clock::virtual_at(Clock::from_timestamp(1_000).expect("in range"));
+    let start = SystemTime::from(clock.now());
+    let mut state = AttributeGraph::from(0);
+    let mut journal = Journal::new(state.clone())
+        .with_snapshot_interval(2)
+        .with_clock(clock.clone());
+
+    for message in [
+        "add count .int 1",
+        "add count .int 2",
+        "add count .int 3",
+        "add name .text lifec",
+        "add count .int 4",
+    ] {
+        journal.dispatch(&mut state, message).expect("valid");
+        clock.advance(Duration::from_secs(1));
+    }
+    assert!(journal.dispatch(&mut state, "not a message").is_err());
+    assert_eq!(journal.entries().len(), 5);
+    assert_eq!(journal.snapshots(), 2);
+
+    assert_eq!(journal.replay().expect("replays"), state);
+    let replayed = journal
+        .replay_until(start + Duration::from_secs(2))
+        .expect("replays");
+    assert_eq!(replayed.find_int("count"), Some(3));
+    assert_eq!(replayed.find_text("name"), None);
+    assert_eq!(journal.replay_to(0).expect("replays").find_int("count"), Some(1));
+
+    // Round-trip through a journal file
+    let dir = crate::testing::TempDir::new("journal");
+    let path = dir.join("journal");
+    let journal = journal.with_path(&path).expect("created");
+    let reopened = Journal::new(AttributeGraph::from(0))
+        .with_snapshot_interval(2)
+        .with_path(&path)
+        .expect("opened");
+    assert_eq!(reopened.entries(), journal.entries());
+    assert_eq!(reopened.snapshots(), 2);
+    assert_eq!(reopened.replay().expect("replays"), state);
+
+    // Entries are appended w/ the open handle, and a journal w/ entries can't be pointed at an existing file
+    let mut reopened = reopened;
+    let mut replayed = reopened.replay().expect("replays");
+    reopened.dispatch(&mut replayed, "add count .int 5").expect("valid");
+    reopened.dispatch(&mut replayed, "add count .int 6").expect("valid");
+    assert_eq!(fs::read_to_string(&path).expect("exists").lines().count(), 7);
+    assert!(reopened.clone().with_path(&path).is_err());
+
+    // Compacting saves the latest snapshot, and removes the entries it includes
+    assert_eq!(reopened.compact().expect("compacted"), 6);
+    assert_eq!(fs::read_to_string(&path).expect("exists").lines().count(), 1);
+    assert_eq!(reopened.replay_to(0).expect("replays").find_int("count"), Some(5));
+
+    let compacted = Journal::new(AttributeGraph::from(0))
+        .with_snapshot_interval(2)
+        .with_path(&path)
+        .expect("opened");
+    assert_eq!(compacted.entries(), reopened.entries());
+    assert_eq!(compacted.entries()[0].sequence, 6);
+    let replayed = compacted.replay().expect("replays");
+    assert_eq!(replayed.find_int("count"), Some(6));
+    assert_eq!(replayed.find_text("name"), Some("lifec".to_string()));
+}
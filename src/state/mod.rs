@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use specs::{storage::HashMapStorage, Component, Entity};
 use tracing::{event, Level};
 use std::{
-    collections::{hash_map::DefaultHasher, BTreeMap},
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet},
     fmt::Display,
     fs,
     hash::{Hash, Hasher},
@@ -15,9 +15,40 @@ use std::{
     str::from_utf8, borrow::Cow,
 };
 
+mod diff;
+pub use diff::AttributeChange;
+pub use diff::GraphDiff;
+pub use diff::PatchErrors;
+
+mod history;
+pub use history::History;
+pub use history::Step;
+
+mod journal;
+pub use journal::Journal;
+pub use journal::JournalEntry;
+
+mod binary;
+pub use binary::BinaryErrors;
+pub use binary::Encoding;
+pub use binary::BINARY_VERSION;
+
+mod content;
+pub use content::ContentHash;
+
+mod redactor;
+pub use redactor::Redactor;
+
 mod v2;
 pub use v2::AttributeIndex;
 pub use v2::Query;
+pub use v2::Predicate;
+pub use v2::QuerySubscriber;
+pub use v2::QuerySubscriptions;
+pub use v2::QueryUpdate;
+
+/// Mask used in place of sensitive values
+pub const REDACTED: &str = "****";
 
 /// Attribute graph is a component that indexes attributes for an entity
 /// It is designed to be a general purpose enough to be the common element of runtime state storage
@@ -166,16 +197,37 @@ impl AttributeGraph {
         name: impl AsRef<str>,
         symbol: impl AsRef<str>,
         message: impl AsRef<str>,
+    ) {
+        self.add_binary_message(name, symbol, message.as_ref().as_bytes());
+    }
+
+    /// adds a message w/ binary content, the content is either utf8 runmd or a binary encoded graph,
+    /// see `AttributeGraph::to_bytes`
+    pub fn add_binary_message(
+        &mut self,
+        name: impl AsRef<str>,
+        symbol: impl AsRef<str>,
+        content: impl Into<Vec<u8>>,
     ) {
         self.define(name.as_ref(), symbol.as_ref())
-            .edit_as(Value::BinaryVector(message.as_ref().as_bytes().to_vec()));
+            .edit_as(Value::BinaryVector(content.into()));
     }
 
     /// finds and applies all messages to graph
     pub fn apply(&mut self, symbol: impl AsRef<str>) {
         for (name, value) in self.take_symbol_values(symbol.as_ref()) {
             if let Value::BinaryVector(content) = value {
-                if let Some(content) = from_utf8(&content).ok() {
+                if Self::is_binary(&content) {
+                    match Self::from_bytes(&content) {
+                        Ok(graph) => {
+                            self.import_blocks(&graph);
+                            self.find_remove(name);
+                        }
+                        Err(err) => {
+                            event!(Level::ERROR, "could not decode message, {:?}", err);
+                        }
+                    }
+                } else if let Some(content) = from_utf8(&content).ok() {
 
                     match self.batch_mut(content) {
                         Ok(_) => {
@@ -233,6 +285,9 @@ impl AttributeGraph {
     }
 
     /// Returns the current hash_code of the graph
+    ///
+    /// Caveat: This hash is not stable across processes, use `content_hash` for an identity
+    ///
     pub fn hash_code(&self) -> u64 {
         let mut hasher = DefaultHasher::default();
 
@@ -334,11 +389,18 @@ impl AttributeGraph {
             let file_name = format!("{}.ron", self.hash_code());
 
             if imgui::MenuItem::new(format!("Save to {}", file_name)).build(ui) {
-                if fs::write(&file_name, self.save().unwrap_or_default()).is_ok() {
+                if fs::write(&file_name, self.save_with(Encoding::Text).unwrap_or_default()).is_ok() {
                     println!("Saved output to {}", file_name);
                 }
             }
 
+            let binary_file_name = format!("{}.lfcg", self.hash_code());
+            if imgui::MenuItem::new(format!("Save to {}", binary_file_name)).build(ui) {
+                if fs::write(&binary_file_name, self.save_with(Encoding::Binary).unwrap_or_default()).is_ok() {
+                    println!("Saved output to {}", binary_file_name);
+                }
+            }
+
             if let Some(file_source) = self
                 .clone()
                 .find_attr("src::file")
@@ -449,6 +511,8 @@ impl AttributeGraph {
                 if ui.table_next_column() {
                     if attr.id() != self.entity() {
                         ui.text(format!("imported {}", attr.id()));
+                    } else if self.is_sensitive(attr.name()) {
+                        ui.text(REDACTED);
                     } else {
                         self.edit_attr(attr.name(), attr.name(), ui);
                     }
@@ -530,6 +594,8 @@ impl AttributeGraph {
                 if ui.table_next_column() {
                     if attr.id() != self.entity() {
                         ui.text(format!("imported {}", attr.id()));
+                    } else if self.is_sensitive(attr.name()) {
+                        ui.text(REDACTED);
                     } else {
                         self.edit_attr(attr.name(), attr.name(), ui);
                     }
@@ -761,6 +827,159 @@ impl AttributeGraph {
         })
     }
 
+    /// Finds a secret that has been resolved by the `secure` plugin
+    pub fn find_secret(&self, with_name: impl AsRef<str>) -> Option<String> {
+        self.find_attr(format!("{}::secret", with_name.as_ref()))
+            .and_then(|a| a.transient())
+            .and_then(|(_, value)| {
+                if let Value::TextBuffer(secret) = value {
+                    Some(secret.to_string())
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Returns a clone of the graph w/o any resolved secrets, used before the graph leaves the runtime
+    pub fn without_secrets(&self) -> Self {
+        let mut clone = self.clone();
+        clone.index.retain(|_, a| !a.name().ends_with("::secret"));
+        clone
+    }
+
+    /// Marks an attribute as sensitive, values of sensitive attributes are masked by `redact` and `redacted`
+    ///
+    /// In runmd, this is the same as `define {name} sensitive`
+    pub fn mark_sensitive(&mut self, name: impl AsRef<str>) {
+        if !self.is_sensitive(name.as_ref()) {
+            self.define(name, "sensitive");
+        }
+    }
+
+    /// Returns true if the attribute is a resolved secret, or has been marked sensitive
+    ///
+    /// Defined attributes, i.e. `{name}::{symbol}` are sensitive if `{name}` is sensitive.
+    pub fn is_sensitive(&self, name: impl AsRef<str>) -> bool {
+        let name = name.as_ref();
+        if name.ends_with("::secret") {
+            return true;
+        }
+
+        let base = name.split("::").next().unwrap_or(name);
+        self.index
+            .values()
+            .any(|a| a.name().strip_suffix("::sensitive") == Some(base))
+    }
+
+    /// If the source attribute is sensitive, marks the derived attribute as sensitive as well,
+    /// returns true if the derived attribute was marked
+    pub fn derive_sensitive(&mut self, source: impl AsRef<str>, derived: impl AsRef<str>) -> bool {
+        if self.is_sensitive(source) {
+            self.mark_sensitive(derived);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the names of attributes marked sensitive
+    fn sensitive_names(&self) -> BTreeSet<&str> {
+        self.iter_attributes()
+            .filter_map(|a| a.name().strip_suffix("::sensitive"))
+            .collect()
+    }
+
+    /// Returns the text of every sensitive value in the graph
+    pub fn sensitive_values(&self) -> Vec<String> {
+        let mut values = self
+            .sensitive_bytes()
+            .into_iter()
+            .filter_map(|v| String::from_utf8(v).ok())
+            .collect::<Vec<_>>();
+
+        // Mask the longest values first, in case a value contains another
+        values.sort_by(|a, b| b.len().cmp(&a.len()));
+        values
+    }
+
+    /// Returns the bytes of every sensitive value in the graph, text is returned as utf8
+    fn sensitive_bytes(&self) -> Vec<Vec<u8>> {
+        let sensitive = self.sensitive_names();
+
+        let bytes = |value: &Value| match value {
+            Value::TextBuffer(text) | Value::Symbol(text) => Some(text.as_bytes().to_vec()),
+            Value::BinaryVector(bytes) => Some(bytes.to_vec()),
+            _ => None,
+        };
+
+        let mut values = vec![];
+        for attr in self.iter_attributes().filter(|a| {
+            a.name().ends_with("::secret")
+                || sensitive.contains(a.name().split("::").next().unwrap_or_default())
+        }) {
+            values.extend(bytes(attr.value()));
+            values.extend(attr.transient().and_then(|(_, value)| bytes(value)));
+        }
+
+        values.retain(|v| !v.is_empty());
+        values
+    }
+
+    /// Returns a redactor for the sensitive values currently in the graph
+    pub fn redactor(&self) -> Redactor {
+        Redactor::new(self.sensitive_bytes())
+    }
+
+    /// Masks any sensitive values found in text, use before text leaves the graph,
+    /// i.e. status updates, tracing events, etc
+    ///
+    /// Caveat: Scans the graph on each call, use `redactor()` when redacting many lines.
+    ///
+    pub fn redact(&self, text: impl AsRef<str>) -> String {
+        self.redactor().redact(text)
+    }
+
+    /// Masks any sensitive values found in bytes, the bytes don't need to be utf8
+    pub fn redact_bytes(&self, bytes: Vec<u8>) -> Vec<u8> {
+        self.redactor().redact_bytes(bytes)
+    }
+
+    /// Returns a clone of the graph w/ resolved secrets removed, and sensitive values cleared,
+    /// use before the graph is serialized or forwarded
+    ///
+    /// Every kind of value is cleared, not only text.
+    ///
+    pub fn redacted(&self) -> Self {
+        let mut clone = self.without_secrets();
+        let names = self.sensitive_names();
+        let sensitive: Vec<String> = clone
+            .index
+            .iter()
+            .filter(|(_, a)| {
+                !a.name().ends_with("::sensitive")
+                    && names.contains(a.name().split("::").next().unwrap_or_default())
+            })
+            .map(|(key, _)| key.to_string())
+            .collect();
+
+        for key in sensitive {
+            if let Some(attr) = clone.index.get_mut(&key) {
+                *attr.value_mut() = Value::Empty;
+
+                if let Some((name, _)) = attr.transient() {
+                    let name = name.to_string();
+                    attr.edit((name, Value::Empty));
+                }
+            }
+        }
+        clone
+    }
+
+    /// Returns the redacted graph in .ron format, regardless of the graph's encoding
+    fn save_ron(&self) -> Option<String> {
+        ron::ser::to_string_pretty(&self.redacted(), PrettyConfig::new()).ok()
+    }
+
     /// Finds the mut value of an attribute by name that is owned by `self.entity`.
     pub fn find_attr_value_mut(&mut self, with_name: impl AsRef<str>) -> Option<&mut Value> {
         self.find_attr_mut(with_name)
@@ -1473,6 +1692,48 @@ fn test_attribute_graph() {
     );
 }
 
+#[test]
+fn test_attribute_graph_redaction() {
+    let mut test_graph = AttributeGraph::default();
+
+    assert!(test_graph
+        .batch_mut(
+            r#"
+    add password .text hunter2
+    define password sensitive
+    add user .text admin
+    define PASSWORD env .symbol password
+    "#
+        )
+        .is_ok());
+
+    assert!(test_graph.is_sensitive("password"));
+    assert!(!test_graph.is_sensitive("user"));
+    assert!(!test_graph.is_sensitive("PASSWORD::env"));
+
+    // env vars resolved from a sensitive value are also sensitive
+    assert!(test_graph.derive_sensitive("password", "PASSWORD"));
+    assert!(test_graph.is_sensitive("PASSWORD::env"));
+
+    assert_eq!(
+        test_graph.redact("login admin hunter2"),
+        format!("login admin {REDACTED}")
+    );
+
+    let redacted = test_graph.redacted();
+    assert_eq!(redacted.find_attr_value("password"), Some(&Value::Empty));
+
+    // Every kind of value is masked, and non-utf8 output is masked at the byte level
+    test_graph.add_int_attr("pin", 1234);
+    test_graph.mark_sensitive("pin");
+    assert_eq!(test_graph.redacted().find_attr_value("pin"), Some(&Value::Empty));
+    let mut output = vec![0xff];
+    output.extend_from_slice(b"hunter2");
+    assert!(!String::from_utf8_lossy(&test_graph.redact_bytes(output)).contains("hunter2"));
+    assert_eq!(redacted.find_text("user"), Some("admin".to_string()));
+    assert!(!test_graph.save().unwrap_or_default().contains("hunter2"));
+}
+
 impl Display for AttributeGraph {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "")
@@ -1509,14 +1770,22 @@ impl From<u32> for AttributeGraph {
 impl RuntimeState for AttributeGraph {
     type Dispatcher = Self;
 
-    /// Try to serialize self to string in .ron format.
+    /// Try to serialize self to string in .ron format, sensitive values are not saved.
+    /// 
+    /// If the graph selects the binary encoding, the binary encoded graph is saved as base64 instead.
+    /// 
     fn save(&self) -> Option<String> {
-        ron::ser::to_string_pretty(self, PrettyConfig::new()).ok()
+        match Encoding::find(self) {
+            Encoding::Text => self.save_ron(),
+            Encoding::Binary => Some(self.save_base64()),
+        }
     }
 
-    /// Try to load self from .ron formatted string.
+    /// Try to load self from .ron formatted string, or from a base64 binary encoded graph.
     fn load(&self, init: impl AsRef<str>) -> Self {
-        if let Some(state) = ron::from_str(init.as_ref()).ok() {
+        if let Some(state) = Self::load_base64(init.as_ref()) {
+            state
+        } else if let Some(state) = ron::from_str(init.as_ref()).ok() {
             state
         } else {
             Self::default()
@@ -2607,7 +2876,11 @@ pub enum AttributeGraphElements {
     /// symbol value implies that the value is of symbolic quality, 
     /// and though no explicit validations are in place, the value of the symbol
     /// should be valid in many contexts that require an identifier
+    ///
+    /// .secret parses the remaining as a reference to a vault entry, `secret::{entry}`,
+    /// the plaintext is only resolved at runtime by the `secure` plugin
     #[token(".symbol", graph_lexer::from_symbol)]
+    #[token(".secret", graph_lexer::from_secret)]
     SymbolValue(Value),
     /// empty element parses
     #[token(".empty")]
@@ -2649,6 +2922,16 @@ mod graph_lexer {
         Some(Value::Symbol(remaining))
     }
 
+    pub fn from_secret(lexer: &mut Lexer<AttributeGraphElements>) -> Option<Value> {
+        let remaining = lexer.remainder().trim().to_string();
+
+        if remaining.is_empty() {
+            None
+        } else {
+            Some(Value::Symbol(format!("secret::{remaining}")))
+        }
+    }
+
     pub fn from_string(lexer: &mut Lexer<AttributeGraphElements>) -> Option<String> {
         let mut slice = lexer.slice();
         if slice.starts_with('#') {
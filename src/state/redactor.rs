@@ -0,0 +1,75 @@
+use super::REDACTED;
+
+/// Masks the sensitive values of a graph, computed once w/ `AttributeGraph::redactor()`,
+///
+/// Use instead of `AttributeGraph::redact` when redacting many lines from the same graph, i.e. process output.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Redactor {
+    /// Sensitive values, longest first in case a value contains another
+    values: Vec<Vec<u8>>,
+}
+
+impl Redactor {
+    /// Returns a new redactor for sensitive values
+    pub fn new(values: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        let mut values = values.into_iter().filter(|v| !v.is_empty()).collect::<Vec<_>>();
+        values.sort_by(|a, b| b.len().cmp(&a.len()));
+        values.dedup();
+        Self { values }
+    }
+
+    /// Returns true if there are no values to mask
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Masks any sensitive values found in text
+    pub fn redact(&self, text: impl AsRef<str>) -> String {
+        let text = text.as_ref();
+        if self.is_empty() {
+            return text.to_string();
+        }
+
+        // Values are matched on byte boundaries of utf8 text, so the result is still utf8
+        String::from_utf8(self.redact_bytes(text.as_bytes().to_vec()))
+            .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).to_string())
+    }
+
+    /// Masks any sensitive values found in bytes, bytes don't need to be utf8
+    pub fn redact_bytes(&self, bytes: Vec<u8>) -> Vec<u8> {
+        if self.is_empty() {
+            return bytes;
+        }
+
+        let mut redacted = Vec::with_capacity(bytes.len());
+        let mut pos = 0;
+        while pos < bytes.len() {
+            match self.values.iter().find(|v| bytes[pos..].starts_with(v)) {
+                Some(value) => {
+                    redacted.extend_from_slice(REDACTED.as_bytes());
+                    pos += value.len();
+                }
+                None => {
+                    redacted.push(bytes[pos]);
+                    pos += 1;
+                }
+            }
+        }
+        redacted
+    }
+}
+
+#[test]
+fn test_redactor() {
+    let redactor = Redactor::new(vec![b"hunter2".to_vec(), b"hunter".to_vec(), vec![]]);
+    assert_eq!(redactor.redact("pw hunter2 hunter"), format!("pw {REDACTED} {REDACTED}"));
+
+    let mut bytes = vec![0xff, 0xfe];
+    bytes.extend_from_slice(b"hunter2");
+    let mut expected = vec![0xff, 0xfe];
+    expected.extend_from_slice(REDACTED.as_bytes());
+    assert_eq!(redactor.redact_bytes(bytes), expected);
+
+    assert!(Redactor::default().is_empty());
+}
@@ -3,6 +3,12 @@ pub use attribute_index::AttributeIndex;
 
 mod query;
 pub use query::Query;
+pub use query::Predicate;
+
+mod subscriptions;
+pub use subscriptions::QuerySubscriber;
+pub use subscriptions::QuerySubscriptions;
+pub use subscriptions::QueryUpdate;
 
 mod tests {
     use crate::catalog::Item;
@@ -73,6 +79,63 @@ mod tests {
         eprintln!("{:#?}", person);
     }
 
+    #[test]
+    fn test_query_predicates() {
+        use std::sync::Arc;
+        use crate::{AttributeGraph, plugins::ThunkContext, state::AttributeIndex};
+
+        let src = ThunkContext::from(
+            AttributeGraph::from(0)
+                .with_text("name", "bob")
+                .with_int("age", 99)
+                .to_owned(),
+        );
+
+        let mut person = Person::default();
+        assert!(src.query().find_int("age").where_gt(18).find_text("name").matches("b*").evaluate(&mut person));
+        assert_eq!(person.name, "bob");
+        assert_eq!(person.age, 99);
+
+        let mut person = Person::default();
+        assert!(!src.query().find_int("age").where_lt(18).find_text("name").evaluate(&mut person));
+        assert_eq!(person.name, "", "dest is not visited if the src doesn't match");
+        assert!(!src.query().find_text("name").matches("?ill").evaluate(&mut person));
+        assert!(!src.query().find_int("missing").where_ne(0).evaluate(&mut person));
+
+        // Join the `process` blocks and `runtime` blocks of each event by a shared key, to find events w/ a non-zero exit code
+        let block = |event: &str, block: &str, values: &[(&str, i32)]| {
+            let mut graph = AttributeGraph::from(0);
+            graph.with_text("event", event).with_text("block", block);
+            for (name, value) in values {
+                graph.with_int(name, *value);
+            }
+            Arc::new(ThunkContext::from(graph))
+        };
+
+        let sources = vec![
+            block("build", "process", &[("code", 0)]),
+            block("build", "runtime", &[("age", 1)]),
+            block("test", "process", &[("code", 1)]),
+            block("test", "runtime", &[("age", 2)]),
+            block("deploy", "runtime", &[("age", 3)]),
+            // Predicates are tested against every source in a group, not only the first one w/ a value
+            block("release", "process", &[("code", 0)]),
+            block("release", "retry", &[("code", 3)]),
+        ];
+
+        let query = src.query().find_int("code").where_ne(0).find_int("age");
+        let joined = query.join(sources, "event");
+        assert_eq!(joined.len(), 2);
+        assert_eq!(joined[0].0.text(), Some("release"));
+
+        let (key, group) = &joined[1];
+        assert_eq!(key.text(), Some("test"));
+
+        let mut person = Person::default();
+        assert!(query.evaluate_group(group, &mut person));
+        assert_eq!(person.age, 2);
+    }
+
     #[derive(Debug, Default)]
     struct Person {
         name: String,
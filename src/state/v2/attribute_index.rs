@@ -370,6 +370,6 @@ pub trait AttributeIndex {
     where
         Self: Sized + Clone + Default + Send + Sync + Any
     {
-        Query { src: Arc::new(self.clone()), entity_id: self.entity_id(), search_params }
+        Query { src: Arc::new(self.clone()), entity_id: self.entity_id(), search_params, predicates: vec![] }
     }
 }
\ No newline at end of file
@@ -1,10 +1,10 @@
-use std::{sync::Arc, any::Any};
+use std::{sync::Arc, any::Any, collections::BTreeMap};
 
 use atlier::system::{Attribute, Value};
 use specs::{Component, DefaultVecStorage};
 use tracing::{event, Level};
 
-use crate::{AttributeIndex, catalog::Item};
+use crate::{AttributeIndex, IndexKey, catalog::Item};
 
 /// A query is used to materialize types that implement `catalog::Item`
 /// 
@@ -15,6 +15,9 @@ use crate::{AttributeIndex, catalog::Item};
 /// When a parameter is evaluated, this query visits a destination type that implements `catalog::Item` and passes
 /// the found value.
 /// 
+/// A search parameter can be followed by predicates, i.e. `find_int("age").where_gt(18)`, in which case the source only
+/// matches if the value is found and satisfies each predicate.
+/// 
 #[derive(Component, Default, Clone, Hash, PartialEq, Eq)]
 #[storage(DefaultVecStorage)]
 pub struct Query<I> 
@@ -35,7 +38,82 @@ where
     /// 
     /// The initial value for transient values is the default value of the literal type. 
     /// 
-    pub search_params: Vec<Attribute>
+    pub search_params: Vec<Attribute>,
+
+    /// Predicates on the values of search params, by the name of the search param
+    /// 
+    pub predicates: Vec<(String, Predicate)>,
+}
+
+/// Predicate on the value found for a search parameter
+/// 
+/// Ints and floats are compared as numbers, and text and symbols are compared as text, see `IndexKey`.
+/// 
+#[derive(Debug, Clone, Hash, PartialEq, Eq)]
+pub enum Predicate {
+    Eq(IndexKey),
+    Ne(IndexKey),
+    Gt(IndexKey),
+    Ge(IndexKey),
+    Lt(IndexKey),
+    Le(IndexKey),
+    /// Glob pattern for text values, `*` matches any sequence of characters and `?` matches any single character
+    Matches(String),
+}
+
+impl Predicate {
+    /// Returns true if the value satisfies this predicate
+    /// 
+    pub fn test(&self, value: &Value) -> bool {
+        match (self, IndexKey::from_value(value)) {
+            (Predicate::Eq(expected), Some(found)) => found == *expected,
+            (Predicate::Ne(expected), Some(found)) => found != *expected,
+            (Predicate::Gt(expected), Some(found)) => Self::comparable(&found, expected) && found > *expected,
+            (Predicate::Ge(expected), Some(found)) => Self::comparable(&found, expected) && found >= *expected,
+            (Predicate::Lt(expected), Some(found)) => Self::comparable(&found, expected) && found < *expected,
+            (Predicate::Le(expected), Some(found)) => Self::comparable(&found, expected) && found <= *expected,
+            (Predicate::Matches(pattern), Some(IndexKey::Text(text))) => Self::glob(pattern, &text),
+            _ => false,
+        }
+    }
+
+    /// Values of different kinds are ordered by kind, but shouldn't satisfy a comparison
+    /// 
+    fn comparable(a: &IndexKey, b: &IndexKey) -> bool {
+        std::mem::discriminant(a) == std::mem::discriminant(b)
+    }
+
+    /// Matches text w/ a glob pattern
+    /// 
+    fn glob(pattern: &str, text: &str) -> bool {
+        let pattern = pattern.chars().collect::<Vec<_>>();
+        let text = text.chars().collect::<Vec<_>>();
+
+        let (mut p, mut t) = (0, 0);
+        let mut backtrack = None;
+        while t < text.len() {
+            match pattern.get(p) {
+                Some('*') => {
+                    backtrack = Some((p, t));
+                    p += 1;
+                }
+                Some(c) if *c == '?' || *c == text[t] => {
+                    p += 1;
+                    t += 1;
+                }
+                _ => match backtrack {
+                    Some((star, matched)) => {
+                        p = star + 1;
+                        t = matched + 1;
+                        backtrack = Some((star, matched + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        pattern[p..].iter().all(|c| *c == '*')
+    }
 }
 
 impl<I> Query<I>
@@ -45,20 +123,81 @@ where
     /// Evaluates the current search parameters, by looping through each transient attribute
     /// and a value is found, visits the dest item
     /// 
-    pub fn evaluate(&self, dest: &mut impl Item) {
+    /// Returns true if the src matched the query's predicates, if the src doesn't match the dest item is not visited
+    /// 
+    pub fn evaluate(&self, dest: &mut impl Item) -> bool {
         self.evaluate_with(self.src.clone(), dest)
     }
 
     /// Evaluates the current search parameters with a `src` that implements `AttributeIndex`
     /// 
-    pub fn evaluate_with(&self, src: Arc<I>, dest: &mut impl Item) {
-        for search in self.search_params.iter() {
-            if let Some((name, _)) = search.transient() {
-                if let Some(value) = src.find_value(name) {
-                    dest.visit(name, value);
+    pub fn evaluate_with(&self, src: Arc<I>, dest: &mut impl Item) -> bool {
+        self.evaluate_group(&[src], dest)
+    }
+
+    /// Returns true if the src matches the query's predicates
+    /// 
+    pub fn matches_with(&self, src: &I) -> bool {
+        self.matches_group([src])
+    }
+
+    /// Returns true if a group of sources matches the query's predicates, a predicate matches if any source in the
+    /// group has a value for it that passes
+    /// 
+    pub fn matches_group<'b>(&self, group: impl IntoIterator<Item = &'b I> + Clone) -> bool
+    where
+        I: 'b,
+    {
+        self.predicates.iter().all(|(name, predicate)| {
+            group
+                .clone()
+                .into_iter()
+                .filter_map(|src| src.find_value(name))
+                .any(|value| predicate.test(value))
+        })
+    }
+
+    /// Evaluates the current search parameters w/ a group of sources, each predicate is tested against every source
+    /// in the group, and each search parameter is found from the first source in the group that has a value for it
+    /// 
+    /// Returns true if the group matched the query's predicates, if the group doesn't match the dest item is not visited
+    /// 
+    pub fn evaluate_group(&self, group: &[Arc<I>], dest: &mut impl Item) -> bool {
+        let find = |name: &str| group.iter().find_map(|src| src.find_value(name));
+
+        let matched = self.matches_group(group.iter().map(|src| &**src));
+
+        if matched {
+            for search in self.search_params.iter() {
+                if let Some((name, _)) = search.transient() {
+                    if let Some(value) = find(name) {
+                        dest.visit(name, value);
+                    }
                 }
             }
         }
+
+        matched
+    }
+
+    /// Groups sources by the value of a shared key attribute, and returns the groups that match this query's predicates
+    /// 
+    /// Within a group, each predicate is tested against every source that has a value for it, so predicates can span
+    /// several sources, i.e. the `process` block and the `runtime` block of the same event. Use `evaluate_group` to
+    /// materialize an item from a returned group.
+    /// 
+    pub fn join(&self, sources: impl IntoIterator<Item = Arc<I>>, key: impl AsRef<str>) -> Vec<(IndexKey, Vec<Arc<I>>)> {
+        let mut groups = BTreeMap::<IndexKey, Vec<Arc<I>>>::default();
+        for src in sources {
+            if let Some(key) = src.find_value(key.as_ref()).and_then(IndexKey::from_value) {
+                groups.entry(key).or_default().push(src);
+            }
+        }
+
+        groups
+            .into_iter()
+            .filter(|(_, group)| self.matches_group(group.iter().map(|src| &**src)))
+            .collect()
     }
 
     /// Visits the dest item w/ the current cached values stored in the search parameters
@@ -184,6 +323,63 @@ where
         self
     }
 
+    /// The last search parameter must equal value
+    /// 
+    pub fn where_eq(self, value: impl Into<IndexKey>) -> Self {
+        self.with_predicate(Predicate::Eq(value.into()))
+    }
+
+    /// The last search parameter must not equal value
+    /// 
+    pub fn where_ne(self, value: impl Into<IndexKey>) -> Self {
+        self.with_predicate(Predicate::Ne(value.into()))
+    }
+
+    /// The last search parameter must be greater than value
+    /// 
+    pub fn where_gt(self, value: impl Into<IndexKey>) -> Self {
+        self.with_predicate(Predicate::Gt(value.into()))
+    }
+
+    /// The last search parameter must be greater than or equal to value
+    /// 
+    pub fn where_ge(self, value: impl Into<IndexKey>) -> Self {
+        self.with_predicate(Predicate::Ge(value.into()))
+    }
+
+    /// The last search parameter must be less than value
+    /// 
+    pub fn where_lt(self, value: impl Into<IndexKey>) -> Self {
+        self.with_predicate(Predicate::Lt(value.into()))
+    }
+
+    /// The last search parameter must be less than or equal to value
+    /// 
+    pub fn where_le(self, value: impl Into<IndexKey>) -> Self {
+        self.with_predicate(Predicate::Le(value.into()))
+    }
+
+    /// The last search parameter must be text that matches a glob pattern, i.e. `prefix*`
+    /// 
+    pub fn matches(self, pattern: impl AsRef<str>) -> Self {
+        self.with_predicate(Predicate::Matches(pattern.as_ref().to_string()))
+    }
+
+    /// Adds a predicate for the last search parameter
+    /// 
+    fn with_predicate(mut self, predicate: Predicate) -> Self {
+        match self.search_params.last().and_then(|s| s.transient()) {
+            Some((name, _)) => {
+                let name = name.to_string();
+                self.predicates.push((name, predicate));
+            }
+            None => {
+                event!(Level::WARN, "predicate {:?} was added before a search parameter, ignoring", predicate);
+            }
+        }
+        self
+    }
+
     /// Add's a transient attribute search parameter
     /// 
     fn add_attribute(&mut self, name: impl AsRef<str>, initial_transient: Value) {
@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use atlier::system::Value;
+use specs::prelude::*;
+use tokio::sync::broadcast::{self, error::TryRecvError};
+use tokio::sync::mpsc;
+use tracing::{event, Level};
+
+use crate::plugins::{EventRuntime, ThunkContext};
+use crate::Query;
+
+/// Notification sent to a query subscription when the values found by the query have changed
+///
+#[derive(Debug, Clone)]
+pub struct QueryUpdate {
+    /// Entity whose context was updated
+    pub entity: Entity,
+    /// Id of the subscription
+    pub subscription: u64,
+    /// Search parameters whose value changed, w/ the new value
+    pub changed: Vec<(String, Value)>,
+    /// Query w/ the new values cached, use `Query::cached` to visit an item w/ the new values
+    pub query: Query<ThunkContext>,
+}
+
+/// Where updates for a subscription are delivered
+enum Notify {
+    Channel(mpsc::UnboundedSender<QueryUpdate>),
+    Callback(Box<dyn Fn(&QueryUpdate) + Send + Sync>),
+}
+
+/// Resource that tracks queries subscribed to an entity's `ThunkContext`,
+///
+/// When the event runtime inserts a new context for the entity, each subscribed query is re-evaluated against the
+/// new context, and if any of the values it finds has changed, the subscription is notified w/ the changed values.
+/// This replaces polling a cached query every frame.
+///
+/// ```ignore
+/// let query = context.query().find_int("code").find_text("stdout");
+/// let mut updates = world.write_resource::<QuerySubscriptions>().subscribe(entity, query);
+/// // ... each frame
+/// while let Ok(update) = updates.try_recv() {
+///     update.query.cached(&mut dashboard);
+/// }
+/// ```
+///
+#[derive(Default)]
+pub struct QuerySubscriptions {
+    next_id: u64,
+    subscriptions: HashMap<Entity, Vec<(u64, Query<ThunkContext>, Notify)>>,
+}
+
+impl QuerySubscriptions {
+    /// Subscribes a query to an entity's context, returns a receiver for updates
+    ///
+    pub fn subscribe(
+        &mut self,
+        entity: Entity,
+        query: Query<ThunkContext>,
+    ) -> mpsc::UnboundedReceiver<QueryUpdate> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.add(entity, query, Notify::Channel(tx));
+        rx
+    }
+
+    /// Subscribes a query to an entity's context, and calls callback w/ each update, returns the id of the subscription
+    ///
+    pub fn subscribe_with(
+        &mut self,
+        entity: Entity,
+        query: Query<ThunkContext>,
+        callback: impl Fn(&QueryUpdate) + Send + Sync + 'static,
+    ) -> u64 {
+        self.add(entity, query, Notify::Callback(Box::new(callback)))
+    }
+
+    /// Removes a subscription, returns true if the subscription existed
+    ///
+    pub fn unsubscribe(&mut self, id: u64) -> bool {
+        let mut removed = false;
+        for subscriptions in self.subscriptions.values_mut() {
+            let before = subscriptions.len();
+            subscriptions.retain(|(subscription, ..)| *subscription != id);
+            removed |= before != subscriptions.len();
+        }
+        removed
+    }
+
+    /// Returns the number of subscriptions for an entity
+    ///
+    pub fn count(&self, entity: Entity) -> usize {
+        self.subscriptions
+            .get(&entity)
+            .map(|s| s.len())
+            .unwrap_or_default()
+    }
+
+    /// Re-evaluates each query subscribed to entity w/ the updated context, and notifies subscriptions
+    /// whose values changed
+    ///
+    pub fn update(&mut self, entity: Entity, context: &ThunkContext) {
+        if let Some(subscriptions) = self.subscriptions.get_mut(&entity) {
+            let src = Arc::new(context.clone());
+
+            subscriptions.retain_mut(|(id, query, notify)| {
+                let previous = query.clone();
+                query.src = src.clone();
+                query.cache_with(&src);
+
+                let changed = query
+                    .search_params
+                    .iter()
+                    .zip(previous.search_params.iter())
+                    .filter(|(next, previous)| next.value() != previous.value())
+                    .map(|(next, _)| (next.name().to_string(), next.value().clone()))
+                    .collect::<Vec<_>>();
+
+                if changed.is_empty() {
+                    return true;
+                }
+
+                let update = QueryUpdate {
+                    entity,
+                    subscription: *id,
+                    changed,
+                    query: query.clone(),
+                };
+
+                match notify {
+                    Notify::Channel(tx) => match tx.send(update) {
+                        Ok(_) => true,
+                        Err(_) => {
+                            event!(Level::DEBUG, "subscription {id} was dropped, removing");
+                            false
+                        }
+                    },
+                    Notify::Callback(callback) => {
+                        callback(&update);
+                        true
+                    }
+                }
+            });
+        }
+    }
+
+    /// Removes all subscriptions for an entity
+    ///
+    pub fn remove(&mut self, entity: Entity) {
+        self.subscriptions.remove(&entity);
+    }
+
+    fn add(&mut self, entity: Entity, query: Query<ThunkContext>, notify: Notify) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        // Cache the current values, so that the first notification only includes values that changed
+        self.subscriptions
+            .entry(entity)
+            .or_default()
+            .push((id, query.cache(), notify));
+        id
+    }
+}
+
+/// System that notifies `QuerySubscriptions` when the event runtime broadcasts that it inserted a new context for an entity
+///
+#[derive(Default)]
+pub struct QuerySubscriber(Option<broadcast::Receiver<Entity>>);
+
+impl<'a> System<'a> for QuerySubscriber {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, broadcast::Sender<Entity>, EventRuntime>,
+        Write<'a, QuerySubscriptions>,
+        ReadStorage<'a, ThunkContext>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+        self.0 = Some(
+            world
+                .read_resource::<broadcast::Sender<Entity>>()
+                .subscribe(),
+        );
+    }
+
+    fn run(&mut self, (entities, _, mut subscriptions, contexts): Self::SystemData) {
+        if let Some(receiver) = self.0.as_mut() {
+            loop {
+                match receiver.try_recv() {
+                    Ok(entity) => {
+                        if !entities.is_alive(entity) {
+                            subscriptions.remove(entity);
+                        } else if let Some(context) = contexts.get(entity) {
+                            subscriptions.update(entity, context);
+                        }
+                    }
+                    Err(TryRecvError::Lagged(skipped)) => {
+                        event!(Level::WARN, "query subscriptions missed {skipped} updates, re-evaluating");
+                        for (entity, context) in (&entities, &contexts).join() {
+                            subscriptions.update(entity, context);
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_query_subscriptions() {
+    use crate::{AttributeGraph, AttributeIndex, Extension};
+
+    let mut world = World::new();
+    let mut dispatcher = DispatcherBuilder::new();
+    EventRuntime::configure_app_world(&mut world);
+    EventRuntime::configure_app_systems(&mut dispatcher);
+    let mut dispatcher = dispatcher.build();
+    dispatcher.setup(&mut world);
+
+    let entity = world.entities().create();
+    let context = ThunkContext::from(AttributeGraph::from(entity.id()).with_int("code", 0).to_owned());
+    world.write_component().insert(entity, context.clone()).ok();
+    world.maintain();
+
+    let mut updates = world
+        .write_resource::<QuerySubscriptions>()
+        .subscribe(entity, context.query().find_int("code").find_text("stdout"));
+
+    let called = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = called.clone();
+    world.write_resource::<QuerySubscriptions>().subscribe_with(
+        entity,
+        context.query().find_int("code"),
+        move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        },
+    );
+
+    // Simulate the event runtime completing an event for the entity
+    let mut next = context.clone();
+    next.as_mut().with_int("code", 1).with_text("stdout", "hello");
+    world.write_component().insert(entity, next).ok();
+    world
+        .read_resource::<broadcast::Sender<Entity>>()
+        .send(entity)
+        .ok();
+    dispatcher.dispatch(&world);
+
+    let update = updates.try_recv().expect("notified");
+    assert_eq!(update.entity, entity);
+    assert_eq!(
+        update.changed,
+        vec![
+            ("code".to_string(), Value::Int(1)),
+            ("stdout".to_string(), Value::TextBuffer("hello".to_string()))
+        ]
+    );
+    assert_eq!(called.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+    // An update w/o changes doesn't notify
+    world
+        .read_resource::<broadcast::Sender<Entity>>()
+        .send(entity)
+        .ok();
+    dispatcher.dispatch(&world);
+    assert!(updates.try_recv().is_err());
+}
@@ -0,0 +1,395 @@
+use std::time::Duration;
+
+use atlier::system::Value;
+use specs::{Entity, World, WorldExt};
+use tokio::runtime::Runtime;
+use tokio::select;
+use tokio::sync::mpsc;
+
+use crate::plugins::{AsyncContext, ErrorContext, Plugin, Project, StatusUpdate, ThunkContext};
+use crate::{AttributeGraph, Clock, Random, RuntimeDispatcher};
+
+mod snapshot;
+pub use snapshot::Snapshot;
+pub use snapshot::SnapshotTest;
+
+mod temp_dir;
+pub use temp_dir::TempDir;
+
+/// Harness for testing plugins that use a `ThunkContext`, w/o building a world, runtime and channels by hand,
+///
+/// Root attributes of the runmd are the plugin's config, and blocks of the runmd are loaded as the project,
+///
+/// ```
+/// use lifec::testing::PluginHarness;
+/// use lifec::plugins::Timer;
+///
+/// let run = PluginHarness::new("add duration .int 5")
+///     .deterministic(0)
+///     .run::<Timer>();
+///
+/// run.assert_completed()
+///     .assert_no_errors()
+///     .assert_text("elapsed", "5s");
+/// ```
+///
+/// Caveat: The harness owns a tokio runtime, so it can't be used from within an async context.
+///
+pub struct PluginHarness {
+    /// Runtime plugins are run on
+    runtime: Runtime,
+    /// Entity the context is enabled w/
+    entity: Entity,
+    /// Context passed to the plugin
+    context: ThunkContext,
+    /// Project passed to the plugin
+    project: Project,
+    /// Duration to wait for the plugin to complete
+    timeout: Duration,
+}
+
+impl PluginHarness {
+    /// Default duration to wait for a plugin to complete
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// Capacity of each in-memory channel, the harness drains the channels while the plugin runs
+    pub const CHANNEL_CAPACITY: usize = 100;
+
+    /// Returns a harness w/ a context and project from runmd
+    ///
+    /// Panics if the runmd can't be interpreted
+    ///
+    pub fn new(runmd: impl AsRef<str>) -> Self {
+        let mut graph = AttributeGraph::from(0);
+        graph
+            .batch_mut(runmd.as_ref())
+            .expect("runmd should be valid");
+
+        Self::from_context(ThunkContext::from(graph.clone())).with_project(Project::from(graph))
+    }
+
+    /// Returns a harness for an existing context, w/ an empty project
+    pub fn from_context(context: ThunkContext) -> Self {
+        let world = World::new();
+        let entity = world.entities().create();
+
+        Self {
+            runtime: Runtime::new().expect("should be able to create a tokio runtime"),
+            entity,
+            context,
+            project: Project::default(),
+            timeout: Self::DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Sets the project passed to the plugin
+    pub fn with_project(mut self, project: Project) -> Self {
+        self.project = project;
+        self
+    }
+
+    /// Sets the duration to wait for the plugin to complete, before it's cancelled
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the clock the plugin reads the current time from
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.context.set_clock(clock);
+        self
+    }
+
+    /// Sets the source of randomness the plugin reads from
+    pub fn with_random(mut self, random: Random) -> Self {
+        self.context.set_random(random);
+        self
+    }
+
+    /// Sets a deterministic clock and seeded randomness, the same as `EventRuntime::enable_deterministic`
+    pub fn deterministic(self, seed: u64) -> Self {
+        self.with_clock(Clock::deterministic(Clock::epoch()))
+            .with_random(Random::seeded(seed))
+    }
+
+    /// Returns a mutable reference to the context passed to the plugin
+    pub fn context_mut(&mut self) -> &mut ThunkContext {
+        &mut self.context
+    }
+
+    /// Runs plugin P to completion, capturing everything the plugin sent while it ran
+    ///
+    /// If the plugin doesn't complete before the timeout, the plugin is cancelled and the run is marked as timed out.
+    ///
+    pub fn run<P>(&self) -> PluginRun
+    where
+        P: Plugin<ThunkContext>,
+    {
+        self.run_with(P::call_with_context)
+    }
+
+    /// Runs call to completion the same way as `run`, for plugins that aren't a type, i.e. a `Pipeline` or a `Thunk`
+    ///
+    pub fn run_with(&self, call: impl FnOnce(&mut ThunkContext) -> Option<AsyncContext>) -> PluginRun {
+        let (status_tx, mut status_rx) = mpsc::channel::<StatusUpdate>(Self::CHANNEL_CAPACITY);
+        let (dispatch_tx, mut dispatch_rx) = mpsc::channel::<AttributeGraph>(Self::CHANNEL_CAPACITY);
+        let (output_tx, mut output_rx) = mpsc::channel::<(u32, u8)>(Self::CHANNEL_CAPACITY);
+
+        let mut context = self.context.enable_async(
+            self.entity,
+            self.runtime.handle().clone(),
+            None,
+            Some(self.project.reload_source()),
+            Some(status_tx),
+            Some(dispatch_tx),
+        );
+        context.enable_output(output_tx);
+
+        let mut run = PluginRun::default();
+        let timeout = self.timeout;
+        let completed = self.runtime.block_on(async {
+            match call(&mut context) {
+                Some((mut task, cancel)) => {
+                    let deadline = tokio::time::sleep(timeout);
+                    tokio::pin!(deadline);
+
+                    loop {
+                        select! {
+                            result = &mut task => {
+                                break result.ok();
+                            }
+                            Some(update) = status_rx.recv() => {
+                                run.status_updates.push(update);
+                            }
+                            Some(graph) = dispatch_rx.recv() => {
+                                run.dispatched.push(graph);
+                            }
+                            Some((_, byte)) = output_rx.recv() => {
+                                run.output.push(byte);
+                            }
+                            _ = &mut deadline => {
+                                cancel.send(()).ok();
+                                task.abort();
+                                run.timed_out = true;
+                                break None;
+                            }
+                        }
+                    }
+                }
+                // Plugins that don't start a task, update the context in place
+                None => Some(context.clone()),
+            }
+        });
+        run.context = completed;
+
+        while let Ok(update) = status_rx.try_recv() {
+            run.status_updates.push(update);
+        }
+        while let Ok(graph) = dispatch_rx.try_recv() {
+            run.dispatched.push(graph);
+        }
+        while let Ok((_, byte)) = output_rx.try_recv() {
+            run.output.push(byte);
+        }
+
+        if let Some(errors) = run.context.as_ref().and_then(ThunkContext::get_errors) {
+            run.errors.push(errors);
+        }
+
+        run
+    }
+}
+
+/// Everything a plugin sent while it ran w/ a `PluginHarness`, and the context it returned
+///
+#[derive(Default)]
+pub struct PluginRun {
+    /// Context the plugin returned, None if the plugin timed out or panicked
+    pub context: Option<ThunkContext>,
+    /// True if the plugin was cancelled because it didn't complete before the timeout
+    pub timed_out: bool,
+    /// Status updates, in the order they were sent
+    pub status_updates: Vec<StatusUpdate>,
+    /// Graphs dispatched by the plugin
+    pub dispatched: Vec<AttributeGraph>,
+    /// Bytes sent to the char device
+    pub output: Vec<u8>,
+    /// Errors from the returned context
+    pub errors: Vec<ErrorContext>,
+}
+
+impl PluginRun {
+    /// Returns the context the plugin returned,
+    ///
+    /// Panics if the plugin didn't complete
+    ///
+    pub fn context(&self) -> &ThunkContext {
+        self.assert_completed();
+        self.context.as_ref().expect("checked above")
+    }
+
+    /// Returns the project of the returned context
+    pub fn project(&self) -> Option<&Project> {
+        self.context.as_ref().and_then(|c| c.project.as_ref())
+    }
+
+    /// Returns the graph for a block in the project of the returned context
+    pub fn block(&self, block_name: impl AsRef<str>, block_symbol: impl AsRef<str>) -> Option<AttributeGraph> {
+        self.project()
+            .and_then(|p| p.find_block(block_name))
+            .and_then(|b| b.get_block(block_symbol))
+    }
+
+    /// Returns the bytes sent to the char device as text
+    pub fn output_text(&self) -> String {
+        String::from_utf8_lossy(&self.output).to_string()
+    }
+
+    /// Asserts that the plugin completed before the timeout, w/o panicking
+    pub fn assert_completed(&self) -> &Self {
+        assert!(!self.timed_out, "plugin did not complete before the timeout");
+        assert!(self.context.is_some(), "plugin did not return a context");
+        self
+    }
+
+    /// Asserts that the returned context doesn't have any errors
+    pub fn assert_no_errors(&self) -> &Self {
+        let errors = self.errors.iter().flat_map(ErrorContext::errors).collect::<Vec<_>>();
+        assert!(errors.is_empty(), "expected no errors, found {:?}", errors);
+        self
+    }
+
+    /// Asserts that the returned context has an error w/ name
+    pub fn assert_error(&self, name: impl AsRef<str>) -> &Self {
+        let errors = self.errors.iter().flat_map(ErrorContext::errors).collect::<Vec<_>>();
+        assert!(
+            errors.iter().any(|(n, _)| n == name.as_ref()),
+            "expected error {}, found {:?}",
+            name.as_ref(),
+            errors
+        );
+        self
+    }
+
+    /// Asserts that a status update was sent w/ a message that contains text
+    pub fn assert_status(&self, text: impl AsRef<str>) -> &Self {
+        assert!(
+            self.status_updates.iter().any(|(_, _, m)| m.contains(text.as_ref())),
+            "expected a status update containing {:?}",
+            text.as_ref()
+        );
+        self
+    }
+
+    /// Asserts that the bytes sent to the char device equal expected
+    pub fn assert_output(&self, expected: impl AsRef<str>) -> &Self {
+        assert_eq!(self.output_text(), expected.as_ref());
+        self
+    }
+
+    /// Asserts the value of an attribute in the returned context
+    pub fn assert_value(&self, name: impl AsRef<str>, expected: Value) -> &Self {
+        assert_value(self.context().as_ref(), name, expected);
+        self
+    }
+
+    /// Asserts the value of a text attribute in the returned context
+    pub fn assert_text(&self, name: impl AsRef<str>, expected: impl AsRef<str>) -> &Self {
+        self.assert_value(name, Value::TextBuffer(expected.as_ref().to_string()))
+    }
+
+    /// Asserts the value of an int attribute in the returned context
+    pub fn assert_int(&self, name: impl AsRef<str>, expected: i32) -> &Self {
+        self.assert_value(name, Value::Int(expected))
+    }
+
+    /// Asserts the value of a bool attribute in the returned context
+    pub fn assert_bool(&self, name: impl AsRef<str>, expected: bool) -> &Self {
+        self.assert_value(name, Value::Bool(expected))
+    }
+
+    /// Asserts that the project of the returned context has a block, and returns the block's graph
+    pub fn assert_block(&self, block_name: impl AsRef<str>, block_symbol: impl AsRef<str>) -> AttributeGraph {
+        self.block(&block_name, &block_symbol).unwrap_or_else(|| {
+            panic!(
+                "expected block {} {} in the project",
+                block_name.as_ref(),
+                block_symbol.as_ref()
+            )
+        })
+    }
+
+    /// Asserts the value of an attribute of a block in the project of the returned context
+    pub fn assert_block_value(
+        &self,
+        block_name: impl AsRef<str>,
+        block_symbol: impl AsRef<str>,
+        name: impl AsRef<str>,
+        expected: Value,
+    ) -> &Self {
+        assert_value(&self.assert_block(block_name, block_symbol), name, expected);
+        self
+    }
+
+    /// Asserts the value of a text attribute of a block in the project of the returned context
+    pub fn assert_block_text(
+        &self,
+        block_name: impl AsRef<str>,
+        block_symbol: impl AsRef<str>,
+        name: impl AsRef<str>,
+        expected: impl AsRef<str>,
+    ) -> &Self {
+        self.assert_block_value(
+            block_name,
+            block_symbol,
+            name,
+            Value::TextBuffer(expected.as_ref().to_string()),
+        )
+    }
+
+    /// Asserts the value of an int attribute of a block in the project of the returned context
+    pub fn assert_block_int(
+        &self,
+        block_name: impl AsRef<str>,
+        block_symbol: impl AsRef<str>,
+        name: impl AsRef<str>,
+        expected: i32,
+    ) -> &Self {
+        self.assert_block_value(block_name, block_symbol, name, Value::Int(expected))
+    }
+}
+
+fn assert_value(graph: &AttributeGraph, name: impl AsRef<str>, expected: Value) {
+    assert_eq!(
+        graph.find_attr_value(&name),
+        Some(&expected),
+        "unexpected value for {}",
+        name.as_ref()
+    );
+}
+
+#[test]
+fn test_plugin_harness() {
+    use crate::plugins::{Process, Timer};
+
+    let run = PluginHarness::new(
+        r#"
+    add command .text echo hello
+    "#,
+    )
+    .deterministic(0)
+    .run::<Process>();
+
+    run.assert_completed()
+        .assert_no_errors()
+        .assert_output("hello\n")
+        .assert_status("# Running")
+        .assert_block_int("echo", "process", "code", 0)
+        .assert_block_text("echo", "process", "timestamp_utc", "1970-01-01 00:00:00 UTC");
+
+    let run = PluginHarness::new("add duration .int 60")
+        .with_timeout(Duration::from_millis(100))
+        .run::<Timer>();
+    assert!(run.timed_out);
+    assert!(run.context.is_none());
+}
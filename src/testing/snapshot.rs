@@ -0,0 +1,431 @@
+use std::collections::BTreeMap;
+use std::fmt::{Error, Write};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use atlier::system::{Extension, Value};
+use specs::{Join, WorldExt};
+
+use crate::editor::{Call, RuntimeEditor};
+use crate::plugins::{BlockContext, Engine, Event, EventRuntime, Expect, Plugin, Process, Project, Remote, ThunkContext};
+use crate::{AttributeGraph, Runtime};
+
+/// Golden-file snapshot tests for the call sequences of a project,
+///
+/// A call sequence is a test if it has an `expect_output` block. Each test runs headless, w/ a deterministic clock,
+/// seeded randomness, and stubbed processes, and the resulting contexts are transpiled and compared to the snapshot
+/// checked-in next to the project, i.e. `demo.runmd` -> `demo.snap.runmd`,
+///
+/// ````runmd
+/// ``` demo call
+/// define a_timer   timer   .symbol wait
+/// define b_process process .symbol echo
+/// ```
+///
+/// ``` demo expect_output
+/// add seed        .int 7
+/// add timeout_ms  .int 5000
+/// define echo     stdout .text hello
+/// define elapsed  ignore .enable
+/// ```
+/// ````
+///
+/// `stdout`/`stderr` defines are the output of the stubbed process or remote w/ that block name, and attributes w/ an
+/// `ignore` define are left out of the snapshot. `timestamp_local` is always ignored, since it depends on the time zone.
+///
+/// Caveat: `expect` blocks w/ `version` or `kernel` checks start a process that can't be stubbed, and `which` or `disk` checks depend
+/// on the machine running the test, so a test that calls one fails w/o running.
+///
+pub struct Snapshot {
+    /// Path to the project
+    project_src: PathBuf,
+    /// Project w/ the call sequences to test
+    project: Project,
+}
+
+/// Result of a snapshot test
+///
+#[derive(Debug, Clone)]
+pub struct SnapshotTest {
+    /// Name of the call sequence
+    pub name: String,
+    /// Output from the checked-in snapshot, None if the snapshot doesn't have this test
+    pub expected: Option<String>,
+    /// Output from running the call sequence
+    pub actual: String,
+    /// True if the call sequence didn't complete before the timeout
+    pub timed_out: bool,
+    /// Events w/ checks that can't be stubbed, if not empty the call sequence isn't run
+    pub unstubbed: Vec<String>,
+}
+
+impl SnapshotTest {
+    /// Returns true if the call sequence completed, and the output matches the snapshot
+    pub fn passed(&self) -> bool {
+        !self.timed_out && self.unstubbed.is_empty() && self.expected.as_ref() == Some(&self.actual)
+    }
+}
+
+impl Snapshot {
+    /// Default duration to wait for a call sequence to complete
+    pub const DEFAULT_TIMEOUT_MS: i32 = 10_000;
+
+    /// Attributes that are always left out of the snapshot
+    pub const ALWAYS_IGNORE: [&'static str; 1] = ["timestamp_local"];
+
+    /// `expect` checks that can't be stubbed, since they start a process or depend on the machine running the test
+    pub const UNSTUBBED_CHECKS: [&'static str; 4] = ["version", "kernel", "which", "disk"];
+
+    /// Loads a project to test, returns None if the project can't be loaded
+    pub fn load(project_src: impl AsRef<Path>) -> Option<Self> {
+        let project_src = project_src.as_ref().to_path_buf();
+        Project::load_file(project_src.to_str()?).map(|project| Self {
+            project_src,
+            project,
+        })
+    }
+
+    /// Returns the path to the snapshot, next to the project
+    pub fn snapshot_src(&self) -> PathBuf {
+        self.project_src.with_extension("snap.runmd")
+    }
+
+    /// Returns the names of call sequences w/ an `expect_output` block
+    pub fn tests(&self) -> Vec<String> {
+        self.project
+            .iter_block()
+            .filter(|(_, block)| {
+                block.get_block(Call::event_name()).is_some() && block.get_block("expect_output").is_some()
+            })
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Runs each test and compares the output w/ the checked-in snapshot
+    pub fn run(&self) -> Vec<SnapshotTest> {
+        let mut expected = fs::read_to_string(self.snapshot_src())
+            .map(|snapshot| Self::sections(&snapshot))
+            .unwrap_or_default();
+
+        self.tests()
+            .into_iter()
+            .map(|name| {
+                let (actual, timed_out, unstubbed) = self.run_test(&name);
+                SnapshotTest {
+                    expected: expected.remove(&name),
+                    name,
+                    actual,
+                    timed_out,
+                    unstubbed,
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the output of tests to the snapshot, accepting the changes, tests that didn't run or timed out keep their checked-in output
+    pub fn update(&self, tests: &[SnapshotTest]) -> std::io::Result<()> {
+        let snapshot = tests
+            .iter()
+            .map(|t| {
+                if t.timed_out || !t.unstubbed.is_empty() {
+                    t.expected.as_deref().unwrap_or_default()
+                } else {
+                    t.actual.as_str()
+                }
+            })
+            .collect::<String>();
+        fs::write(self.snapshot_src(), snapshot)
+    }
+
+    /// Runs a call sequence until all of it's events are idle, returns the output, true if the sequence timed out, and the events
+    /// that can't be stubbed, in which case the sequence isn't run
+    fn run_test(&self, name: &str) -> (String, bool, Vec<String>) {
+        let expect_output = self
+            .project
+            .find_block(name)
+            .and_then(|b| b.get_block("expect_output"))
+            .unwrap_or_default();
+        let seed = expect_output.find_int("seed").unwrap_or_default();
+        let timeout_ms = expect_output.find_int("timeout_ms").unwrap_or(Self::DEFAULT_TIMEOUT_MS);
+
+        let mut ignore = Self::ALWAYS_IGNORE.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+        for (attr, _) in expect_output.find_symbol_values("ignore") {
+            ignore.push(attr.trim_end_matches("::ignore").to_string());
+        }
+
+        let mut editor = RuntimeEditor::default();
+        *editor.project_mut() = self.stub_processes(&expect_output);
+
+        let (mut world, dispatcher) = Call::standalone::<RuntimeEditor>();
+        let mut dispatcher = dispatcher.build();
+        dispatcher.setup(&mut world);
+        EventRuntime::enable_deterministic(&mut world, seed as u64);
+
+        let mut timed_out = false;
+        if let Some(start) = editor.runtime().create_engine::<Call>(&world, name.to_string()) {
+            let unstubbed = Self::unstubbed(&world);
+            if !unstubbed.is_empty() {
+                return (String::new(), false, unstubbed);
+            }
+
+            Runtime::start_event(start, &world);
+
+            let deadline = Instant::now() + Duration::from_millis(timeout_ms as u64);
+            loop {
+                dispatcher.dispatch(&world);
+                editor.on_run(&world);
+                world.maintain();
+                editor.on_maintain(&mut world);
+
+                if world.read_component::<Event>().join().all(Event::is_idle) {
+                    break;
+                }
+
+                if Instant::now() > deadline {
+                    timed_out = true;
+                    break;
+                }
+            }
+        }
+
+        let mut output = String::new();
+        self.transpile_output(&mut output, name, &world).ok();
+
+        let output = output
+            .lines()
+            .filter(|line| match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+                ["add", attr, ..] | ["define", attr, ..] => !ignore.iter().any(|i| i.as_str() == *attr),
+                _ => true,
+            })
+            .map(|line| format!("{line}\n"))
+            .collect();
+
+        (output, timed_out, vec![])
+    }
+
+    /// Returns the events w/ checks that can't be stubbed, i.e. `expect` w/ `version` or `which` checks, see `Snapshot::UNSTUBBED_CHECKS`
+    fn unstubbed(world: &specs::World) -> Vec<String> {
+        let events = world.read_component::<Event>();
+        let contexts = world.read_component::<ThunkContext>();
+        (&events, &contexts)
+            .join()
+            .filter(|(event, tc)| {
+                event.plugin_symbol() == Expect::symbol()
+                    && Self::UNSTUBBED_CHECKS
+                        .iter()
+                        .any(|check| !tc.as_ref().find_symbol_values(check).is_empty())
+            })
+            .map(|(event, tc)| format!("{} {}", tc.block.block_name, event.plugin_symbol()))
+            .collect()
+    }
+
+    /// Returns the project w/ each process and remote block stubbed, so that tests don't start any processes
+    fn stub_processes(&self, expect_output: &AttributeGraph) -> Project {
+        let mut project = self.project.clone();
+
+        for (block_name, block) in project.iter_block_mut() {
+            let find_output = |symbol: &str| {
+                expect_output
+                    .find_symbol_values(symbol)
+                    .into_iter()
+                    .find_map(|(name, value)| match value {
+                        Value::TextBuffer(output) if name == format!("{block_name}::{symbol}") => Some(output),
+                        _ => None,
+                    })
+            };
+            let stdout = find_output("stdout");
+            let stderr = find_output("stderr");
+
+            for symbol in [Process::symbol(), Remote::symbol()] {
+                block.update_block(symbol, |process| {
+                    process.add_bool_attr("stub", true);
+                    if let Some(stdout) = stdout.as_ref() {
+                        process.add_text_attr("stub_stdout", stdout);
+                    }
+                    if let Some(stderr) = stderr.as_ref() {
+                        process.add_text_attr("stub_stderr", stderr);
+                    }
+                });
+            }
+        }
+
+        project
+    }
+
+    /// Transpiles the context of each event, followed by the blocks each event changed in it's project
+    fn transpile_output(&self, src: &mut String, name: &str, world: &specs::World) -> Result<(), Error> {
+        writeln!(src, "``` {name} snapshot")?;
+        writeln!(src, "```")?;
+
+        let events = world.read_component::<Event>();
+        let contexts = world.read_component::<ThunkContext>();
+        for (event, tc) in (&events, &contexts).join() {
+            writeln!(src)?;
+            writeln!(src, "``` {} {}", tc.block.block_name, event.plugin_symbol())?;
+            let graph = tc.as_ref().redacted();
+            for attr in graph.iter_attributes().filter(|a| a.id() == graph.entity()) {
+                if attr.name().starts_with("block_") || attr.name().ends_with("::secret") {
+                    continue;
+                }
+
+                if attr.is_stable() {
+                    BlockContext::transpile_value(src, "add", attr.name(), attr.value())?;
+                } else if let (Some((a, b)), Some((_, value))) = (attr.name().split_once("::"), attr.transient()) {
+                    BlockContext::transpile_value(src, "define", format!("{a} {b}"), value)?;
+                }
+            }
+            writeln!(src, "```")?;
+
+            for (block_name, block) in tc.project.iter().flat_map(Project::iter_block) {
+                let changed = block.transpile()?;
+                let original = self.project.find_block(block_name).map(|b| b.transpile()).transpose()?;
+                if original.as_ref() != Some(&changed) {
+                    writeln!(src)?;
+                    write!(src, "{}", changed)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Splits a snapshot into the output of each test
+    fn sections(snapshot: &str) -> BTreeMap<String, String> {
+        let mut sections = BTreeMap::<String, String>::default();
+        let mut current = None;
+
+        for line in snapshot.lines() {
+            if let Some(name) = line
+                .strip_prefix("``` ")
+                .and_then(|header| header.strip_suffix(" snapshot"))
+            {
+                current = Some(name.trim().to_string());
+            }
+
+            if let Some(current) = current.as_ref() {
+                let section = sections.entry(current.to_string()).or_default();
+                section.push_str(line);
+                section.push('\n');
+            }
+        }
+
+        sections
+    }
+}
+
+#[test]
+fn test_snapshot() {
+    let dir = super::TempDir::new("snapshot");
+    let project_src = dir.join("project.runmd");
+    let watched = dir.join("watched");
+    fs::create_dir(&watched).expect("can create dir");
+    fs::write(
+        &project_src,
+        format!(
+            r#"
+``` demo call
+define a_timer   timer   .symbol wait
+define b_process process .symbol echo
+define c_remote  remote  .symbol shell
+```
+
+``` wait timer
+add duration .int 2
+```
+
+``` echo process
+add command .text program_that_does_not_exist
+```
+
+``` shell remote
+add command .text program_that_does_not_exist
+```
+
+``` demo expect_output
+add seed .int 7
+define echo stdout .text hello
+define shell stdout .text world
+```
+
+``` probe call
+define a_expect expect .symbol env
+```
+
+``` env expect
+define cargo version .text >=1.60
+```
+
+``` probe expect_output
+add seed .int 7
+```
+
+``` slow call
+define a_watch watch .symbol changes
+```
+
+``` changes watch
+add watch_path .text {dir}
+```
+
+``` slow expect_output
+add timeout_ms .int 200
+```
+
+``` where call
+define a_expect expect .symbol paths
+```
+
+``` paths expect
+define cargo which .text cargo
+define src disk .int 1
+```
+
+``` where expect_output
+add seed .int 7
+```
+"#,
+            dir = watched.display()
+        ),
+    )
+    .expect("can write project");
+
+    // The checked-in output of a test that times out is kept when the snapshot is updated
+    let slow_expected = "``` slow snapshot\n```\n";
+
+    let snapshot = Snapshot::load(&project_src).expect("loaded");
+    assert_eq!(snapshot.tests(), vec!["demo", "probe", "slow", "where"]);
+    fs::write(snapshot.snapshot_src(), slow_expected).expect("can write snapshot");
+
+    let tests = snapshot.run();
+    assert_eq!(tests.len(), 4);
+    assert!(!tests[0].timed_out);
+    assert!(tests[0].expected.is_none());
+    assert!(!tests[0].passed());
+    // The process and remote are stubbed, so the command doesn't need to exist
+    assert!(tests[0].actual.contains("add stub_stdout .text hello"));
+    assert!(tests[0].actual.contains("add stub_stdout .text world"));
+    assert!(tests[0].actual.contains("add timestamp_utc .text 1970-01-01 00:00:02 UTC"));
+    assert!(!tests[0].actual.contains("timestamp_local"));
+
+    // The version check would start a process, so the test fails w/o running
+    assert_eq!(tests[1].unstubbed.len(), 1);
+    assert!(tests[1].actual.is_empty());
+    assert!(!tests[1].passed());
+
+    // The watch never sees a change, so the test times out
+    assert!(tests[2].timed_out);
+    assert_eq!(tests[2].expected.as_deref(), Some(slow_expected));
+    assert!(!tests[2].passed());
+
+    // `which` and `disk` checks depend on the machine running the test
+    assert_eq!(tests[3].unstubbed.len(), 1);
+    assert!(!tests[3].passed());
+
+    snapshot.update(&tests).expect("can write snapshot");
+    let tests = snapshot.run();
+    assert!(tests[0].passed(), "{:#?}", tests);
+    assert!(!tests[1].passed());
+    assert_eq!(tests[2].expected.as_deref(), Some(slow_expected));
+    assert!(tests[3].expected.is_none());
+}
@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+
+/// Temporary directory for tests, the directory and everything in it is removed when the guard is dropped,
+///
+/// Since the guard is dropped while unwinding, the directory is also removed when an assertion fails,
+///
+/// ```
+/// use lifec::testing::TempDir;
+///
+/// let dir = TempDir::new("example");
+/// std::fs::write(dir.join("hello.txt"), "hello").expect("can write");
+///
+/// let path = dir.path().to_path_buf();
+/// drop(dir);
+/// assert!(!path.exists());
+/// ```
+///
+pub struct TempDir {
+    /// Path to the directory
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates a new empty directory under the system temp dir, w/ name as part of the directory name
+    ///
+    /// Panics if the directory can't be created
+    ///
+    pub fn new(name: impl AsRef<str>) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "lifec_test_{}_{}_{}",
+            name.as_ref(),
+            std::process::id(),
+            rand::random::<u32>()
+        ));
+
+        std::fs::create_dir_all(&path).expect("should be able to create a temp dir");
+        Self { path }
+    }
+
+    /// Returns the path to the directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns a path inside of the directory
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.path.join(path)
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.path).ok();
+    }
+}
@@ -0,0 +1,347 @@
+use std::fmt::Debug;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+use serde_json::{json, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Trace exporter is a tracing layer that appends closed spans to a file as OTLP-compatible JSON lines,
+///
+/// Each line of the file is an OTLP/JSON document, the same format as the file exporter of the OpenTelemetry
+/// collector, so the file can be loaded into any trace viewer that accepts OTLP/JSON, i.e. Jaeger. The event runtime
+/// creates a span for each event it starts, nested under a span for the sequence and the `runtime` world
+/// the event is part of, so the file includes sequence-level timing.
+///
+/// ```ignore
+/// use tracing_subscriber::prelude::*;
+///
+/// let exporter = TraceExporter::new("lifec_trace.json");
+/// tracing_subscriber::registry().with(exporter.clone()).init();
+/// // ... run
+/// exporter.flush().ok();
+/// ```
+///
+/// Caveat: Closed spans are buffered and appended to the file when a root span closes, or when the buffer
+/// has `max_spans`. Spans that are still open are not written until they close.
+///
+#[derive(Clone)]
+pub struct TraceExporter {
+    /// Path to the file spans are written to
+    path: PathBuf,
+    /// Closed spans that haven't been written yet, in OTLP/JSON form
+    spans: Arc<Mutex<Vec<Value>>>,
+    /// Number of closed spans to buffer before they are written
+    max_spans: usize,
+}
+
+/// Stored in a span's extensions, while the span is open
+struct SpanRecord {
+    trace_id: String,
+    span_id: String,
+    parent_span_id: Option<String>,
+    start: SystemTime,
+    attributes: Vec<Value>,
+    events: Vec<Value>,
+}
+
+/// Collects span fields and event fields as OTLP key values
+#[derive(Default)]
+struct KeyValues {
+    message: Option<String>,
+    attributes: Vec<Value>,
+}
+
+impl TraceExporter {
+    /// Default number of closed spans to buffer before they are written
+    pub const DEFAULT_MAX_SPANS: usize = 1000;
+
+    /// Returns a new exporter that writes to path
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            spans: Arc::new(Mutex::new(vec![])),
+            max_spans: Self::DEFAULT_MAX_SPANS,
+        }
+    }
+
+    /// Sets the number of closed spans to buffer before they are written
+    pub fn with_max_spans(mut self, max_spans: usize) -> Self {
+        self.max_spans = max_spans;
+        self
+    }
+
+    /// Returns the path this exporter writes to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns closed spans that haven't been written yet as an OTLP/JSON document
+    pub fn to_json(&self) -> Value {
+        let spans = self
+            .spans
+            .lock()
+            .map(|s| s.clone())
+            .unwrap_or_default();
+
+        Self::document(spans)
+    }
+
+    fn document(spans: Vec<Value>) -> Value {
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [ Self::key_value("service.name", json!({ "stringValue": "lifec" })) ]
+                },
+                "scopeSpans": [{
+                    "scope": { "name": "lifec" },
+                    "spans": spans
+                }]
+            }]
+        })
+    }
+
+    /// Appends closed spans that haven't been written yet to the file, as a single line
+    pub fn flush(&self) -> std::io::Result<()> {
+        let spans = match self.spans.lock() {
+            Ok(mut spans) if !spans.is_empty() => std::mem::take(&mut *spans),
+            _ => return Ok(()),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let mut line = serde_json::to_string(&Self::document(spans))?;
+        line.push('\n');
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    fn key_value(key: &str, value: Value) -> Value {
+        json!({ "key": key, "value": value })
+    }
+
+    fn random_id<const LEN: usize>() -> String {
+        let mut bytes = [0; LEN];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn unix_nanos(time: SystemTime) -> String {
+        time.duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+impl Visit for KeyValues {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.attributes.push(TraceExporter::key_value(
+            field.name(),
+            json!({ "intValue": value.to_string() }),
+        ));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.attributes.push(TraceExporter::key_value(
+            field.name(),
+            json!({ "intValue": value.to_string() }),
+        ));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.attributes.push(TraceExporter::key_value(
+            field.name(),
+            json!({ "boolValue": value }),
+        ));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.attributes.push(TraceExporter::key_value(
+                field.name(),
+                json!({ "stringValue": value }),
+            ));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+impl<S> Layer<S> for TraceExporter
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let parent = span.parent().and_then(|parent| {
+                parent
+                    .extensions()
+                    .get::<SpanRecord>()
+                    .map(|p| (p.trace_id.to_string(), p.span_id.to_string()))
+            });
+
+            let (trace_id, parent_span_id) = match parent {
+                Some((trace_id, parent_span_id)) => (trace_id, Some(parent_span_id)),
+                None => (Self::random_id::<16>(), None),
+            };
+
+            let mut key_values = KeyValues::default();
+            attrs.record(&mut key_values);
+
+            span.extensions_mut().insert(SpanRecord {
+                trace_id,
+                span_id: Self::random_id::<8>(),
+                parent_span_id,
+                start: SystemTime::now(),
+                attributes: key_values.attributes,
+                events: vec![],
+            });
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(record) = span.extensions_mut().get_mut::<SpanRecord>() {
+                let mut key_values = KeyValues::default();
+                values.record(&mut key_values);
+                record.attributes.append(&mut key_values.attributes);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.event_span(event) {
+            if let Some(record) = span.extensions_mut().get_mut::<SpanRecord>() {
+                let mut key_values = KeyValues::default();
+                event.record(&mut key_values);
+
+                let mut attributes = key_values.attributes;
+                attributes.push(Self::key_value(
+                    "level",
+                    json!({ "stringValue": event.metadata().level().to_string() }),
+                ));
+
+                record.events.push(json!({
+                    "timeUnixNano": Self::unix_nanos(SystemTime::now()),
+                    "name": key_values.message.unwrap_or(event.metadata().name().to_string()),
+                    "attributes": attributes,
+                }));
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            if let Some(record) = span.extensions_mut().remove::<SpanRecord>() {
+                let is_root = record.parent_span_id.is_none();
+
+                let span = json!({
+                    "traceId": record.trace_id,
+                    "spanId": record.span_id,
+                    "parentSpanId": record.parent_span_id.unwrap_or_default(),
+                    "name": span.name(),
+                    "kind": 1,
+                    "startTimeUnixNano": Self::unix_nanos(record.start),
+                    "endTimeUnixNano": Self::unix_nanos(SystemTime::now()),
+                    "attributes": record.attributes,
+                    "events": record.events,
+                });
+
+                let is_full = match self.spans.lock() {
+                    Ok(mut spans) => {
+                        spans.push(span);
+                        spans.len() >= self.max_spans
+                    }
+                    Err(_) => false,
+                };
+
+                if is_root || is_full {
+                    if let Err(err) = self.flush() {
+                        eprintln!("could not write trace file {:?}, {err}", self.path);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_trace_exporter() {
+    use tracing::{info_span, event, Level};
+    use tracing_subscriber::prelude::*;
+
+    let dir = crate::testing::TempDir::new("trace");
+    let path = dir.join("trace.json");
+    let exporter = TraceExporter::new(&path);
+    let subscriber = tracing_subscriber::registry().with(exporter.clone());
+
+    tracing::subscriber::with_default(subscriber, || {
+        let sequence = info_span!("sequence", entity = 1);
+        let _sequence = sequence.enter();
+        let event = info_span!("event", entity = 1, plugin = "println");
+        let _event = event.enter();
+        event!(Level::INFO, "hello");
+    });
+
+    let read_lines = || {
+        std::fs::read_to_string(&path)
+            .expect("written")
+            .lines()
+            .map(|l| serde_json::from_str::<Value>(l).expect("valid json"))
+            .collect::<Vec<_>>()
+    };
+
+    let lines = read_lines();
+    assert_eq!(lines.len(), 1);
+    let spans = lines[0]["resourceSpans"][0]["scopeSpans"][0]["spans"]
+        .as_array()
+        .expect("spans");
+    assert_eq!(spans.len(), 2);
+
+    let (event, sequence) = (&spans[0], &spans[1]);
+    assert_eq!(event["name"], "event");
+    assert_eq!(sequence["name"], "sequence");
+    assert_eq!(event["traceId"], sequence["traceId"]);
+    assert_eq!(event["parentSpanId"], sequence["spanId"]);
+    assert_eq!(event["events"][0]["name"], "hello");
+    assert_eq!(event["attributes"][1]["value"]["stringValue"], "println");
+
+    // Written spans are drained from the buffer, and each root span appends a line
+    assert!(exporter.to_json()["resourceSpans"][0]["scopeSpans"][0]["spans"]
+        .as_array()
+        .expect("spans")
+        .is_empty());
+
+    // Spans are also written once the buffer is full, before the root span closes
+    let subscriber = tracing_subscriber::registry().with(exporter.clone().with_max_spans(1));
+    tracing::subscriber::with_default(subscriber, || {
+        let sequence = info_span!("sequence", entity = 2);
+        let _sequence = sequence.enter();
+        let event = info_span!("event", entity = 2, plugin = "println");
+        let _event = event.enter();
+    });
+
+    let lines = read_lines();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[1]["resourceSpans"][0]["scopeSpans"][0]["spans"][0]["name"], "event");
+    assert_eq!(lines[2]["resourceSpans"][0]["scopeSpans"][0]["spans"][0]["name"], "sequence");
+}